@@ -0,0 +1,225 @@
+use crate::breach::HashList;
+use crate::command::NewEntry;
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::expiry::{self, DAY};
+use crate::strength::{self, MAX_SCORE};
+use crate::time;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+// Passwords shorter than this after normalizing are only compared exactly
+const MIN_SIMILAR_LENGTH: usize = 4;
+
+// Reports about the decrypted entries of the vault. Passwords themselves never appear in
+// a report.
+pub struct Report {
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Output {
+    pub fn from_name(name: &str) -> Option<Output> {
+        match name {
+            "table" => Some(Output::Table),
+            "json" => Some(Output::Json),
+            "csv" => Some(Output::Csv),
+            _ => None,
+        }
+    }
+}
+
+impl Report {
+    // Array of objects keyed by the column names in snake case
+    pub fn to_json(&self) -> String {
+        let rows: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| {
+                        (
+                            column.to_lowercase().replace(' ', "_"),
+                            Value::from(value.as_str()),
+                        )
+                    })
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&rows).unwrap()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&self.columns).unwrap();
+        for row in &self.rows {
+            writer.write_record(row).unwrap();
+        }
+
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+}
+
+// Every entry with its strength score, the weakest first
+pub fn strength(entries: &[NewEntry]) -> Report {
+    let mut scored: Vec<(&NewEntry, strength::Estimate)> = entries
+        .iter()
+        .map(|entry| {
+            let estimate = strength::estimate(&entry.password, &[&entry.domain, &entry.username]);
+            (entry, estimate)
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        a.1.guesses_log10
+            .total_cmp(&b.1.guesses_log10)
+            .then(a.0.domain.cmp(&b.0.domain))
+    });
+
+    let rows = scored
+        .into_iter()
+        .map(|(entry, estimate)| {
+            vec![
+                entry.domain.clone(),
+                entry.username.clone(),
+                format!("{}/{}", estimate.score, MAX_SCORE),
+                estimate.feedback().join(" "),
+            ]
+        })
+        .collect();
+
+    Report {
+        columns: vec!["Domain", "Username", "Score", "Feedback"],
+        rows,
+    }
+}
+
+// Entries that share a password, or use passwords that differ only in case or in digits
+// and symbols at the end, like "summer1" and "Summer2!". Entries of a group are listed next
+// to each other.
+pub fn reuse(entries: &[NewEntry]) -> Report {
+    let mut groups: BTreeMap<String, Vec<&NewEntry>> = BTreeMap::new();
+    for entry in entries {
+        groups
+            .entry(similarity_key(&entry.password))
+            .or_default()
+            .push(entry);
+    }
+
+    let shared = groups.into_values().filter(|members| members.len() > 1);
+
+    let mut rows = Vec::new();
+    for (index, members) in shared.enumerate() {
+        let group = (index + 1).to_string();
+        for entry in &members {
+            let identical = members
+                .iter()
+                .any(|other| other.domain != entry.domain && other.password == entry.password);
+
+            rows.push(vec![
+                group.clone(),
+                entry.domain.clone(),
+                entry.username.clone(),
+                String::from(if identical { "identical" } else { "similar" }),
+            ]);
+        }
+    }
+
+    Report {
+        columns: vec!["Group", "Domain", "Username", "Match"],
+        rows,
+    }
+}
+
+// Entries whose password appears in the Pwned Passwords list, the most breached first
+pub fn breached<R: Read + Seek>(
+    entries: &[NewEntry],
+    list: &mut HashList<R>,
+) -> Result<Report, String> {
+    let mut found = Vec::new();
+    for entry in entries {
+        if let Some(count) = list.count(&entry.password)? {
+            found.push((entry, count));
+        }
+    }
+    found.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.domain.cmp(&b.0.domain)));
+
+    let rows = found
+        .into_iter()
+        .map(|(entry, count)| {
+            vec![
+                entry.domain.clone(),
+                entry.username.clone(),
+                count.to_string(),
+            ]
+        })
+        .collect();
+
+    Ok(Report {
+        columns: vec!["Domain", "Username", "Breaches"],
+        rows,
+    })
+}
+
+// Entries past their rotation date, the longest overdue first
+pub fn stale(index_set: &IndexSet, now: u64) -> Report {
+    let mut expired: Vec<(&str, u64, u64)> = index_set
+        .data
+        .iter()
+        .filter_map(|entry| {
+            let expires = expiry::expires_at(entry).filter(|time| *time <= now)?;
+            Some((entry.key.as_str(), entry.modified, expires))
+        })
+        .collect();
+    expired.sort_by(|a, b| a.2.cmp(&b.2).then(a.0.cmp(b.0)));
+
+    let date = |secs: u64| time::format_time(secs)[..10].to_string();
+    let rows = expired
+        .into_iter()
+        .map(|(domain, modified, expires)| match modified {
+            0 => vec![
+                domain.to_string(),
+                String::from("unknown"),
+                String::from("unknown"),
+                String::from("unknown"),
+            ],
+            _ => vec![
+                domain.to_string(),
+                date(modified),
+                date(expires),
+                ((now - expires) / DAY).to_string(),
+            ],
+        })
+        .collect();
+
+    Report {
+        columns: vec!["Domain", "Changed", "Expired", "Days overdue"],
+        rows,
+    }
+}
+
+// Passwords with the same key are reported together
+fn similarity_key(password: &str) -> String {
+    let base = password
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation())
+        .to_lowercase();
+
+    match base.chars().count() >= MIN_SIMILAR_LENGTH {
+        true => format!("similar:{}", base),
+        false => format!("exact:{}", password),
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,140 @@
+use super::*;
+use crate::breach;
+use crate::config;
+use crate::entry::index_entry::IndexEntry;
+use crate::storage;
+
+fn entry(domain: &str, password: &str) -> NewEntry {
+    NewEntry {
+        domain: String::from(domain),
+        username: String::from("john"),
+        password: String::from(password),
+        url: String::new(),
+        tags: Vec::new(),
+        folder: String::new(),
+    }
+}
+
+#[test]
+fn test_strength() {
+    let entries = [
+        entry("github", "k8#Qz!v2Lp@9xW"),
+        entry("gmail", "letmein"),
+        entry("bank", "bank2024"),
+    ];
+    let report = strength(&entries);
+
+    let domains: Vec<&str> = report.rows.iter().map(|row| row[0].as_str()).collect();
+    assert_eq!(domains, vec!["gmail", "bank", "github"]);
+    assert_eq!(report.rows[0][2], "0/4");
+    assert_eq!(report.rows[2][2], "4/4");
+    assert!(report.rows[1][3].contains("domain or username"));
+
+    // Passwords are never part of the report
+    for row in &report.rows {
+        assert!(!row
+            .iter()
+            .any(|value| entries.iter().any(|e| value.contains(&e.password))));
+    }
+}
+
+#[test]
+fn test_reuse() {
+    let entries = [
+        entry("gmail", "Summer2023!"),
+        entry("github", "summer2024"),
+        entry("bank", "x9#Lq2!vZ"),
+        entry("shop", "Summer2023!"),
+        entry("forum", "abc1"),
+        entry("wiki", "abc2"),
+        entry("news", "abc1"),
+    ];
+    let report = reuse(&entries);
+
+    let rows: Vec<Vec<&str>> = report
+        .rows
+        .iter()
+        .map(|row| row.iter().map(|value| value.as_str()).collect())
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            vec!["1", "forum", "john", "identical"],
+            vec!["1", "news", "john", "identical"],
+            vec!["2", "gmail", "john", "identical"],
+            vec!["2", "github", "john", "similar"],
+            vec!["2", "shop", "john", "identical"],
+        ]
+    );
+}
+
+#[test]
+fn test_output() {
+    let report = Report {
+        columns: vec!["Domain", "Match"],
+        rows: vec![vec![String::from("gmail"), String::from("a, \"b\"")]],
+    };
+
+    assert_eq!(
+        serde_json::from_str::<Value>(&report.to_json()).unwrap(),
+        serde_json::json!([{ "domain": "gmail", "match": "a, \"b\"" }])
+    );
+    assert_eq!(report.to_csv(), "Domain,Match\ngmail,\"a, \"\"b\"\"\"\n");
+    assert_eq!(Output::from_name("json"), Some(Output::Json));
+    assert_eq!(Output::from_name("xml"), None);
+}
+
+#[test]
+fn test_breached() {
+    let entries = [
+        entry("gmail", "password"),
+        entry("bank", "k8#Qz!v2Lp@9xW"),
+        entry("forum", "letmein"),
+    ];
+
+    let mut lines = [
+        format!("{}:10434004", breach::sha1_hex("password")),
+        format!("{}:2", breach::sha1_hex("letmein")),
+        format!("{}:5", breach::sha1_hex("123456")),
+    ];
+    lines.sort();
+    let text = lines.join("\r\n");
+    let mut list = HashList::new(std::io::Cursor::new(text.into_bytes())).unwrap();
+
+    let report = breached(&entries, &mut list).unwrap();
+    assert_eq!(
+        report.rows,
+        vec![
+            vec!["gmail", "john", "10434004"],
+            vec!["forum", "john", "2"],
+        ]
+    );
+}
+
+#[test]
+fn test_stale() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    config::set("expiry.days", "90").unwrap();
+    let now = 1_000 * DAY;
+
+    let mut index_set = IndexSet::new();
+    for (domain, modified) in [
+        ("fresh", now - 10 * DAY),
+        ("old", now - 100 * DAY),
+        ("legacy", 0),
+    ] {
+        let mut index_entry = IndexEntry::new(domain, 0);
+        index_entry.modified = modified;
+        index_set.add(&index_entry);
+    }
+
+    let report = stale(&index_set, now);
+    assert_eq!(
+        report.rows,
+        vec![
+            vec!["legacy", "unknown", "unknown", "unknown"],
+            vec!["old", "1972-06-19", "1972-09-17", "10"],
+        ]
+    );
+}
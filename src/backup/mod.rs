@@ -0,0 +1,282 @@
+use crate::config;
+use crate::crypto::{self, NONCE_SIZE, SALT_SIZE};
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::{write_string, ByteReader};
+use crate::errs;
+use crate::storage;
+use crate::time;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Header of a backup: magic, version, creation time and reason stay readable without password
+pub const BACKUP_MAGIC: &[u8; 7] = b"DOMEBAK";
+pub const BACKUP_VERSION: u8 = 1;
+
+const EXTENSION: &str = "bak";
+const INDEX_NAME: &str = "index.bin";
+const VAULT_NAME: &str = "vault.bin";
+const CONFIG_NAME: &str = "config";
+
+// Live files of the vault and when they were taken. Config is optional, it exists only after
+// something was configured.
+pub struct Contents {
+    pub created: u64,
+    pub index: Vec<u8>,
+    pub vault: Vec<u8>,
+    pub config: Option<Vec<u8>>,
+}
+
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub created: u64,
+    pub reason: String,
+}
+
+impl Contents {
+    pub fn read_live() -> Result<Contents, String> {
+        let (vault, index) = storage::open().snapshot()?;
+
+        Ok(Contents {
+            created: time::now(),
+            index,
+            vault,
+            config: fs::read(storage::config_file_path()).ok(),
+        })
+    }
+}
+
+// Saves encrypted snapshot of the live files into the backup directory and applies retention
+pub fn create(master_password: &str, reason: &str) -> Result<PathBuf, String> {
+    let contents = Contents::read_live()?;
+    let created = contents.created;
+    let data = encode(&contents, reason, master_password);
+
+    let dir = storage::backup_dir_path();
+    fs::create_dir_all(&dir).map_err(|err| format!("Can't create {}: {}", dir.display(), err))?;
+
+    let stamp = file_stamp(created);
+    let path = (1..)
+        .map(|num| match num {
+            1 => dir.join(format!("dome-{}.{}", stamp, EXTENSION)),
+            num => dir.join(format!("dome-{}-{}.{}", stamp, num, EXTENSION)),
+        })
+        .find(|path| !path.exists())
+        .unwrap();
+
+    storage::replace_file(&path, &data)?;
+
+    prune(
+        config::get_number("backup.keep") as usize,
+        config::get_number("backup.max_age_days"),
+        created,
+    );
+
+    Ok(path)
+}
+
+pub fn encode(contents: &Contents, reason: &str, password: &str) -> Vec<u8> {
+    let mut files = vec![(INDEX_NAME, &contents.index), (VAULT_NAME, &contents.vault)];
+    if let Some(config) = &contents.config {
+        files.push((CONFIG_NAME, config));
+    }
+
+    // Every file carries its own checksum, so restore can tell which one is damaged
+    let mut payload = vec![files.len() as u8];
+    for (name, data) in files {
+        write_string(&mut payload, name);
+        payload.extend(Sha256::digest(data));
+        payload.extend((data.len() as u64).to_le_bytes());
+        payload.extend(data);
+    }
+
+    let salt = crypto::generate_salt();
+    let key = crypto::derive_key(password, &salt);
+    let (cipher_text, nonce) = crypto::encrypt_bytes(&key, &payload);
+
+    let mut data = header(contents.created, reason);
+    data.extend(salt);
+    data.extend(nonce);
+    data.extend(cipher_text);
+    data
+}
+
+// Decrypts the backup and checks that files are complete and consistent
+pub fn decode(data: &[u8], password: &str) -> Result<Contents, String> {
+    let (created, reason) = read_header(data)?;
+    let invalid = || String::from("Backup is damaged.");
+
+    let mut reader = ByteReader::new(&data[header(created, &reason).len()..]);
+    let salt = reader.read_bytes(SALT_SIZE).ok_or_else(invalid)?;
+    let nonce = reader.read_bytes(NONCE_SIZE).ok_or_else(invalid)?;
+
+    let key = crypto::derive_key(password, salt);
+    let payload = crypto::decrypt_bytes(&key, nonce, reader.read_rest())
+        .map_err(|_| String::from(errs::BACKUP_DECRYPTION_FAILED))?;
+
+    let mut reader = ByteReader::new(&payload);
+    let count = reader.read_u8().ok_or_else(invalid)?;
+    let (mut index, mut vault, mut config) = (None, None, None);
+
+    for _ in 0..count {
+        let name = reader.read_string().ok_or_else(invalid)?;
+        let checksum = reader.read_bytes(32).ok_or_else(invalid)?;
+        let len = reader.read_u64().ok_or_else(invalid)?;
+        let file = reader.read_bytes(len as usize).ok_or_else(invalid)?;
+
+        if Sha256::digest(file).as_slice() != checksum {
+            return Err(format!("Checksum of {} in the backup doesn't match.", name));
+        }
+
+        match name.as_str() {
+            INDEX_NAME => index = Some(file.to_vec()),
+            VAULT_NAME => vault = Some(file.to_vec()),
+            CONFIG_NAME => config = Some(file.to_vec()),
+            _ => {}
+        }
+    }
+
+    let contents = Contents {
+        created,
+        index: index.ok_or_else(invalid)?,
+        vault: vault.ok_or_else(invalid)?,
+        config,
+    };
+
+    verify(&contents)?;
+    Ok(contents)
+}
+
+pub fn read_header(data: &[u8]) -> Result<(u64, String), String> {
+    if !data.starts_with(BACKUP_MAGIC) {
+        return Err(String::from("File is not a Dome backup."));
+    }
+
+    let mut reader = ByteReader::new(&data[BACKUP_MAGIC.len()..]);
+    let invalid = || String::from("Backup is damaged.");
+
+    let version = reader.read_u8().ok_or_else(invalid)?;
+    if version > BACKUP_VERSION {
+        return Err(String::from(
+            "Backup was created by a newer version of Dome.",
+        ));
+    }
+
+    let created = reader.read_u64().ok_or_else(invalid)?;
+    let reason = reader.read_string().ok_or_else(invalid)?;
+    Ok((created, reason))
+}
+
+// Replaces live files with the verified contents of a backup
+pub fn restore(contents: &Contents) -> Result<(), String> {
+    storage::open().replace(&contents.vault, &contents.index)?;
+
+    match &contents.config {
+        Some(config) => storage::replace_file(&storage::config_file_path(), config),
+        None => Ok(()),
+    }
+}
+
+// Backups in the backup directory, oldest first
+pub fn list() -> Vec<Snapshot> {
+    let entries = match fs::read_dir(storage::backup_dir_path()) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|path| {
+            let data = fs::read(&path).ok()?;
+            let (created, reason) = read_header(&data).ok()?;
+            Some(Snapshot {
+                path,
+                created,
+                reason,
+            })
+        })
+        .collect();
+
+    // "dome-<time>-2.bak" was created after "dome-<time>.bak"
+    snapshots.sort_by_key(|s| (s.created, s.path.as_os_str().len(), s.path.clone()));
+    snapshots
+}
+
+// Keeps `keep` newest backups and deletes the ones older than `max_age_days`.
+// The newest backup is never deleted. Zero disables the rule.
+pub fn prune(keep: usize, max_age_days: u64, now: u64) -> Vec<PathBuf> {
+    let snapshots = list();
+    let mut removed = Vec::new();
+
+    for (position, snapshot) in snapshots.iter().rev().enumerate() {
+        if position == 0 {
+            continue;
+        }
+
+        let too_many = keep > 0 && position >= keep;
+        let too_old =
+            max_age_days > 0 && now.saturating_sub(snapshot.created) > max_age_days * 24 * 3600;
+
+        if (too_many || too_old) && fs::remove_file(&snapshot.path).is_ok() {
+            removed.push(snapshot.path.clone());
+        }
+    }
+
+    removed
+}
+
+// Name of a backup in the backup directory, or path to any backup file
+pub fn resolve(name: &str) -> PathBuf {
+    let in_dir = storage::backup_dir_path().join(name);
+    if !Path::new(name).exists() && in_dir.exists() {
+        return in_dir;
+    }
+
+    PathBuf::from(name)
+}
+
+// Index must be readable and every record it points to must be inside the vault
+fn verify(contents: &Contents) -> Result<(), String> {
+    let index_set = IndexSet::try_from_binary(&contents.index)
+        .map_err(|err| format!("Backup can't be restored. {}", err))?;
+
+    for entry in index_set.data {
+        if !record_fits(&contents.vault, entry.value) {
+            return Err(format!(
+                "Record of {} is missing from the vault in the backup.",
+                entry.key
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn record_fits(vault: &[u8], offset: u64) -> bool {
+    vault
+        .get(offset as usize..)
+        .and_then(storage::record_size)
+        .is_some()
+}
+
+fn header(created: u64, reason: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend(BACKUP_MAGIC);
+    data.push(BACKUP_VERSION);
+    data.extend(created.to_le_bytes());
+    write_string(&mut data, reason);
+    data
+}
+
+// "20261019-031500"
+fn file_stamp(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = time::utc(secs);
+    format!(
+        "{}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+#[cfg(test)]
+mod test;
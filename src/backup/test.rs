@@ -0,0 +1,147 @@
+use super::*;
+use crate::command::{self, NewEntry};
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+
+fn add(domain: &str) {
+    let entry = NewEntry {
+        domain: String::from(domain),
+        username: String::from("john"),
+        password: String::from("s3cret"),
+        url: String::new(),
+        tags: Vec::new(),
+        folder: String::new(),
+    };
+    command::add_entry(&entry, MASTER_PASSWORD).unwrap();
+}
+
+fn live_at(created: u64) -> Contents {
+    Contents {
+        created,
+        ..Contents::read_live().unwrap()
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    add("gmail");
+
+    let live = live_at(1_000);
+    let data = encode(&live, "manual", MASTER_PASSWORD);
+    let contents = decode(&data, MASTER_PASSWORD).unwrap();
+
+    assert_eq!(read_header(&data).unwrap(), (1_000, String::from("manual")));
+    assert_eq!(contents.created, 1_000);
+    assert_eq!(contents.index, live.index);
+    assert_eq!(contents.vault, live.vault);
+    assert!(contents.config.is_none());
+}
+
+#[test]
+fn test_wrong_password_and_tampering() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    add("gmail");
+
+    let mut data = encode(&live_at(1_000), "manual", MASTER_PASSWORD);
+    // Restore counts this error as a wrong master password
+    assert_eq!(
+        decode(&data, "wrong").err().unwrap(),
+        errs::BACKUP_DECRYPTION_FAILED
+    );
+
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    assert!(decode(&data, MASTER_PASSWORD).is_err());
+    assert_ne!(
+        decode(b"not a backup", MASTER_PASSWORD).err().unwrap(),
+        errs::BACKUP_DECRYPTION_FAILED
+    );
+}
+
+#[test]
+fn test_verify_missing_record() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    add("gmail");
+
+    let mut contents = live_at(1_000);
+    contents.vault.truncate(10);
+    let data = encode(&contents, "manual", MASTER_PASSWORD);
+
+    assert_eq!(
+        decode(&data, MASTER_PASSWORD).err().unwrap(),
+        "Record of gmail is missing from the vault in the backup."
+    );
+
+    // Index from a newer version or cut short is refused instead of read
+    let mut contents = live_at(1_000);
+    contents.index[4] += 1;
+    let data = encode(&contents, "manual", MASTER_PASSWORD);
+    assert!(decode(&data, MASTER_PASSWORD)
+        .err()
+        .unwrap()
+        .contains("newer version"));
+
+    let mut contents = live_at(1_000);
+    contents.index.pop();
+    let data = encode(&contents, "manual", MASTER_PASSWORD);
+    assert!(decode(&data, MASTER_PASSWORD)
+        .err()
+        .unwrap()
+        .contains("damaged"));
+}
+
+#[test]
+fn test_create_and_restore() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    add("gmail");
+    config::set("backup.keep", "5").unwrap();
+
+    let path = create(MASTER_PASSWORD, "manual").unwrap();
+    command::remove_entry("gmail", MASTER_PASSWORD).unwrap();
+    add("github");
+    config::set("backup.keep", "7").unwrap();
+
+    let contents = decode(&fs::read(&path).unwrap(), MASTER_PASSWORD).unwrap();
+    restore(&contents).unwrap();
+
+    let index_set = command::get_index_set().unwrap();
+    assert!(index_set.has("gmail"));
+    assert!(!index_set.has("github"));
+    assert_eq!(config::get_number("backup.keep"), 5);
+    assert_eq!(
+        command::get_entry("gmail", MASTER_PASSWORD).unwrap().1,
+        "s3cret"
+    );
+}
+
+#[test]
+fn test_retention() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    fs::create_dir_all(storage::backup_dir_path()).unwrap();
+
+    let day = 24 * 3600;
+    for num in 1..=5u64 {
+        let data = encode(&live_at(num * day), "manual", MASTER_PASSWORD);
+        let path = storage::backup_dir_path().join(format!("dome-{}.bak", num));
+        fs::write(path, data).unwrap();
+    }
+
+    assert_eq!(prune(3, 0, 5 * day).len(), 2);
+    assert_eq!(list()[0].created, 3 * day);
+
+    // Only the newest one is left when all of them are too old
+    assert_eq!(prune(0, 1, 100 * day).len(), 2);
+    assert_eq!(list().len(), 1);
+    assert_eq!(list()[0].created, 5 * day);
+}
+
+#[test]
+fn test_file_stamp() {
+    assert_eq!(file_stamp(1_792_379_725), "20261019-031525");
+}
@@ -0,0 +1,127 @@
+use sha1::{Digest, Sha1};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Pwned Passwords file of Have I Been Pwned, downloaded ordered by hash. Every line is an
+// uppercase SHA-1 hash and the number of times it was seen in breaches, like
+// "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004". Lines are found by binary search
+// over byte offsets, so even the full list is never read whole and nothing leaves the computer.
+const HASH_LENGTH: usize = 40;
+
+pub struct HashList<R: Read + Seek> {
+    reader: BufReader<R>,
+    len: u64,
+}
+
+pub fn open(path: &Path) -> Result<HashList<File>, String> {
+    let file = File::open(path).map_err(|_| format!("File {} can't be read.", path.display()))?;
+    HashList::new(file)
+}
+
+pub fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+impl<R: Read + Seek> HashList<R> {
+    pub fn new(mut source: R) -> Result<HashList<R>, String> {
+        let len = source
+            .seek(SeekFrom::End(0))
+            .map_err(|err| err.to_string())?;
+        let mut list = HashList {
+            reader: BufReader::new(source),
+            len,
+        };
+
+        // Only the first line is checked, checking the order would mean reading everything
+        if len > 0 {
+            let line = list.line_at(0)?.map(|(_, line)| line);
+            if line.as_deref().and_then(parse_line).is_none() {
+                return Err(String::from(
+                    "File is not a list of Pwned Passwords SHA-1 hashes.",
+                ));
+            }
+        }
+
+        Ok(list)
+    }
+
+    // How many times the password was seen in breaches, None if it wasn't
+    pub fn count(&mut self, password: &str) -> Result<Option<u64>, String> {
+        let target = sha1_hex(password);
+        let (mut low, mut high) = (0, self.len);
+
+        // Lines that start in low..high are left, low is always the start of a line
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let (start, line) = match self.line_at(middle)? {
+                Some((start, line)) if start < high => (start, line),
+                _ => {
+                    high = middle;
+                    continue;
+                }
+            };
+
+            let (hash, count) = parse_line(&line)
+                .ok_or_else(|| format!("Line at byte {} of the hash list is damaged.", start))?;
+
+            match hash.as_str().cmp(target.as_str()) {
+                std::cmp::Ordering::Equal => return Ok(Some(count)),
+                std::cmp::Ordering::Less => low = start + line.len() as u64,
+                std::cmp::Ordering::Greater => high = start,
+            }
+        }
+
+        Ok(None)
+    }
+
+    // First line that starts at the position or after it, with its start
+    fn line_at(&mut self, position: u64) -> Result<Option<(u64, String)>, String> {
+        let mut start = position;
+        let mut line = Vec::new();
+
+        if position > 0 {
+            self.seek(position - 1)?;
+            // Rest of the line the position falls into
+            start = position - 1 + self.read_line(&mut line)? as u64;
+            line.clear();
+        } else {
+            self.seek(0)?;
+        }
+
+        match self.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some((start, String::from_utf8_lossy(&line).into_owned()))),
+        }
+    }
+
+    fn seek(&mut self, position: u64) -> Result<(), String> {
+        self.reader
+            .seek(SeekFrom::Start(position))
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> Result<usize, String> {
+        self.reader
+            .read_until(b'\n', line)
+            .map_err(|err| err.to_string())
+    }
+}
+
+// Hash in uppercase and the count, the line may still end with a line break
+fn parse_line(line: &str) -> Option<(String, u64)> {
+    let (hash, count) = line.trim_end().split_once(':')?;
+
+    if hash.len() != HASH_LENGTH || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some((hash.to_ascii_uppercase(), count.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,73 @@
+use super::*;
+use std::io::Cursor;
+
+// Hash list with the given passwords and their counts, between hashes of other passwords
+fn hash_list(breached: &[(&str, u64)], line_end: &str) -> String {
+    let mut lines: Vec<String> = (0..200)
+        .map(|i| format!("{}:{}", sha1_hex(&format!("filler{}", i)), i + 1))
+        .chain(
+            breached
+                .iter()
+                .map(|(password, count)| format!("{}:{}", sha1_hex(password), count)),
+        )
+        .collect();
+    lines.sort();
+    lines.join(line_end) + line_end
+}
+
+#[test]
+fn test_sha1() {
+    assert_eq!(
+        sha1_hex("password"),
+        "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+    );
+}
+
+#[test]
+fn test_count() {
+    let breached = [("password", 10434004), ("123456", 37359195), ("letmein", 1)];
+
+    for line_end in ["\n", "\r\n"] {
+        let text = hash_list(&breached, line_end);
+        let mut list = HashList::new(Cursor::new(text.into_bytes())).unwrap();
+
+        for (password, count) in breached {
+            assert_eq!(list.count(password).unwrap(), Some(count));
+        }
+        for i in 0..200 {
+            assert_eq!(list.count(&format!("filler{}", i)).unwrap(), Some(i + 1));
+        }
+        assert_eq!(list.count("k8#Qz!v2Lp@9xW").unwrap(), None);
+    }
+}
+
+#[test]
+fn test_edges() {
+    // Without the last line break
+    let text = hash_list(&[("password", 3)], "\n");
+    let mut list = HashList::new(Cursor::new(text.trim_end().as_bytes().to_vec())).unwrap();
+    assert_eq!(list.count("password").unwrap(), Some(3));
+
+    let mut empty = HashList::new(Cursor::new(Vec::new())).unwrap();
+    assert_eq!(empty.count("password").unwrap(), None);
+
+    let single = format!("{}:7\n", sha1_hex("password"));
+    let mut list = HashList::new(Cursor::new(single.into_bytes())).unwrap();
+    assert_eq!(list.count("password").unwrap(), Some(7));
+    assert_eq!(list.count("other").unwrap(), None);
+}
+
+#[test]
+fn test_not_a_hash_list() {
+    assert!(HashList::new(Cursor::new(b"password\n123456\n".to_vec())).is_err());
+}
+
+#[test]
+fn test_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pwned-passwords-sha1-ordered-by-hash.txt");
+    std::fs::write(&path, hash_list(&[("letmein", 42)], "\r\n")).unwrap();
+
+    assert_eq!(open(&path).unwrap().count("letmein").unwrap(), Some(42));
+    assert!(open(&dir.path().join("missing.txt")).is_err());
+}
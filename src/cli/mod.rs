@@ -1,6 +1,8 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use cli_table::{format::Justify, print_stdout, Cell, Style, Table};
 use rpassword::read_password as get_password;
 use std::io::{self, Write};
+use std::process::{Command, Stdio};
 
 const RED: &str = "\x1b[31m";
 const YELLOW: &str = "\x1b[33m";
@@ -22,6 +24,31 @@ pub fn get_confirmation(text: &str) -> bool {
     }
 }
 
+// Prints numbered options and lets user pick one. Returns None if input is left empty.
+pub fn pick(text: &str, options: &[String]) -> Option<usize> {
+    for (index, option) in options.iter().enumerate() {
+        println!("{:>3}) {}", index + 1, option);
+    }
+
+    loop {
+        print!("{}", text);
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim();
+
+        if input.is_empty() {
+            return None;
+        }
+
+        match input.parse::<usize>() {
+            Ok(num) if num >= 1 && num <= options.len() => return Some(num - 1),
+            _ => println!("Please enter a number between 1 and {}.", options.len()),
+        }
+    }
+}
+
 pub fn read_password(text: &str) -> String {
     print!("{}", text);
     io::stdout().flush().unwrap();
@@ -40,6 +67,44 @@ pub fn create_password(text: &str, text_repeat: &str) -> Result<String, String>
     Err("Passwords don't match!".to_string())
 }
 
+// Tries clipboard tools of the system first. If none is installed, asks the terminal
+// to set the clipboard with OSC 52 escape sequence.
+pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "windows") {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+
+    for (program, args) in tools {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin
+                    .write_all(text.as_bytes())
+                    .map_err(|e| e.to_string())?;
+            }
+
+            if child.wait().map(|status| status.success()).unwrap_or(false) {
+                return Ok(());
+            }
+        }
+    }
+
+    print!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    io::stdout().flush().map_err(|e| e.to_string())
+}
+
 pub fn warn(message: &str) {
     println!("{}{}{}", YELLOW, message, RESET);
 }
@@ -64,3 +129,15 @@ pub fn print_entry(domain: &str, username: &str, password: &str) {
 
     print_stdout(table).unwrap();
 }
+
+pub fn print_table(title: &[&str], rows: &[Vec<String>]) {
+    let table = rows
+        .iter()
+        .map(|row| row.iter().map(|value| value.cell()).collect::<Vec<_>>())
+        .collect::<Vec<_>>()
+        .table()
+        .title(title.iter().map(|name| name.cell()).collect::<Vec<_>>())
+        .bold(true);
+
+    print_stdout(table).unwrap();
+}
@@ -1,32 +1,268 @@
+use crate::audit;
+use crate::backup;
+use crate::breach;
 use crate::cli;
-use crate::datastructures::sorted_indexentry_set::IndexSet;
-use crate::entry::{index_entry::IndexEntry, vault_entry::Entry, BinarySerialization};
+use crate::config;
+use crate::crypto::{self, Cipher};
+use crate::datastructures::sorted_indexentry_set::{IndexSet, INDEX_VERSION};
+use crate::entry::{
+    index_entry::{self, IndexEntry},
+    vault_entry::Entry,
+    BinarySerialization,
+};
+use crate::errs;
+use crate::expiry;
+use crate::export;
+use crate::import::{self, Duplicates, Format};
+use crate::kdbx;
+use crate::keyfile;
+use crate::log::{self, Event};
+use crate::recovery::{self, Share};
+use crate::search::{self, SearchItem};
+use crate::shell;
 use crate::storage;
-use crate::{crypto, errs};
+use crate::strength;
+use crate::sync;
+use crate::team::{self, Identity, Pins, Team};
+use crate::throttle;
+use crate::time;
+use crate::tui;
 
 use rand::Rng;
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
 
-const COMMANDS: [&str; 7] = ["--version", "help", "add", "get", "list", "gen", "remove"];
+pub const COMMANDS: [&str; 31] = [
+    "--version",
+    "help",
+    "init",
+    "add",
+    "get",
+    "list",
+    "gen",
+    "remove",
+    "tag",
+    "mv",
+    "search",
+    "edit",
+    "tui",
+    "shell",
+    "import",
+    "export",
+    "backup",
+    "restore",
+    "config",
+    "passwd",
+    "compact",
+    "convert",
+    "cipher",
+    "sync",
+    "conflicts",
+    "team",
+    "share",
+    "recovery",
+    "audit",
+    "expiry",
+    "log",
+];
+// Flags that don't take a value
+const SWITCHES: [&str; 3] = ["--dry-run", "--list", "--remove-keyfile"];
 const MASTER_PASSWORD_TEXT: &str = "Enter master password: ";
 const PASSWORD_TEXT: &str = "Password: ";
 const REPEAT_PASSWORD_TEXT: &str = "Repeat password: ";
+const NEW_MASTER_PASSWORD_TEXT: &str = "Enter new master password: ";
+const PASSPHRASE_TEXT: &str = "Enter export passphrase: ";
+const KDBX_PASSWORD_TEXT: &str = "Enter KeePass database password: ";
+
+// Master password of an unlocked shell session. Commands use it instead of asking for it.
+static SESSION_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
 
 // dome help
 pub fn command_help() {
     let help = vec![
         vec!["--version", "Displays current version of Dome."],
+        vec![
+            "init --keyfile <path>",
+            "Requires the key file next to the master password of a new vault.",
+        ],
         vec!["list", "Displays a list of all domains savedin the vault."],
+        vec![
+            "list --tag <tag> --folder <folder>",
+            "Displays only domains with given tag or inside given folder.",
+        ],
         vec![
             "add <domain> -u <username>",
             "Adds new entry into the vault. Username is optional.",
         ],
-        vec!["get <domain>", "Shows the password for given domain."],
+        vec![
+            "    --tag <tags> --folder <folder>",
+            "Comma separated tags and a folder like infra/db for the new entry.",
+        ],
+        vec!["    --url <url>", "Address of the login page."],
+        vec![
+            "get <domain>",
+            "Shows the password for given domain. Falls back to the closest match.",
+        ],
+        vec![
+            "search <query>",
+            "Searches domains, usernames, URLs and tags.",
+        ],
+        vec![
+            "edit <domain> -u <username>",
+            "Changes username, URL or password of the entry.",
+        ],
+        vec!["    --url <url>", "New address of the login page."],
         vec!["remove <domain>", "Removes entry from the vault."],
+        vec![
+            "tag <domain> <tags>",
+            "Adds comma separated tags to the entry.",
+        ],
+        vec![
+            "tag <domain> --remove <tags>",
+            "Removes comma separated tags from the entry.",
+        ],
+        vec!["mv <domain> <folder>", "Moves entry into the folder."],
         vec![
             "gen <length>",
             "Generates random password of the given length.",
         ],
+        vec![
+            "import <format> <path>",
+            "Imports entries exported from other password manager.",
+        ],
+        vec![
+            "    --duplicates <action>",
+            "What to do with existing domains: skip, overwrite or rename.",
+        ],
+        vec!["    --dry-run", "Only shows what would be imported."],
+        vec![
+            "export <format> <path>",
+            "Exports all entries. Formats: bitwarden, keepass-csv, kdbx, dome.",
+        ],
+        vec![
+            "    --cipher <cipher>",
+            "Cipher of KeePass database: aes or chacha20.",
+        ],
+        vec![
+            "backup",
+            "Saves encrypted snapshot of the vault into the backup directory.",
+        ],
+        vec!["backup --list", "Displays saved backups."],
+        vec![
+            "restore <backup>",
+            "Verifies the backup and replaces the vault with it.",
+        ],
+        vec![
+            "config <key> <value>",
+            "Changes a setting. Without arguments displays all settings.",
+        ],
+        vec![
+            "compact",
+            "Frees space of deleted and replaced entries in the vault.",
+        ],
+        vec![
+            "convert <files|single-file>",
+            "Moves the vault between two files and a single file. Without layout displays the current one.",
+        ],
+        vec![
+            "cipher <aes-256-gcm|xchacha20-poly1305>",
+            "Re-encrypts the vault with the cipher. Without cipher displays the current one.",
+        ],
+        vec![
+            "sync --remote <url>",
+            "Commits the vault to a git repository and merges changes of the remote.",
+        ],
+        vec![
+            "sync --dir <path>",
+            "Merges the vault with other devices through a shared directory.",
+        ],
+        vec![
+            "conflicts <domain> --keep <local|other>",
+            "Resolves an entry changed on two devices. Without domain lists the conflicts.",
+        ],
+        vec![
+            "team init <path> --name <name>",
+            "Creates a team vault with you as its first member, or joins an existing one.",
+        ],
+        vec!["team key", "Displays your public key for the team."],
+        vec![
+            "team add-member <key> --name <name>",
+            "Adds a member with the public key to the team vault.",
+        ],
+        vec![
+            "team remove-member <name>",
+            "Removes the member and re-keys every entry the member could read.",
+        ],
+        vec![
+            "team trust <key> --name <name>",
+            "Pins the checked key of a member added by someone else.",
+        ],
+        vec![
+            "team list",
+            "Displays members and entries shared in the team vault.",
+        ],
+        vec!["team get <domain>", "Displays the entry shared with you."],
+        vec![
+            "share <domain> --with <names>",
+            "Shares the entry with members of the team, names are separated by comma.",
+        ],
+        vec![
+            "recovery split --shares <count> --threshold <count>",
+            "Splits the master password into shares, any threshold of them recovers it.",
+        ],
+        vec![
+            "recovery combine",
+            "Recovers the vault from shares and sets a new master password.",
+        ],
+        vec![
+            "audit strength",
+            "Scores the strength of every password, the weakest first.",
+        ],
+        vec![
+            "audit reuse",
+            "Groups entries that share the same or a similar password.",
+        ],
+        vec![
+            "audit breached --file <path>",
+            "Finds passwords that appear in the downloaded Pwned Passwords list.",
+        ],
+        vec![
+            "audit stale",
+            "Lists passwords that are past their rotation date.",
+        ],
+        vec![
+            "expiry <domain> <days>",
+            "Password of the entry expires this many days after it was changed.",
+        ],
+        vec![
+            "expiry --tag <tag> <days>",
+            "Passwords with the tag expire this many days after they were changed.",
+        ],
+        vec![
+            "audit <report> --format <format>",
+            "Prints the report as table, json or csv.",
+        ],
+        vec![
+            "log --domain <domain> --event <event>",
+            "Displays logged access to the vault, filters are optional.",
+        ],
+        vec!["log verify", "Checks that nobody changed the log."],
+        vec![
+            "passwd",
+            "Changes master password and re-encrypts every entry.",
+        ],
+        vec![
+            "passwd --keyfile <path>",
+            "Requires the key file next to the master password, generates it if missing.",
+        ],
+        vec!["passwd --remove-keyfile", "Stops requiring the key file."],
+        vec!["tui", "Opens interactive terminal interface."],
+        vec![
+            "shell --timeout <minutes>",
+            "Unlocks the vault once and accepts commands until exit.",
+        ],
     ];
 
     println!("List of all availible commands:\n");
@@ -35,73 +271,189 @@ pub fn command_help() {
         let (c, e) = (item.first().unwrap(), item.get(1).unwrap());
         println!("{:<32} {}", c, e);
     }
+
+    println!("\nImport formats: {}", import::FORMAT_NAMES.join(", "));
 }
 
-// dome list
-pub fn command_list() {
-    let index_binaries: Vec<u8> = storage::index::index_read();
-    let index_set = IndexSet::from_binary(&index_binaries);
+// dome list --tag <tag> --folder <folder>
+pub fn command_list(tag: Option<&str>, folder: Option<&str>) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
 
     if index_set.is_empty() {
-        println!("Your wault is empty. Add new entry using 'add <domain> -u <username>' command.")
+        return println!(
+            "Your wault is empty. Add new entry using 'add <domain> -u <username>' command."
+        );
+    }
+
+    if tag.is_none() && folder.is_none() {
+        return index_set.print();
+    }
+
+    let mut entries: Vec<&IndexEntry> = match tag {
+        Some(tag) => index_set.with_tag(&index_entry::normalize_tag(tag)),
+        None => index_set.in_folder(folder.unwrap_or_default()),
+    };
+
+    if let Some(folder) = folder {
+        entries.retain(|entry| entry.in_folder(folder));
+    }
+
+    if entries.is_empty() {
+        println!("No entries match given tag or folder.");
+
+        let tags = index_set.tag_names();
+        if !tags.is_empty() {
+            let names: Vec<&str> = tags.iter().map(|tag| tag.as_str()).collect();
+            println!("Existing tags: {}", names.join(", "));
+        }
+    } else {
+        IndexSet::print_entries(&entries);
+    }
+}
+
+// dome tag <domain> <tags>
+// dome tag <domain> --remove <tags>
+pub fn command_tag(domain: &str, tags: &[String], remove: &[String]) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    if !index_set.has(domain) {
+        return cli::warn(format!("[NOT FOUND] Domain {} is not in the vault.", domain).as_str());
+    }
+
+    // Tags are sealed with the master password
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let result = change_index(domain, &master_password, &mut |set| {
+        set.add_tags(domain, tags);
+        set.remove_tags(domain, remove);
+    });
+    if let Err(err) = result {
+        return cli::error(format!("[ERROR] {}", err).as_str());
+    }
+
+    let Some(set) = read_index_set() else {
+        return;
+    };
+    if let Some((_, entry)) = set.find(domain) {
+        IndexSet::print_entries(&[entry]);
+    }
+}
+
+// dome mv <domain> <folder>
+pub fn command_move(domain: &str, folder: &str) {
+    let folder = index_entry::normalize_folder(folder);
+
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    if !index_set.has(domain) {
+        return cli::warn(format!("[NOT FOUND] Domain {} is not in the vault.", domain).as_str());
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let result = change_index(domain, &master_password, &mut |set| {
+        set.move_to_folder(domain, &folder);
+    });
+    if let Err(err) = result {
+        return cli::error(format!("[ERROR] {}", err).as_str());
+    }
+
+    if folder.is_empty() {
+        println!("{} was moved to the root folder.", domain);
     } else {
-        index_set.print();
+        println!("{} was moved to {}.", domain, folder);
     }
 }
 
 // dome gen <length>
 pub fn command_generate(len: u8) {
-    let mut password = Vec::new();
-    let mut rng = rand::thread_rng();
+    println!("{}", generate_password(len))
+}
 
-    for _ in 0..len {
-        let dec: u8 = rng.gen_range(33..=122);
-        password.push(dec as char);
+// dome search <query>
+pub fn command_search(query: &str) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    let items = get_search_items(&index_set);
+    let results = search::search(query, &items);
+
+    if results.is_empty() {
+        return println!("No entries match {}.", query);
     }
 
-    let result = String::from_iter(password);
-    println!("{}", result)
-}
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let item = items.iter().find(|item| item.key == result.key).unwrap();
+            vec![
+                index.to_string(),
+                item.key.clone(),
+                item.username.clone(),
+                item.url.clone(),
+                item.tags.join(", "),
+                result.field.to_string(),
+            ]
+        })
+        .collect();
 
-// dome add <domain> -u <username>
-pub fn command_add(domain: &str, username: &str) {
-    let mut index_binaries: Vec<u8> = storage::index::index_read();
-    let mut index_set = IndexSet::from_binary(&index_binaries);
+    cli::print_table(&["", "Domain", "Username", "URL", "Tags", "Matched"], &rows);
+}
 
+// dome add <domain> -u <username> --tag <tags> --folder <folder> --url <url>
+pub fn command_add(domain: &str, username: &str, tags: &[String], folder: &str, url: &str) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
     if index_set.has(domain) {
         return cli::error(format!("[ERROR] Domain {} already exist in a vault.", domain).as_str());
     }
 
     cli::warn("[ADD] You are adding new entry to the vault.\n----------------------------------------------------------------------");
-    let master_password = match cli::create_password(MASTER_PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
-        Ok(value) => value,
-        Err(err) => panic!("{}", err),
+    let master_password = match session_password() {
+        Some(value) => value,
+        None => match create_master_password() {
+            Ok(value) => value,
+            Err(err) => return cli::error(&err),
+        },
     };
 
     println!("\nCreate password for {}.", domain);
 
     match cli::create_password(PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
         Ok(password) => {
-            // Encrypt password using secret key generated from master password
-            let salt = crypto::generate_salt();
-            let key = crypto::derive_key(&master_password, &salt);
-            let (cipher, nonce) = crypto::encrypt(&key, &password);
-
-            // Create entry for vailt and entry for index file
-            let vault_last_byte = storage::vault::vault_len();
-            let entity = Entry::new(domain, username, &cipher, &nonce, &salt);
-            let index_entity = IndexEntry::new(domain, vault_last_byte);
-            let _ = index_set.add(&index_entity);
-
-            // Serialize entries and save into binary files
-            index_binaries = index_set.serialize();
-            storage::index::index_write(&index_binaries);
+            if !password_allowed(&password, &[domain, username]) {
+                return;
+            }
 
-            let entity_binaries = entity.serialize();
-            storage::vault::vault_add(&entity_binaries);
+            let new_entry = NewEntry {
+                domain: String::from(domain),
+                username: String::from(username),
+                password,
+                url: String::from(url),
+                tags: tags.to_vec(),
+                folder: String::from(folder),
+            };
 
-            println!("\nNew entry was added to the vault.");
-            cli::print_entry(domain, username, "");
+            match add_entry(&new_entry, &master_password) {
+                Ok(()) => {
+                    log_event(Event::Add, domain, &master_password);
+                    println!("\nNew entry was added to the vault.");
+                    cli::print_entry(domain, username, "");
+                }
+                Err(err) => cli::error(&err),
+            }
         }
         Err(err) => println!("{}", err),
     }
@@ -109,101 +461,2352 @@ pub fn command_add(domain: &str, username: &str) {
 
 // dome get <domain>
 pub fn command_get(domain: &str) {
-    let index_binaries: Vec<u8> = storage::index::index_read();
-    let index_set = IndexSet::from_binary(&index_binaries);
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
 
-    match index_set.find(domain) {
-        Some((_, entry)) => {
-            let binary_data = storage::vault::vault_read(entry.value);
-            let entry = Entry::deserialize(&binary_data);
+    let domain = match index_set.find(domain) {
+        Some(_) => String::from(domain),
+        None => match resolve_domain(&index_set, domain) {
+            Some(key) => key,
+            None => return println!("Password for domain {} was not found in a vault.", domain),
+        },
+    };
 
-            let master_password = cli::read_password(MASTER_PASSWORD_TEXT);
-            let key = crypto::derive_key(&master_password, &entry.salt);
-            let text_password = crypto::decrypt(&key, &entry.nonce, &entry.password);
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
 
+    match get_entry(&domain, &master_password) {
+        Ok((entry, text_password)) => {
+            log_event(Event::Get, &domain, &master_password);
             cli::print_entry(&entry.domain, &entry.username, &text_password);
+            warn_if_expired(&domain);
+            if let Err(err) = mark_accessed(&domain) {
+                cli::error(format!("[ERROR] {}", err).as_str());
+            }
+        }
+        Err(err) => {
+            log_failure(Event::UnlockFailed, &domain);
+            cli::error(&err)
         }
-        None => println!("Password for domain {} was not found in a vault.", domain),
     }
 }
 
-pub fn command_remove(domain: &str) {
-    let mut set = get_index_set();
-    match set.find(domain) {
-        None => {
-            return cli::warn(
-                format!("[NOT FOUND] Domain {} is not in the vault.", domain).as_str(),
-            );
+fn warn_if_expired(domain: &str) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    let Some((_, index_entry)) = index_set.find(domain) else {
+        return;
+    };
+
+    if expiry::is_expired(index_entry, time::now()) {
+        cli::warn(
+            format!(
+                "[EXPIRED] Password of {} is due for a change. Change it with dome edit {}.",
+                domain, domain
+            )
+            .as_str(),
+        );
+    }
+}
+
+// Remembers when the password was last read
+pub fn mark_accessed(domain: &str) -> Result<(), String> {
+    storage::open().transaction(&mut |backend| {
+        let mut index_set = backend.list()?;
+        let mut index_entity = match index_set.find(domain) {
+            Some((_, index_entity)) => index_entity.clone(),
+            None => return Ok(()),
+        };
+
+        index_entity.accessed = time::now();
+        index_set.replace(&index_entity);
+        backend.write_index(&index_set)
+    })
+}
+
+// dome edit <domain> -u <username> --url <url>
+pub fn command_edit(domain: &str, username: Option<&str>, url: Option<&str>) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    if !index_set.has(domain) {
+        return cli::warn(format!("[NOT FOUND] Domain {} is not in the vault.", domain).as_str());
+    }
+
+    let mut changes = EntryChanges {
+        username: username.map(String::from),
+        url: url.map(String::from),
+        ..Default::default()
+    };
+
+    if cli::get_confirmation("Do you want to change the password? [y/n]: ") {
+        match cli::create_password(PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
+            Ok(password) => {
+                if !password_allowed(&password, &[domain, username.unwrap_or_default()]) {
+                    return;
+                }
+                changes.password = Some(password);
+            }
+            Err(err) => return println!("{}", err),
         }
-        Some((_, entry)) => {
-            if !cli::get_confirmation(
-                format!(
-                    "Are you sure you want to delete {} from the vault? [y/n]: ",
-                    domain
-                )
-                .as_str(),
-            ) {
-                println!("Action was aborted.");
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    match edit_entry(domain, &changes, &master_password) {
+        Ok(()) => {
+            log_event(Event::Edit, domain, &master_password);
+            println!("{} was updated.", domain);
+            match get_entry(domain, &master_password) {
+                Ok((entry, _)) => cli::print_entry(&entry.domain, &entry.username, ""),
+                Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
             }
+        }
+        Err(err) => cli::error(&err),
+    }
+}
+
+// dome remove <domain>
+pub fn command_remove(domain: &str) {
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    if !index_set.has(domain) {
+        return cli::warn(format!("[NOT FOUND] Domain {} is not in the vault.", domain).as_str());
+    }
+
+    if !cli::get_confirmation(
+        format!(
+            "Are you sure you want to delete {} from the vault? [y/n]: ",
+            domain
+        )
+        .as_str(),
+    ) {
+        return println!("Action was aborted.");
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    if !backup_before(&master_password, "remove") {
+        return;
+    }
 
-            let size = storage::vault::vault_remove(entry.value);
-            set.remove(domain, size);
-            let b_set = set.serialize();
-            storage::index::index_write(&b_set);
-            println!("{} was deleted from the vault.", domain);
+    match remove_entry(domain, &master_password) {
+        Ok(()) => {
+            log_event(Event::Remove, domain, &master_password);
+            println!("{} was deleted from the vault.", domain)
         }
+        Err(err) => cli::error(&err),
     }
 }
 
-// dome --version
-pub fn command_version() {
-    let version = env!("CARGO_PKG_VERSION");
-    println!("Dome - {}", version);
+#[derive(Clone)]
+pub struct NewEntry {
+    pub domain: String,
+    pub username: String,
+    pub password: String,
+    pub url: String,
+    pub tags: Vec<String>,
+    pub folder: String,
 }
 
-fn get_index_set() -> IndexSet {
-    let index_binaries: Vec<u8> = storage::index::index_read();
-    IndexSet::from_binary(&index_binaries)
+// Fields left as None are not changed
+#[derive(Default)]
+pub struct EntryChanges {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub folder: Option<String>,
 }
 
-pub struct Command {
-    pub command: String,
-    pub args: Vec<String>,
-    pub flags: HashMap<String, String>,
+pub fn set_session_password(password: Option<String>) {
+    *SESSION_PASSWORD.lock().unwrap() = password;
 }
 
-impl Command {
-    pub fn new(command: &str) -> Command {
-        Command {
-            command: String::from(command),
-            args: Vec::new(),
-            flags: HashMap::new(),
+pub fn session_password() -> Option<String> {
+    SESSION_PASSWORD.lock().unwrap().clone()
+}
+
+// Session password of the shell, or the typed one once the vault accepts it
+fn read_master_password() -> Result<String, String> {
+    if let Some(password) = session_password() {
+        return Ok(password);
+    }
+
+    // Nothing is asked while the vault makes attempts wait
+    throttle::check(time::now())?;
+    try_master_password(ask_master_password()?)
+}
+
+fn ask_master_password() -> Result<String, String> {
+    match session_password() {
+        Some(password) => Ok(password),
+        None => unlock_password(cli::read_password(MASTER_PASSWORD_TEXT)),
+    }
+}
+
+// Verifies the master password. Wrong ones are counted, logged and make the following
+// attempts wait.
+pub fn try_master_password(master_password: String) -> Result<String, String> {
+    let now = time::now();
+    if throttle::tampered() {
+        cli::warn("[THROTTLE] Failed attempt counter was changed outside of Dome, the higher count is used.");
+    }
+    throttle::check(now)?;
+
+    if let Err(err) = verify_master_password(&master_password) {
+        return Err(count_failure(err, now));
+    }
+
+    // Counter is reset only after the seal shows it wasn't turned back, resetting seals the
+    // index again
+    let index_set = get_index_set()?;
+    index_set
+        .verify_seal(&master_password, storage::index::sealed_version())
+        .map_err(|err| format!("[TAMPERED] {}", err))?;
+    storage::index::record_seal(index_set.seal_version())?;
+
+    if let Err(err) = throttle::succeeded(&master_password) {
+        cli::warn(format!("[THROTTLE] Failed attempts were not reset: {}", err).as_str());
+    }
+    Ok(master_password)
+}
+
+// Logs and counts a wrong master password. Returns the error with what it means for the
+// next attempts.
+fn count_failure(err: String, now: u64) -> String {
+    log_failure(Event::UnlockFailed, "");
+
+    match throttle::failed(now) {
+        Ok(None) => err,
+        Ok(Some(note)) => format!("{} {}", err, note),
+        Err(throttle_err) => format!("{} {}", err, throttle_err),
+    }
+}
+
+// Asks for the master password twice. It is scored only when the vault is empty, because
+// otherwise it already exists and has to match.
+fn create_master_password() -> Result<String, String> {
+    let password = cli::create_password(MASTER_PASSWORD_TEXT, REPEAT_PASSWORD_TEXT)?;
+
+    if !get_index_set()?.is_empty() {
+        return try_master_password(unlock_password(password)?);
+    }
+
+    if !password_allowed(&password, &[]) {
+        return Err(String::from("Action was aborted."));
+    }
+
+    unlock_password(password)
+}
+
+// Warns about a weak password. Returns false when the configured minimum score refuses it.
+fn password_allowed(password: &str, user_inputs: &[&str]) -> bool {
+    match strength::check(password, user_inputs) {
+        Ok(None) => true,
+        Ok(Some(warning)) => {
+            cli::warn(format!("[STRENGTH] {}", warning).as_str());
+            true
+        }
+        Err(err) => {
+            cli::error(format!("[ERROR] {}", err).as_str());
+            false
         }
     }
 }
 
-pub fn parse_args(args: &[String]) -> Result<Command, String> {
-    if args.len() < 2 {
-        return Err(errs::INVALID_COMMAND.to_string());
+// Logging never stops the command, it only warns
+pub fn log_event(event: Event, domain: &str, master_password: &str) {
+    if let Err(err) = log::record(event, domain, master_password) {
+        cli::warn(format!("[LOG] {}", err).as_str());
     }
+}
 
-    let command = &args[1];
-    if !COMMANDS.contains(&command.as_str()) {
-        return Err(errs::INVALID_COMMAND.to_string());
+pub fn log_failure(event: Event, domain: &str) {
+    if let Err(err) = log::record_failure(event, domain) {
+        cli::warn(format!("[LOG] Event was not logged: {}", err).as_str());
     }
+}
 
-    let mut comm = Command::new(command);
-    if args.len() == 2 {
-        return Ok(comm);
+// Mixes the configured key file into the typed master password. Fails when the key file
+// can't be read, the password alone would skip it.
+pub fn unlock_password(password: String) -> Result<String, String> {
+    keyfile::unlock(&password).map_err(|err| format!("[ERROR] {}", err))
+}
+
+// Succeeds if the password decrypts at least one entry, or if the vault is empty
+pub fn verify_master_password(master_password: &str) -> Result<(), String> {
+    let index_set = get_index_set()?;
+
+    if index_set.is_empty() {
+        return Ok(());
     }
 
-    let command_args = &args[2..];
-    let len = command_args.len();
-    let mut index = 0;
+    for index_entry in &index_set.data {
+        if get_entry(&index_entry.key, master_password).is_ok() {
+            return Ok(());
+        }
+    }
 
-    while index < len {
-        let current_arg = &command_args[index];
-        if current_arg.starts_with("-") {
+    Err(String::from(errs::DECRYPTION_FAILED))
+}
+
+pub fn generate_password(len: u8) -> String {
+    let mut password = Vec::new();
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..len {
+        let dec: u8 = rng.gen_range(33..=122);
+        password.push(dec as char);
+    }
+
+    String::from_iter(password)
+}
+
+pub fn add_entry(new_entry: &NewEntry, master_password: &str) -> Result<(), String> {
+    if get_index_set()?.has(&new_entry.domain) {
+        return Err(format!(
+            "Domain {} already exist in a vault.",
+            new_entry.domain
+        ));
+    }
+
+    put_entry(new_entry, master_password)
+}
+
+// Adds the entry, or replaces the entry with the same domain
+pub fn put_entry(new_entry: &NewEntry, master_password: &str) -> Result<(), String> {
+    if new_entry.domain.is_empty() {
+        return Err(String::from("Domain can't be empty."));
+    }
+
+    // Encrypt password using secret key generated from master password
+    let id = crypto::generate_id();
+    let entity = encrypt_entry(
+        &new_entry.domain,
+        &new_entry.username,
+        &new_entry.password,
+        &id,
+        get_index_set()?.cipher,
+        master_password,
+    )?;
+
+    // Record, its tombstone and the index change together
+    storage::open().transaction(&mut |backend| {
+        let mut index_set = backend.list()?;
+
+        // Create entry for vailt and entry for index file
+        let mut index_entity =
+            IndexEntry::new(&new_entry.domain, backend.write_entry(&entity.serialize())?);
+        index_entity.folder = index_entry::normalize_folder(&new_entry.folder);
+        index_entity.tags = new_entry.tags.clone();
+        index_entity.tags.sort();
+        index_entity.tags.dedup();
+        index_entity.url = new_entry.url.clone();
+        index_entity.id = id.clone();
+        index_entity.created = time::now();
+        index_entity.modified = index_entity.created;
+
+        if let Some((_, old)) = index_set.find(&new_entry.domain) {
+            index_entity.created = old.created;
+            index_entity.accessed = old.accessed;
+            index_entity.expiry_days = old.expiry_days;
+
+            // Replaced record becomes a tombstone
+            index_set.garbage += backend.delete_entry(old.value)?;
+            index_set.replace(&index_entity);
+        } else {
+            index_set.add(&index_entity);
+        }
+
+        index_set.seal(master_password);
+        backend.write_index(&index_set)
+    })?;
+    // First entry of a new vault is where its index gets sealed
+    storage::index::record_seal(INDEX_VERSION)?;
+    compact_if_needed(master_password);
+
+    Ok(())
+}
+
+// Returns the vault entry together with its decrypted password
+pub fn get_entry(domain: &str, master_password: &str) -> Result<(Entry, String), String> {
+    let backend = storage::open();
+    let index_set = backend.list()?;
+
+    match index_set.find(domain) {
+        Some((_, index_entry)) => {
+            let entry = Entry::deserialize(&backend.read_entry(index_entry.value));
+            let key = crypto::derive_key(master_password, &entry.salt);
+            let aad = crypto::associated_data(domain, &entry.username, &index_entry.id);
+            let text_password =
+                crypto::decrypt(entry.cipher, &key, &entry.nonce, &entry.password, &aad)?;
+
+            Ok((entry, text_password))
+        }
+        None => Err(format!(
+            "Password for domain {} was not found in a vault.",
+            domain
+        )),
+    }
+}
+
+pub fn edit_entry(
+    domain: &str,
+    changes: &EntryChanges,
+    master_password: &str,
+) -> Result<(), String> {
+    // Only someone who knows the master password can edit the entry
+    let (entry, password) = get_entry(domain, master_password)?;
+
+    // Record is rewritten and the entry counts as modified only when the username or the
+    // password really changed, so editing tags doesn't restart the expiry
+    let username = changes.username.as_ref().filter(|u| **u != entry.username);
+    let new_password = changes.password.as_ref().filter(|p| **p != password);
+
+    let id = crypto::generate_id();
+    let entity = if username.is_some() || new_password.is_some() {
+        let username = username.unwrap_or(&entry.username);
+        let password = new_password.unwrap_or(&password);
+        let cipher = get_index_set()?.cipher;
+        Some(encrypt_entry(
+            domain,
+            username,
+            password,
+            &id,
+            cipher,
+            master_password,
+        )?)
+    } else {
+        None
+    };
+
+    storage::open().transaction(&mut |backend| {
+        let mut index_set = backend.list()?;
+        let mut index_entity = match index_set.find(domain) {
+            Some((_, index_entity)) => index_entity.clone(),
+            None => return Err(format!("Domain {} is not in the vault.", domain)),
+        };
+
+        // Updated record is appended, the old one becomes a tombstone
+        if let Some(entity) = &entity {
+            index_set.garbage += backend.delete_entry(index_entity.value)?;
+            index_entity.value = backend.write_entry(&entity.serialize())?;
+            index_entity.id = id.clone();
+            index_entity.modified = time::now();
+        }
+
+        if let Some(url) = &changes.url {
+            index_entity.url = url.clone();
+        }
+
+        if let Some(folder) = &changes.folder {
+            index_entity.folder = index_entry::normalize_folder(folder);
+        }
+
+        if let Some(tags) = &changes.tags {
+            index_entity.tags = tags.clone();
+            index_entity.tags.sort();
+            index_entity.tags.dedup();
+        }
+
+        index_set.replace(&index_entity);
+        index_set.seal(master_password);
+        backend.write_index(&index_set)
+    })?;
+    compact_if_needed(master_password);
+
+    Ok(())
+}
+
+// Changes the entry in the index and seals it again. Fails when the entry is gone.
+fn change_index(
+    domain: &str,
+    master_password: &str,
+    change: &mut dyn FnMut(&mut IndexSet),
+) -> Result<(), String> {
+    storage::open().transaction(&mut |backend| {
+        let mut index_set = backend.list()?;
+        if !index_set.has(domain) {
+            return Err(format!("Domain {} is not in the vault.", domain));
+        }
+
+        change(&mut index_set);
+        index_set.seal(master_password);
+        backend.write_index(&index_set)
+    })
+}
+
+pub fn remove_entry(domain: &str, master_password: &str) -> Result<(), String> {
+    storage::open().transaction(&mut |backend| {
+        let mut set = backend.list()?;
+
+        let entry = match set.find(domain) {
+            Some((_, entry)) => entry.clone(),
+            None => return Err(format!("Domain {} is not in the vault.", domain)),
+        };
+
+        let size = backend.delete_entry(entry.value)?;
+        set.remove(domain, size);
+        set.seal(master_password);
+        backend.write_index(&set)
+    })?;
+    compact_if_needed(master_password);
+
+    Ok(())
+}
+
+// Rewrites the vault with live records only and seals the moved locations. Returns number
+// of freed bytes.
+pub fn compact_vault(master_password: &str) -> Result<u64, String> {
+    let backend = storage::open();
+    let mut index_set = backend.list()?;
+    let old_len = backend.records_len();
+    let mut vault = Vec::new();
+
+    for index_entry in index_set.data.iter_mut() {
+        let record = backend.read_entry(index_entry.value);
+        index_entry.value = vault.len() as u64;
+        vault.extend(record);
+    }
+
+    index_set.garbage = 0;
+    index_set.seal(master_password);
+    save_vault(&vault, &index_set)?;
+
+    Ok(old_len.saturating_sub(vault.len() as u64))
+}
+
+// Compacts the vault once garbage takes more than the configured share of it. Failed
+// compaction leaves the vault as it was, so it is simply tried again next time.
+fn compact_if_needed(master_password: &str) {
+    let percent = config::get_number("compact.garbage_percent");
+    let backend = storage::open();
+
+    let garbage = backend.list().map_or(0, |index_set| index_set.garbage);
+    if percent > 0 && garbage * 100 > backend.records_len() * percent {
+        let _ = compact_vault(master_password);
+    }
+}
+
+// Vault and index with every record encrypted by a new master password
+pub struct ReencryptedVault {
+    pub vault: Vec<u8>,
+    pub index_set: IndexSet,
+    pub skipped: Vec<String>,
+}
+
+// Re-encrypts every entry that the current password decrypts with the new password and
+// cipher. Entries encrypted with a different password are copied as they are and listed in
+// `skipped`. Only live records are written, so the new vault has no garbage.
+pub fn reencrypt_vault(
+    current: &str,
+    new: &str,
+    cipher: Cipher,
+) -> Result<ReencryptedVault, String> {
+    let backend = storage::open();
+    let mut index_set = backend.list()?;
+    let mut vault = Vec::new();
+    let mut skipped = Vec::new();
+
+    for index_entry in index_set.data.iter_mut() {
+        let record = backend.read_entry(index_entry.value);
+        let entry = Entry::deserialize(&record);
+        let key = crypto::derive_key(current, &entry.salt);
+        let aad = crypto::associated_data(&index_entry.key, &entry.username, &index_entry.id);
+
+        let record = match crypto::decrypt(entry.cipher, &key, &entry.nonce, &entry.password, &aad)
+        {
+            Ok(password) => {
+                // Records from before IDs get one now
+                if index_entry.id.is_empty() {
+                    index_entry.id = crypto::generate_id();
+                }
+                let (domain, id) = (&index_entry.key, &index_entry.id);
+                encrypt_entry(domain, &entry.username, &password, id, cipher, new)?.serialize()
+            }
+            Err(_) => {
+                skipped.push(index_entry.key.clone());
+                record
+            }
+        };
+
+        index_entry.value = vault.len() as u64;
+        vault.extend(record);
+    }
+
+    index_set.garbage = 0;
+    index_set.cipher = cipher;
+    index_set.seal(new);
+
+    Ok(ReencryptedVault {
+        vault,
+        index_set,
+        skipped,
+    })
+}
+
+// Replaces vault and index together, an interrupted write leaves the old ones in place
+pub fn save_vault(vault: &[u8], index_set: &IndexSet) -> Result<(), String> {
+    storage::open().replace(vault, &index_set.serialize())
+}
+
+fn encrypt_entry(
+    domain: &str,
+    username: &str,
+    password: &str,
+    id: &[u8],
+    cipher: Cipher,
+    master_password: &str,
+) -> Result<Entry, String> {
+    if domain.len() > u8::MAX as usize || username.len() > u8::MAX as usize {
+        return Err(String::from(
+            "Domain and username can't be longer than 255 bytes.",
+        ));
+    }
+
+    let salt = crypto::generate_salt();
+    let key = crypto::derive_key(master_password, &salt);
+    let aad = crypto::associated_data(domain, username, id);
+    let (cipher_text, nonce) = crypto::encrypt(cipher, &key, password, &aad);
+
+    if cipher_text.len() > u8::MAX as usize {
+        return Err(String::from("Password is too long."));
+    }
+
+    Ok(Entry::new(
+        domain,
+        username,
+        &cipher_text,
+        &nonce,
+        &salt,
+        cipher,
+    ))
+}
+
+// dome import <format> <path> --duplicates <skip|overwrite|rename> --dry-run
+pub fn command_import(format: &str, path: &str, duplicates: &str, dry_run: bool) {
+    let format = match Format::from_name(format) {
+        Some(format) => format,
+        None => {
+            return cli::error(
+                format!(
+                    "[ERROR] Unknown format {}. Supported formats: {}",
+                    format,
+                    import::FORMAT_NAMES.join(", ")
+                )
+                .as_str(),
+            )
+        }
+    };
+
+    let duplicates = match Duplicates::from_name(duplicates) {
+        Some(duplicates) => duplicates,
+        None => return cli::error("[ERROR] Duplicates must be skip, overwrite or rename."),
+    };
+
+    let passphrase = match format {
+        Format::Dome => Some(cli::read_password(PASSPHRASE_TEXT)),
+        Format::Kdbx => Some(cli::read_password(KDBX_PASSWORD_TEXT)),
+        _ => None,
+    };
+
+    let entries = match import::read(format, Path::new(path), passphrase.as_deref()) {
+        Ok(entries) => entries,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    let planned = import::plan(entries, &index_set, duplicates);
+    let rows: Vec<Vec<String>> = planned
+        .iter()
+        .map(|item| {
+            let action = match &item.action {
+                import::Action::Add => String::from("add"),
+                import::Action::Overwrite => String::from("overwrite"),
+                import::Action::Rename(name) => format!("rename to {}", name),
+                import::Action::Skip(reason) => format!("skip, {}", reason),
+            };
+
+            vec![
+                item.entry.domain.clone(),
+                item.entry.username.clone(),
+                item.entry.folder.clone(),
+                action,
+            ]
+        })
+        .collect();
+
+    cli::print_table(&["Domain", "Username", "Folder", "Action"], &rows);
+
+    let count = planned
+        .iter()
+        .filter(|item| !matches!(item.action, import::Action::Skip(_)))
+        .count();
+
+    if dry_run {
+        return println!("\nDry run, {} entries would be imported.", count);
+    }
+
+    if count == 0 {
+        return println!("\nThere is nothing to import.");
+    }
+
+    if !cli::get_confirmation(format!("\nImport {} entries? [y/n]: ", count).as_str()) {
+        return println!("Action was aborted.");
+    }
+
+    let master_password = match session_password() {
+        Some(value) => value,
+        None => match create_master_password() {
+            Ok(value) => value,
+            Err(err) => return cli::error(&err),
+        },
+    };
+
+    let overwrites = planned
+        .iter()
+        .any(|item| item.action == import::Action::Overwrite);
+
+    if overwrites && !backup_before(&master_password, "import") {
+        return;
+    }
+
+    let report = import::apply(&planned, &master_password);
+
+    println!(
+        "\nImported {} new entries, overwritten {}, renamed {}, skipped {}.",
+        report.added, report.overwritten, report.renamed, report.skipped
+    );
+
+    for (domain, err) in &report.failed {
+        cli::error(format!("[ERROR] {} was not imported: {}", domain, err).as_str());
+    }
+}
+
+// dome export <format> <path> --cipher <aes|chacha20>
+pub fn command_export(format: &str, path: &str, cipher: &str) {
+    let format = match export::Format::from_name(format) {
+        Some(format) => format,
+        None => {
+            return cli::error(
+                format!(
+                    "[ERROR] Unknown format {}. Supported formats: {}",
+                    format,
+                    export::FORMAT_NAMES.join(", ")
+                )
+                .as_str(),
+            )
+        }
+    };
+
+    let cipher = match kdbx::Cipher::from_name(cipher) {
+        Some(cipher) => cipher,
+        None => return cli::error("[ERROR] Cipher must be aes or chacha20."),
+    };
+
+    let path = Path::new(path);
+    if path.exists()
+        && !cli::get_confirmation(
+            format!("{} already exists. Overwrite it? [y/n]: ", path.display()).as_str(),
+        )
+    {
+        return println!("Action was aborted.");
+    }
+
+    if format.is_plaintext() {
+        cli::warn("[EXPORT] Passwords will be saved UNENCRYPTED. Anyone who can read the file can read your passwords.");
+        if !cli::get_confirmation("Do you want to continue with unencrypted export? [y/n]: ") {
+            return println!("Action was aborted.");
+        }
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+    let (entries, failed) = match export::collect(&master_password) {
+        Ok(collected) => collected,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    for domain in &failed {
+        cli::warn(
+            format!(
+                "[EXPORT] {} can't be decrypted with this master password and was skipped.",
+                domain
+            )
+            .as_str(),
+        );
+    }
+
+    if entries.is_empty() {
+        return cli::error("[ERROR] There are no entries to export.");
+    }
+
+    let data = match format {
+        export::Format::Bitwarden => export::to_bitwarden(&entries).into_bytes(),
+        export::Format::KeepassCsv => export::to_keepass_csv(&entries).into_bytes(),
+        export::Format::Dome => {
+            println!("Create passphrase for the export.");
+            match cli::create_password(PASSPHRASE_TEXT, REPEAT_PASSWORD_TEXT) {
+                Ok(passphrase) => export::to_dome(&entries, &passphrase),
+                Err(err) => return println!("{}", err),
+            }
+        }
+        export::Format::Kdbx => {
+            println!("Create password for the KeePass database.");
+            let password = match cli::create_password(KDBX_PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
+                Ok(password) => password,
+                Err(err) => return println!("{}", err),
+            };
+
+            match kdbx::write(&entries, &password, cipher, kdbx::KdfSettings::default()) {
+                Ok(data) => data,
+                Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+            }
+        }
+    };
+
+    match export::write_file(path, &data) {
+        Ok(()) => {
+            log_event(Event::Export, "", &master_password);
+            println!(
+                "{} entries were exported to {}.",
+                entries.len(),
+                path.display()
+            )
+        }
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome backup
+pub fn command_backup() {
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    match backup::create(&master_password, "manual") {
+        Ok(path) => println!("Backup was saved to {}.", path.display()),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome backup --list
+pub fn command_backup_list() {
+    let snapshots = backup::list();
+    if snapshots.is_empty() {
+        return println!("There are no backups yet.");
+    }
+
+    let rows: Vec<Vec<String>> = snapshots
+        .iter()
+        .rev()
+        .map(|snapshot| {
+            vec![
+                snapshot
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                time::format_time(snapshot.created),
+                snapshot.reason.clone(),
+            ]
+        })
+        .collect();
+
+    cli::print_table(&["Backup", "Created", "Reason"], &rows);
+}
+
+// dome restore <backup>
+pub fn command_restore(name: &str) {
+    let path = backup::resolve(name);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(err) => {
+            return cli::error(format!("[ERROR] Can't read {}: {}", path.display(), err).as_str())
+        }
+    };
+
+    // Backup is encrypted with the master password that was used when it was created, which
+    // doesn't have to be the one of the vault. Typed passwords are throttled all the same,
+    // otherwise restore would let anyone guess without waiting.
+    let typed = session_password().is_none();
+    if typed {
+        if let Err(err) = throttle::check(time::now()) {
+            return cli::error(&err);
+        }
+    }
+
+    let master_password = match ask_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+    let mut contents = match backup::decode(&data, &master_password) {
+        Ok(contents) => contents,
+        Err(err) if typed && err == errs::BACKUP_DECRYPTION_FAILED => {
+            let err = count_failure(err, time::now());
+            return cli::error(format!("[ERROR] {}", err).as_str());
+        }
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    // Backup files can be swapped as easily as live ones, so the seal is checked before they
+    // replace the vault. Backups may be older than the seal of the vault, the restored index
+    // is sealed again with the current version.
+    let mut index_set = match IndexSet::try_from_binary(&contents.index).and_then(|index_set| {
+        index_set
+            .verify_seal(&master_password, 0)
+            .map(|()| index_set)
+    }) {
+        Ok(index_set) => index_set,
+        Err(err) => return cli::error(format!("[TAMPERED] {}", err).as_str()),
+    };
+    index_set.seal(&master_password);
+    contents.index = index_set.serialize();
+    println!(
+        "Backup from {} with {} entries was verified.",
+        time::format_time(contents.created),
+        index_set.data.len()
+    );
+
+    if !cli::get_confirmation("Replace the current vault with this backup? [y/n]: ") {
+        return println!("Action was aborted.");
+    }
+
+    if !backup_before(&master_password, "restore") {
+        return;
+    }
+
+    match backup::restore(&contents) {
+        Ok(()) => println!("Vault was restored from {}.", path.display()),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome config <key> <value>
+pub fn command_config(key: Option<&str>, value: Option<&str>) {
+    match (key, value) {
+        (Some(key), Some(value)) => {
+            // Limits on wrong master passwords can only be changed with the master password
+            if key.starts_with("attempts.") {
+                if let Err(err) = read_master_password() {
+                    return cli::error(&err);
+                }
+            }
+            match config::set(key, value) {
+                Ok(()) => println!("{} was set to {}.", key, value),
+                Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+            }
+        }
+        (Some(key), None) => println!("{}", config::get(key)),
+        _ => {
+            let mut rows: Vec<Vec<String>> = config::SETTINGS
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.key.to_string(),
+                        config::get(s.key),
+                        s.description.to_string(),
+                    ]
+                })
+                .collect();
+
+            for (tag, days) in config::tag_expiry_policies() {
+                rows.push(vec![
+                    format!("{}{}", config::TAG_EXPIRY_PREFIX, tag),
+                    days.to_string(),
+                    format!("Passwords tagged {} expire after this many days.", tag),
+                ]);
+            }
+
+            cli::print_table(&["Setting", "Value", "Description"], &rows);
+        }
+    }
+}
+
+// Destructive commands save a backup first and stop if it can't be saved
+fn backup_before(master_password: &str, reason: &str) -> bool {
+    match backup::create(master_password, reason) {
+        Ok(path) => {
+            println!("Backup was saved to {}.", path.display());
+            true
+        }
+        Err(err) => {
+            cli::error(format!("[ERROR] Backup failed, nothing was changed: {}", err).as_str());
+            false
+        }
+    }
+}
+
+// dome compact
+pub fn command_compact() {
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    if !backup_before(&master_password, "compact") {
+        return;
+    }
+
+    match compact_vault(&master_password) {
+        Ok(freed) => println!("Vault was compacted, {} bytes were freed.", freed),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome convert <layout>
+pub fn command_convert(name: Option<&str>) {
+    let name = match name {
+        Some(name) => name,
+        None => return println!("Vault uses the {} layout.", storage::layout().name()),
+    };
+
+    let layout = match storage::Layout::from_name(name) {
+        Some(layout) => layout,
+        None => {
+            return cli::error(
+                format!("[ERROR] Unknown layout {}. Use files or single-file.", name).as_str(),
+            )
+        }
+    };
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    if !backup_before(&master_password, "convert") {
+        return;
+    }
+
+    match storage::convert(layout) {
+        Ok(()) => println!("Vault was converted to the {} layout.", name),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome cipher <cipher>
+pub fn command_cipher(name: Option<&str>) {
+    let name = match name {
+        Some(name) => name,
+        None => {
+            if let Some(index_set) = read_index_set() {
+                println!("Vault uses the {} cipher.", index_set.cipher.name());
+            }
+            return;
+        }
+    };
+
+    let cipher = match Cipher::from_name(name) {
+        Some(cipher) => cipher,
+        None => {
+            return cli::error(
+                format!(
+                    "[ERROR] Unknown cipher {}. Supported ciphers: {}",
+                    name,
+                    Cipher::NAMES.join(", ")
+                )
+                .as_str(),
+            )
+        }
+    };
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    // Records are rewritten even when the vault already uses the cipher, so records left
+    // from an interrupted migration are moved too
+    let reencrypted = match reencrypt_vault(&master_password, &master_password, cipher) {
+        Ok(reencrypted) => reencrypted,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    if !reencrypted.skipped.is_empty() {
+        cli::warn(
+            format!(
+                "[CIPHER] {} can't be decrypted with this master password and will keep its cipher.",
+                reencrypted.skipped.join(", ")
+            )
+            .as_str(),
+        );
+    }
+
+    if !backup_before(&master_password, "cipher") {
+        return;
+    }
+
+    match save_vault(&reencrypted.vault, &reencrypted.index_set) {
+        Ok(()) => println!("Vault now uses the {} cipher.", cipher.name()),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome sync --remote <url>
+// dome sync --dir <path>
+pub fn command_sync(remote: Option<&str>, dir: Option<&str>) {
+    if let Some(path) = dir {
+        if let Err(err) = sync::directory::set_dir(Path::new(path)) {
+            return cli::error(format!("[ERROR] {}", err).as_str());
+        }
+        println!("Shared directory was set to {}.", path);
+    }
+
+    if remote.is_none() && sync::directory::is_configured() {
+        return command_sync_directory();
+    }
+
+    if let Some(url) = remote {
+        if let Err(err) = sync::set_remote(url) {
+            return cli::error(format!("[ERROR] {}", err).as_str());
+        }
+        println!("Remote was set to {}.", url);
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let report = match sync::sync(&master_password) {
+        Ok(report) => report,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    for domain in &report.conflicts {
+        cli::warn(
+            format!(
+                "[CONFLICT] {} was changed on both sides, local version was kept.",
+                domain
+            )
+            .as_str(),
+        );
+    }
+
+    match report.remote {
+        Some(url) if report.updated => println!("Vault was synchronized with {} and updated.", url),
+        Some(url) => println!("Vault was synchronized with {}.", url),
+        None => println!(
+            "Changes were committed to {}. Set a remote with 'sync --remote <url>' to share them.",
+            storage::sync_dir_path().display()
+        ),
+    }
+}
+
+fn command_sync_directory() {
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let report = match sync::directory::sync(&master_password) {
+        Ok(report) => report,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    if !report.updated.is_empty() {
+        println!(
+            "Changes of other devices were merged: {}.",
+            report.updated.join(", ")
+        );
+    }
+
+    if !report.conflicts.is_empty() {
+        cli::warn(
+            format!(
+                "[CONFLICT] Changed on more devices: {}. Resolve them with 'conflicts'.",
+                report.conflicts.join(", ")
+            )
+            .as_str(),
+        );
+    }
+
+    println!("Vault was synchronized.");
+}
+
+// dome conflicts
+// dome conflicts <domain> --keep <local|other>
+pub fn command_conflicts(domain: Option<&str>, keep: Option<&str>) {
+    let domain = match domain {
+        Some(domain) => domain,
+        None => return print_conflicts(),
+    };
+
+    let keep_other = match keep {
+        Some("local") => false,
+        Some("other") => true,
+        _ => return cli::error("[ERROR] Choose the version with --keep local or --keep other."),
+    };
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    match sync::directory::resolve(domain, keep_other, &master_password) {
+        Ok(()) => println!("Conflict of {} was resolved.", domain),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+fn print_conflicts() {
+    let conflicts = match sync::directory::conflicts() {
+        Ok(conflicts) => conflicts,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    if conflicts.is_empty() {
+        return println!("There are no conflicts.");
+    }
+
+    let backend = storage::open();
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    let username = |entry: Option<Entry>| match entry {
+        Some(entry) => entry.username,
+        None => String::from("(removed)"),
+    };
+
+    let rows: Vec<Vec<String>> = conflicts
+        .iter()
+        .map(|conflict| {
+            let local = index_set
+                .find(&conflict.domain)
+                .map(|(_, e)| Entry::deserialize(&backend.read_entry(e.value)));
+            let other = conflict
+                .change
+                .entry
+                .as_ref()
+                .map(|e| Entry::deserialize(&e.record));
+
+            vec![
+                conflict.domain.clone(),
+                username(local),
+                conflict.device.clone(),
+                username(other),
+            ]
+        })
+        .collect();
+
+    cli::print_table(&["Domain", "Local", "Other device", "Other"], &rows);
+}
+
+// dome team <action>
+pub fn command_team(action: Option<&str>, args: &[String], name: Option<&str>) {
+    let action = match action {
+        Some(action) => action,
+        None => return println!("Team action not specified."),
+    };
+
+    let actions = [
+        "init",
+        "key",
+        "add-member",
+        "remove-member",
+        "trust",
+        "list",
+        "get",
+    ];
+    if !actions.contains(&action) {
+        return cli::error(format!("[ERROR] Unknown team action {}.", action).as_str());
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let identity = match Identity::load(&master_password) {
+        Ok(identity) => identity,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    let result = match action {
+        "init" => team_init(args.first(), name, &identity),
+        "key" => {
+            println!("{}", identity.public_text());
+            Ok(())
+        }
+        "trust" => team_trust(args.first(), name),
+        "list" => team_list(&identity),
+        "get" => team_get(args.first(), &identity),
+        _ => team_change(action, args.first(), name, &identity),
+    };
+
+    if let Err(err) = result {
+        cli::error(format!("[ERROR] {}", err).as_str());
+    }
+}
+
+fn team_init(path: Option<&String>, name: Option<&str>, identity: &Identity) -> Result<(), String> {
+    let path = path.ok_or_else(|| String::from("Path of the team vault not specified."))?;
+    let name = name.unwrap_or("me");
+
+    if team::init(Path::new(path), name, identity)? {
+        println!(
+            "Team vault {} was created, you are its member {}.",
+            path, name
+        );
+    } else {
+        println!("Team vault {} was set.", path);
+    }
+    Ok(())
+}
+
+// Pins the key of a member added by someone else, after it was checked with the member
+fn team_trust(key: Option<&String>, name: Option<&str>) -> Result<(), String> {
+    let key = key.ok_or_else(|| String::from("Public key not specified."))?;
+    let name = name.ok_or_else(|| String::from("Name of the member not specified."))?;
+    let key = team::parse_public_key(key)?;
+
+    let mut pins = Pins::load()?;
+    pins.pin(name, key);
+    pins.save()?;
+
+    let team = Team::read(&team::team_path()?)?;
+    match team.member(name) {
+        Some(member) if member.public == key => println!("Key of {} was pinned.", name),
+        Some(_) => cli::warn(
+            format!(
+                "[TEAM] Key of {} was pinned, but the team vault has a different one.",
+                name
+            )
+            .as_str(),
+        ),
+        None => cli::warn(
+            format!(
+                "[TEAM] Key of {} was pinned, but {} is not a member of the team.",
+                name, name
+            )
+            .as_str(),
+        ),
+    }
+    Ok(())
+}
+
+fn team_list(identity: &Identity) -> Result<(), String> {
+    let team = Team::read(&team::team_path()?)?;
+    let own = team.name_of(identity).unwrap_or_default();
+    let pins = Pins::load()?;
+
+    let rows: Vec<Vec<String>> = team
+        .members
+        .iter()
+        .map(|member| {
+            let marker = if member.name == own { " (you)" } else { "" };
+            let pinned = match pins.get(&member.name) {
+                _ if member.name == own => "",
+                Some(key) if *key == member.public => "yes",
+                Some(_) => "CHANGED",
+                None => "no",
+            };
+            vec![
+                format!("{}{}", member.name, marker),
+                team::encode_public_key(&member.public),
+                String::from(pinned),
+            ]
+        })
+        .collect();
+    cli::print_table(&["Member", "Public key", "Pinned"], &rows);
+
+    let rows: Vec<Vec<String>> = team
+        .entries
+        .iter()
+        .map(|entry| {
+            let members: Vec<&str> = entry.wraps.iter().map(|w| w.member.as_str()).collect();
+            vec![entry.domain.clone(), members.join(", ")]
+        })
+        .collect();
+    cli::print_table(&["Domain", "Shared with"], &rows);
+    Ok(())
+}
+
+fn team_get(domain: Option<&String>, identity: &Identity) -> Result<(), String> {
+    let domain = domain.ok_or_else(|| String::from("Domain not specified."))?;
+    let secret = Team::read(&team::team_path()?)?.get(domain, identity)?;
+
+    cli::print_entry(domain, &secret.username, &secret.password);
+    Ok(())
+}
+
+// Member changes need a member of the team, only members can re-key the entries
+fn team_change(
+    action: &str,
+    arg: Option<&String>,
+    name: Option<&str>,
+    identity: &Identity,
+) -> Result<(), String> {
+    let path = team::team_path()?;
+    let mut team = Team::read(&path)?;
+    if team.name_of(identity).is_none() {
+        return Err(String::from("You are not a member of the team."));
+    }
+
+    let mut pins = Pins::load()?;
+
+    if action == "add-member" {
+        let key = arg.ok_or_else(|| String::from("Public key not specified."))?;
+        let name = name.ok_or_else(|| String::from("Name of the member not specified."))?;
+        let key = team::parse_public_key(key)?;
+        team.add_member(name, key)?;
+        team.write(&path)?;
+        pins.pin(name, key);
+        pins.save()?;
+        println!("{} was added to the team.", name);
+    } else {
+        let name = arg.ok_or_else(|| String::from("Member not specified."))?;
+        let rekeyed = team.remove_member(name, identity, &pins)?;
+        team.write(&path)?;
+        pins.unpin(name);
+        pins.save()?;
+        println!(
+            "{} was removed from the team, {} entries were re-keyed.",
+            name, rekeyed
+        );
+    }
+
+    Ok(())
+}
+
+// dome share <domain> --with <names>
+pub fn command_share(domain: &str, with: Option<&str>) {
+    let with: Vec<String> = match with {
+        Some(names) => names
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => return cli::error("[ERROR] Choose members with --with <names>."),
+    };
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+    let (entry, password) = match get_entry(domain, &master_password) {
+        Ok(entry) => entry,
+        Err(err) => return cli::error(&err),
+    };
+
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+    let url = index_set
+        .find(domain)
+        .map(|(_, index_entry)| index_entry.url.clone())
+        .unwrap_or_default();
+    let secret = team::Secret {
+        username: entry.username,
+        password,
+        url,
+    };
+
+    let share = || -> Result<(), String> {
+        let identity = Identity::load(&master_password)?;
+        let path = team::team_path()?;
+        let mut team = Team::read(&path)?;
+        team.share(&identity, &Pins::load()?, domain, &secret, &with)?;
+        team.write(&path)
+    };
+
+    match share() {
+        Ok(()) => println!("{} was shared with {}.", domain, with.join(", ")),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome recovery split --shares <count> --threshold <count>
+// dome recovery combine
+pub fn command_recovery(action: Option<&str>, shares: Option<&str>, threshold: Option<&str>) {
+    match action {
+        Some("split") => recovery_split(shares.unwrap_or("5"), threshold.unwrap_or("3")),
+        Some("combine") => recovery_combine(),
+        Some(action) => cli::error(format!("[ERROR] Unknown recovery action {}.", action).as_str()),
+        None => println!("Recovery action not specified."),
+    }
+}
+
+fn recovery_split(shares: &str, threshold: &str) {
+    let (shares, threshold) = match (shares.parse::<u8>(), threshold.parse::<u8>()) {
+        (Ok(shares), Ok(threshold)) => (shares, threshold),
+        _ => return cli::error("[ERROR] Shares and threshold must be numbers from 1 to 255."),
+    };
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    print_shares(&master_password, shares, threshold);
+}
+
+// Splits the master password and prints the shares. They rebuild the master password
+// itself, not a key of the vault, so the split is remembered and offered again when the
+// password changes.
+fn print_shares(master_password: &str, count: u8, threshold: u8) {
+    let shares = match recovery::split_password(master_password, count, threshold) {
+        Ok(shares) => shares,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    cli::warn(
+        format!(
+            "[RECOVERY] Give every share to a different person. Any {} of them rebuild the master password and unlock the vault. After dome passwd they only rebuild the old password.",
+            threshold
+        )
+        .as_str(),
+    );
+    for share in &shares {
+        println!(
+            "\nShare {} of {}:\n{}",
+            share.x,
+            shares.len(),
+            share.to_text()
+        );
+    }
+
+    if let Err(err) = recovery::record_issued(count, threshold) {
+        cli::warn(format!("[RECOVERY] {}", err).as_str());
+    }
+}
+
+// Shares of the old master password don't unlock the vault anymore, only the backups made
+// before the change. New ones are made with the same count and threshold.
+fn reissue_shares(master_password: &str) {
+    let Some((count, threshold)) = recovery::issued() else {
+        return;
+    };
+
+    cli::warn("[RECOVERY] Recovery shares made before rebuild the old master password. They no longer unlock the vault, only backups made before the change.");
+    let question = format!(
+        "Split the new master password into {} shares again? [y/n]: ",
+        count
+    );
+    if cli::get_confirmation(&question) {
+        return print_shares(master_password, count, threshold);
+    }
+
+    if let Err(err) = recovery::forget_issued() {
+        cli::warn(format!("[RECOVERY] {}", err).as_str());
+    }
+}
+
+fn recovery_combine() {
+    let mut shares: Vec<Share> = Vec::new();
+
+    // The first share tells how many are needed
+    while shares.is_empty() || shares.len() < shares[0].threshold as usize {
+        let text = cli::read_password(&format!("Share {}: ", shares.len() + 1));
+        if text.trim().is_empty() {
+            return println!("Action was aborted.");
+        }
+
+        match Share::parse(&text) {
+            Ok(share) if shares.iter().any(|s| s.x == share.x) => {
+                cli::warn("[RECOVERY] This share was already entered.")
+            }
+            Ok(share) => shares.push(share),
+            Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+        }
+    }
+
+    let current = match recovery::combine_password(&shares) {
+        Ok(password) => password,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    if let Err(err) = verify_master_password(&current) {
+        return cli::error(&err);
+    }
+
+    println!("Master password was recovered. Create new master password.");
+    let new = match cli::create_password(NEW_MASTER_PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
+        Ok(password) if password_allowed(&password, &[]) => password,
+        Ok(_) => return,
+        Err(err) => return println!("{}", err),
+    };
+
+    if let Err(err) = keyfile::check_password(&new) {
+        return cli::error(format!("[ERROR] {}", err).as_str());
+    }
+
+    // Shares recover the vault even when the key file was lost
+    match keyfile::unlock(&new) {
+        Ok(new) => {
+            change_master_password(&current, &new);
+        }
+        Err(err) => {
+            cli::warn(
+                format!(
+                    "[RECOVERY] {} New master password will be used without it.",
+                    err
+                )
+                .as_str(),
+            );
+            if change_master_password(&current, &new) {
+                if let Err(err) = keyfile::clear() {
+                    cli::error(format!("[ERROR] {}", err).as_str());
+                }
+            }
+        }
+    }
+}
+
+// dome audit <strength|reuse|breached|stale> --format <table|json|csv>
+// dome audit breached --file <path>
+pub fn command_audit(report: Option<&str>, format: &str, file: Option<&str>) {
+    type Audit = Box<dyn FnMut(&[NewEntry]) -> Result<audit::Report, String>>;
+
+    let output = match audit::Output::from_name(format) {
+        Some(output) => output,
+        None => return cli::error(format!("[ERROR] Unknown output format {}.", format).as_str()),
+    };
+
+    let Some(index_set) = read_index_set() else {
+        return;
+    };
+
+    let mut report: Audit = match report {
+        // Stale passwords are found from the index alone, nothing is decrypted
+        Some("stale") => return print_report(&audit::stale(&index_set, time::now()), output),
+        Some("strength") => Box::new(|entries| Ok(audit::strength(entries))),
+        Some("reuse") => Box::new(|entries| Ok(audit::reuse(entries))),
+        Some("breached") => {
+            let path = match file {
+                Some(path) => path,
+                None => return println!("Path to the Pwned Passwords file not specified."),
+            };
+            let mut list = match breach::open(Path::new(path)) {
+                Ok(list) => list,
+                Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+            };
+            Box::new(move |entries| audit::breached(entries, &mut list))
+        }
+        Some(report) => {
+            return cli::error(format!("[ERROR] Unknown audit report {}.", report).as_str())
+        }
+        None => return println!("Audit report not specified."),
+    };
+
+    if index_set.is_empty() {
+        return println!("Your vault is empty.");
+    }
+
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+    let (entries, failed) = match export::collect(&master_password) {
+        Ok(collected) => collected,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    if entries.is_empty() {
+        return cli::error(errs::DECRYPTION_FAILED);
+    }
+
+    for domain in &failed {
+        cli::warn(
+            format!(
+                "[AUDIT] {} can't be decrypted with this master password and was skipped.",
+                domain
+            )
+            .as_str(),
+        );
+    }
+
+    let report = match report(&entries) {
+        Ok(report) => report,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    print_report(&report, output);
+}
+
+fn print_report(report: &audit::Report, output: audit::Output) {
+    match output {
+        audit::Output::Table if report.rows.is_empty() => println!("Nothing to report."),
+        audit::Output::Table => cli::print_table(&report.columns, &report.rows),
+        audit::Output::Json => println!("{}", report.to_json()),
+        audit::Output::Csv => print!("{}", report.to_csv()),
+    }
+}
+
+// dome expiry <domain> <days>
+// dome expiry --tag <tag> <days>
+pub fn command_expiry(domain: Option<&str>, tag: Option<&str>, days: Option<&str>) {
+    let days = match days.map(|days| days.parse::<u32>()) {
+        Some(Ok(days)) => days,
+        Some(Err(_)) => return println!("Days must be a number, 0 removes the policy."),
+        None => return println!("Days not specified."),
+    };
+
+    if let Some(tag) = tag {
+        let tag = index_entry::normalize_tag(tag);
+        let key = format!("{}{}", config::TAG_EXPIRY_PREFIX, tag);
+        return match config::set(&key, &days.to_string()) {
+            Ok(()) if days == 0 => println!("Passwords tagged {} follow expiry.days.", tag),
+            Ok(()) => println!("Passwords tagged {} expire after {} days.", tag, days),
+            Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+        };
+    }
+
+    let Some(domain) = domain else {
+        return println!("Domain not specified.");
+    };
+
+    // Expiry of the entry is sealed with the master password, the tag policies are settings
+    let master_password = match read_master_password() {
+        Ok(master_password) => master_password,
+        Err(err) => return cli::error(&err),
+    };
+
+    let result = change_index(domain, &master_password, &mut |index_set| {
+        if let Some((_, index_entity)) = index_set.find(domain) {
+            let mut index_entity = index_entity.clone();
+            index_entity.expiry_days = days;
+            index_set.replace(&index_entity);
+        }
+    });
+
+    match result {
+        Ok(()) if days == 0 => println!("{} follows the policy of its tags.", domain),
+        Ok(()) => println!(
+            "Password of {} expires {} days after it was changed.",
+            domain, days
+        ),
+        Err(err) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// dome log --domain <domain> --event <event>
+// dome log verify
+pub fn command_log(action: Option<&str>, domain: Option<&str>, event: Option<&str>) {
+    if action == Some("verify") {
+        let master_password = match read_master_password() {
+            Ok(master_password) => master_password,
+            Err(err) => return cli::error(&err),
+        };
+        return match log::verify(&master_password) {
+            Ok(count) => println!("Log is intact, {} events were verified.", count),
+            Err(err) => cli::error(format!("[TAMPERED] {}", err).as_str()),
+        };
+    }
+
+    if let Some(action) = action {
+        return cli::error(format!("[ERROR] Unknown log action {}.", action).as_str());
+    }
+
+    let lines = match log::read() {
+        Ok(lines) => lines,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .filter(|line| domain.is_none_or(|domain| line.domain == domain))
+        .filter(|line| event.is_none_or(|event| line.event == event))
+        .map(|line| {
+            vec![
+                time::format_time(line.time),
+                line.event.clone(),
+                line.domain.clone(),
+            ]
+        })
+        .collect();
+
+    if rows.is_empty() {
+        return println!("No events were logged.");
+    }
+
+    cli::print_table(&["Time", "Event", "Domain"], &rows);
+}
+
+// dome passwd --keyfile <path> --remove-keyfile
+pub fn command_passwd(key_file: Option<&str>, remove_key_file: bool) {
+    if key_file.is_some() || remove_key_file {
+        return change_key_file(key_file);
+    }
+
+    let current = match read_master_password() {
+        Ok(current) => current,
+        Err(err) => return cli::error(&err),
+    };
+    println!("Create new master password.");
+    let new = match cli::create_password(NEW_MASTER_PASSWORD_TEXT, REPEAT_PASSWORD_TEXT) {
+        Ok(password) if password_allowed(&password, &[]) => match unlock_password(password) {
+            Ok(password) => password,
+            Err(err) => return cli::error(&err),
+        },
+        Ok(_) => return,
+        Err(err) => return println!("{}", err),
+    };
+
+    change_master_password(&current, &new);
+}
+
+// Re-encrypts the vault with the same master password and the new key file, or without one
+// when it's None
+fn change_key_file(key_file: Option<&str>) {
+    if key_file.is_none() && keyfile::configured().is_none() {
+        return println!("Vault doesn't use a key file.");
+    }
+
+    // Shell session only knows the combined password, so the typed one is asked again
+    let password = cli::read_password(MASTER_PASSWORD_TEXT);
+    let current = match keyfile::unlock(&password) {
+        Ok(current) => current,
+        Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+    };
+
+    let current = match try_master_password(current) {
+        Ok(current) => current,
+        Err(err) => return cli::error(&err),
+    };
+
+    let new = match key_file {
+        Some(path) => match use_key_file(Path::new(path)) {
+            Ok(data) => keyfile::composite(&password, &data),
+            Err(err) => return cli::error(format!("[ERROR] {}", err).as_str()),
+        },
+        None => password,
+    };
+
+    if !change_master_password(&current, &new) {
+        return;
+    }
+
+    let result = match key_file {
+        Some(path) => keyfile::set(Path::new(path)),
+        None => keyfile::clear(),
+    };
+
+    match (result, key_file) {
+        (Ok(()), Some(path)) => println!("Vault now requires the key file {}.", path),
+        (Ok(()), None) => println!("Vault no longer requires a key file."),
+        (Err(err), _) => cli::error(format!("[ERROR] {}", err).as_str()),
+    }
+}
+
+// Contents of the key file, a new random key is generated when the file doesn't exist
+fn use_key_file(path: &Path) -> Result<Vec<u8>, String> {
+    if !path.exists() {
+        keyfile::generate(path)?;
+        cli::warn(
+            format!(
+                "[KEYFILE] Generated new key file {}. Keep a copy of it, the vault can't be opened without it.",
+                path.display()
+            )
+            .as_str(),
+        );
+    }
+
+    keyfile::read(path)
+}
+
+// dome init --keyfile <path>
+pub fn command_init(key_file: Option<&str>) {
+    if let Some(path) = key_file {
+        let Some(index_set) = read_index_set() else {
+            return;
+        };
+        if !index_set.is_empty() {
+            return cli::error(
+                "[ERROR] Vault already has entries. Use dome passwd --keyfile <path> to add a key file.",
+            );
+        }
+
+        let path = Path::new(path);
+        if let Err(err) = use_key_file(path).and_then(|_| keyfile::set(path)) {
+            return cli::error(format!("[ERROR] {}", err).as_str());
+        }
+        println!(
+            "Vault requires the key file {} next to the master password.",
+            path.display()
+        );
+    }
+
+    println!("Vault is ready in {}.", storage::dome_dir_path().display());
+}
+
+// Re-encrypts the vault and the team key with the new master password. Returns whether
+// the password was changed.
+fn change_master_password(current: &str, new: &str) -> bool {
+    if new == current {
+        cli::error("[ERROR] New master password is the same as the current one.");
+        return false;
+    }
+
+    let reencrypted = match get_index_set()
+        .and_then(|index_set| reencrypt_vault(current, new, index_set.cipher))
+    {
+        Ok(reencrypted) => reencrypted,
+        Err(err) => {
+            cli::error(format!("[ERROR] {}", err).as_str());
+            return false;
+        }
+    };
+
+    let total = reencrypted.index_set.data.len();
+    if total > 0 && reencrypted.skipped.len() == total {
+        cli::error(errs::DECRYPTION_FAILED);
+        return false;
+    }
+
+    // A record left under the old password could never be read again once it is forgotten
+    if !reencrypted.skipped.is_empty() {
+        cli::error(
+            format!(
+                "[ERROR] {} can't be decrypted with the current master password. Master password was not changed, remove or fix these entries first.",
+                reencrypted.skipped.join(", ")
+            )
+            .as_str(),
+        );
+        return false;
+    }
+
+    if !backup_before(current, "passwd") {
+        return false;
+    }
+
+    match save_vault(&reencrypted.vault, &reencrypted.index_set) {
+        Ok(()) => {
+            if let Err(err) = team::reencrypt_identity(current, new) {
+                cli::warn(format!("[PASSWD] Team key was not re-encrypted: {}", err).as_str());
+            }
+            if let Err(err) = log::reencrypt_key(current, new) {
+                cli::warn(
+                    format!("[PASSWD] Key of the log was not re-encrypted: {}", err).as_str(),
+                );
+            }
+            if session_password().is_some() {
+                set_session_password(Some(new.to_string()));
+            }
+            println!(
+                "Master password was changed, {} entries were re-encrypted. Backups made before the change still use the old master password.",
+                total
+            );
+            reissue_shares(new);
+            true
+        }
+        Err(err) => {
+            cli::error(format!("[ERROR] {}", err).as_str());
+            false
+        }
+    }
+}
+
+// dome tui
+pub fn command_tui() {
+    match tui::terminal::TerminalBackend::new() {
+        Ok(mut backend) => tui::run(&mut backend),
+        Err(err) => cli::error(format!("[ERROR] Can't open terminal interface: {}", err).as_str()),
+    }
+}
+
+// dome shell --timeout <minutes>
+pub fn command_shell(timeout_minutes: u64) {
+    if session_password().is_some() {
+        return println!("Shell is already running.");
+    }
+
+    match read_master_password() {
+        Ok(master_password) => {
+            log_event(Event::Unlock, "", &master_password);
+            shell::run(master_password, timeout_minutes)
+        }
+        Err(err) => cli::error(&err),
+    }
+}
+
+// dome --version
+pub fn command_version() {
+    let version = env!("CARGO_PKG_VERSION");
+    println!("Dome - {}", version);
+}
+
+// Finds the entry closest to the query. Asks user to pick one if there are several or the
+// match is weak.
+fn resolve_domain(index_set: &IndexSet, query: &str) -> Option<String> {
+    let items = get_search_items(index_set);
+    let results = search::search(query, &items);
+
+    if results.is_empty() {
+        return None;
+    }
+
+    if let Some(result) = search::best_match(&results) {
+        cli::warn(format!("[SEARCH] Using closest match {}.", result.key).as_str());
+        return Some(result.key.clone());
+    }
+
+    println!("Domain {} was not found. Did you mean:", query);
+    let options: Vec<String> = results.iter().map(|result| result.key.clone()).collect();
+    cli::pick("Select entry (leave empty to cancel): ", &options)
+        .map(|index| options[index].clone())
+}
+
+pub fn get_search_items(index_set: &IndexSet) -> Vec<SearchItem> {
+    let backend = storage::open();
+
+    index_set
+        .data
+        .iter()
+        .map(|index_entry| {
+            let entry = Entry::deserialize(&backend.read_entry(index_entry.value));
+
+            SearchItem {
+                key: index_entry.key.clone(),
+                username: entry.username,
+                url: index_entry.url.clone(),
+                tags: index_entry.tags.clone(),
+            }
+        })
+        .collect()
+}
+
+// Index as it is on disk. Its seal needs the master password and is checked by
+// try_master_password, so every command that asks for the password works on a verified
+// index. Commands that run without the password can't check it: list, search, the stale
+// audit, the shell completion and the TUI list before unlocking only show domains and
+// metadata, and throttle reads its counter before a password is entered, taking the higher
+// copy instead.
+pub fn get_index_set() -> Result<IndexSet, String> {
+    storage::open().list()
+}
+
+// Index for commands that only print their errors, None once the error was printed
+fn read_index_set() -> Option<IndexSet> {
+    get_index_set()
+        .map_err(|err| cli::error(format!("[ERROR] {}", err).as_str()))
+        .ok()
+}
+
+// Runs parsed command. Used both for command line arguments and for lines entered in the shell.
+pub fn execute(c: &Command) {
+    if c.command == "--version" {
+        command_version()
+    }
+
+    if c.command == "help" {
+        command_help()
+    }
+
+    if c.command == "list" {
+        command_list(
+            c.flags.get("--tag").map(|s| s.as_str()),
+            c.flags.get("--folder").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "add" {
+        match c.args.first() {
+            Some(domain) => {
+                let username = match c.flags.get("-u") {
+                    Some(val) => val,
+                    None => "",
+                };
+
+                let tags = match c.flags.get("--tag") {
+                    Some(val) => index_entry::parse_tags(val),
+                    None => Vec::new(),
+                };
+                let folder = match c.flags.get("--folder") {
+                    Some(val) => val,
+                    None => "",
+                };
+
+                let url = match c.flags.get("--url") {
+                    Some(val) => val,
+                    None => "",
+                };
+
+                command_add(domain, username, &tags, folder, url)
+            }
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "edit" {
+        match c.args.first() {
+            Some(domain) => command_edit(
+                domain,
+                c.flags.get("-u").map(|s| s.as_str()),
+                c.flags.get("--url").map(|s| s.as_str()),
+            ),
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "tui" {
+        command_tui()
+    }
+
+    if c.command == "remove" {
+        match c.args.first() {
+            Some(domain) => command_remove(domain),
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "tag" {
+        match c.args.first() {
+            Some(domain) => {
+                let tags = index_entry::parse_tags(&c.args[1..].join(","));
+                let remove = match c.flags.get("--remove") {
+                    Some(val) => index_entry::parse_tags(val),
+                    None => Vec::new(),
+                };
+
+                if tags.is_empty() && remove.is_empty() {
+                    println!("No tags specified.");
+                } else {
+                    command_tag(domain, &tags, &remove)
+                }
+            }
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "mv" {
+        match (c.args.first(), c.args.get(1)) {
+            (Some(domain), Some(folder)) => command_move(domain, folder),
+            (Some(_), None) => println!("Folder not specified."),
+            _ => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "search" {
+        if c.args.is_empty() {
+            println!("Search query not specified.");
+        } else {
+            command_search(&c.args.join(" "))
+        }
+    }
+
+    if c.command == "get" {
+        match c.args.first() {
+            Some(domain) => command_get(domain),
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "gen" {
+        match c.args.first().map(|value| value.parse::<u8>()) {
+            Some(Ok(len)) => command_generate(len),
+            Some(Err(_)) => println!("Length must be a number between 0 and 255"),
+            None => command_generate(8),
+        }
+    }
+
+    if c.command == "import" {
+        match (c.args.first(), c.args.get(1)) {
+            (Some(format), Some(path)) => command_import(
+                format,
+                path,
+                c.flags.get("--duplicates").map_or("skip", |s| s.as_str()),
+                c.flags.contains_key("--dry-run"),
+            ),
+            (Some(_), None) => println!("Path to the exported file not specified."),
+            _ => println!("Format not specified."),
+        }
+    }
+
+    if c.command == "export" {
+        match (c.args.first(), c.args.get(1)) {
+            (Some(format), Some(path)) => command_export(
+                format,
+                path,
+                c.flags.get("--cipher").map_or("aes", |s| s.as_str()),
+            ),
+            (Some(_), None) => println!("Path of the export file not specified."),
+            _ => println!("Format not specified."),
+        }
+    }
+
+    if c.command == "backup" {
+        if c.flags.contains_key("--list") {
+            command_backup_list()
+        } else {
+            command_backup()
+        }
+    }
+
+    if c.command == "restore" {
+        match c.args.first() {
+            Some(name) => command_restore(name),
+            None => println!("Backup not specified."),
+        }
+    }
+
+    if c.command == "config" {
+        command_config(
+            c.args.first().map(|s| s.as_str()),
+            c.args.get(1).map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "compact" {
+        command_compact()
+    }
+
+    if c.command == "convert" {
+        command_convert(c.args.first().map(|s| s.as_str()))
+    }
+
+    if c.command == "cipher" {
+        command_cipher(c.args.first().map(|s| s.as_str()))
+    }
+
+    if c.command == "sync" {
+        command_sync(
+            c.flags.get("--remote").map(|s| s.as_str()),
+            c.flags.get("--dir").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "team" {
+        command_team(
+            c.args.first().map(|s| s.as_str()),
+            c.args.get(1..).unwrap_or_default(),
+            c.flags.get("--name").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "share" {
+        match c.args.first() {
+            Some(domain) => command_share(domain, c.flags.get("--with").map(|s| s.as_str())),
+            None => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "conflicts" {
+        command_conflicts(
+            c.args.first().map(|s| s.as_str()),
+            c.flags.get("--keep").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "recovery" {
+        command_recovery(
+            c.args.first().map(|s| s.as_str()),
+            c.flags.get("--shares").map(|s| s.as_str()),
+            c.flags.get("--threshold").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "init" {
+        command_init(c.flags.get("--keyfile").map(|s| s.as_str()))
+    }
+
+    if c.command == "audit" {
+        command_audit(
+            c.args.first().map(|s| s.as_str()),
+            c.flags.get("--format").map_or("table", |s| s.as_str()),
+            c.flags.get("--file").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "expiry" {
+        match c.args.as_slice() {
+            [days] if c.flags.contains_key("--tag") => {
+                command_expiry(None, c.flags.get("--tag").map(|s| s.as_str()), Some(days))
+            }
+            [domain, days] => command_expiry(Some(domain), None, Some(days)),
+            [_] => println!("Days not specified."),
+            _ => println!("Domain not specified."),
+        }
+    }
+
+    if c.command == "log" {
+        command_log(
+            c.args.first().map(|s| s.as_str()),
+            c.flags.get("--domain").map(|s| s.as_str()),
+            c.flags.get("--event").map(|s| s.as_str()),
+        )
+    }
+
+    if c.command == "passwd" {
+        command_passwd(
+            c.flags.get("--keyfile").map(|s| s.as_str()),
+            c.flags.contains_key("--remove-keyfile"),
+        )
+    }
+
+    if c.command == "shell" {
+        let timeout = match c.flags.get("--timeout") {
+            Some(value) => value.parse::<u64>().ok(),
+            None => Some(shell::DEFAULT_TIMEOUT_MINUTES),
+        };
+
+        match timeout {
+            Some(minutes) => command_shell(minutes),
+            None => println!("Timeout must be a number of minutes."),
+        }
+    }
+}
+
+pub struct Command {
+    pub command: String,
+    pub args: Vec<String>,
+    pub flags: HashMap<String, String>,
+}
+
+impl Command {
+    pub fn new(command: &str) -> Command {
+        Command {
+            command: String::from(command),
+            args: Vec::new(),
+            flags: HashMap::new(),
+        }
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    if args.len() < 2 {
+        return Err(errs::INVALID_COMMAND.to_string());
+    }
+
+    let command = &args[1];
+    if !COMMANDS.contains(&command.as_str()) {
+        return Err(errs::INVALID_COMMAND.to_string());
+    }
+
+    let mut comm = Command::new(command);
+    if args.len() == 2 {
+        return Ok(comm);
+    }
+
+    let command_args = &args[2..];
+    let len = command_args.len();
+    let mut index = 0;
+
+    while index < len {
+        let current_arg = &command_args[index];
+        if SWITCHES.contains(&current_arg.as_str()) {
+            comm.flags.insert(current_arg.clone(), "".to_string());
+            index += 1;
+        } else if current_arg.starts_with("-") {
             let next_arg = if len > index + 1 {
                 command_args[index + 1].clone()
             } else {
@@ -35,3 +35,328 @@ fn test_parse_args() {
 
     assert_eq!(command.args.first().unwrap(), "domain");
 }
+
+#[test]
+fn test_parse_switches() {
+    let args: Vec<String> = ["dome", "import", "--dry-run", "bitwarden", "export.json"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let command = parse_args(&args).unwrap();
+
+    assert!(command.flags.contains_key("--dry-run"));
+    assert_eq!(command.args, vec!["bitwarden", "export.json"]);
+}
+
+fn new_entry(domain: &str, password: &str) -> NewEntry {
+    NewEntry {
+        domain: String::from(domain),
+        username: String::from("john"),
+        password: String::from(password),
+        url: String::new(),
+        tags: vec![String::from("mail")],
+        folder: String::new(),
+    }
+}
+
+#[test]
+fn test_reencrypt_vault() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    add_entry(&new_entry("gmail", "gm41l"), "current").unwrap();
+    add_entry(&new_entry("github", "g1thub"), "current").unwrap();
+    add_entry(&new_entry("other", "0ther"), "different").unwrap();
+    edit_entry(
+        "gmail",
+        &EntryChanges {
+            password: Some(String::from("gm41l-2")),
+            ..Default::default()
+        },
+        "current",
+    )
+    .unwrap();
+
+    let reencrypted = reencrypt_vault("current", "new", Cipher::Aes256Gcm).unwrap();
+    assert_eq!(reencrypted.skipped, vec!["other"]);
+    save_vault(&reencrypted.vault, &reencrypted.index_set).unwrap();
+
+    assert_eq!(get_entry("gmail", "new").unwrap().1, "gm41l-2");
+    assert_eq!(get_entry("github", "new").unwrap().1, "g1thub");
+    assert_eq!(get_entry("other", "different").unwrap().1, "0ther");
+    assert!(get_entry("gmail", "current").is_err());
+    assert_eq!(get_index_set().unwrap().with_tag("mail").len(), 3);
+
+    // Unreferenced record of the edit is gone
+    assert_eq!(
+        storage::open().records_len(),
+        reencrypted.vault.len() as u64
+    );
+}
+
+#[test]
+fn test_passwd_keeps_vault_with_foreign_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    add_entry(&new_entry("gmail", "gm41l"), "current").unwrap();
+    add_entry(&new_entry("other", "0ther"), "different").unwrap();
+
+    assert!(!change_master_password("current", "new"));
+    assert_eq!(get_entry("gmail", "current").unwrap().1, "gm41l");
+    assert_eq!(get_entry("other", "different").unwrap().1, "0ther");
+}
+
+#[test]
+fn test_remove_leaves_tombstone() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    config::set("compact.garbage_percent", "0").unwrap();
+
+    add_entry(&new_entry("gmail", "gm41l"), "pw").unwrap();
+    add_entry(&new_entry("github", "g1thub"), "pw").unwrap();
+    let gmail = get_index_set().unwrap().find("gmail").unwrap().1.value;
+    let github = get_index_set().unwrap().find("github").unwrap().1.value;
+    let len = storage::open().records_len();
+
+    remove_entry("gmail", "pw").unwrap();
+
+    // Nothing moves, the record is wiped in place
+    assert_eq!(storage::open().records_len(), len);
+    assert_eq!(
+        get_index_set().unwrap().find("github").unwrap().1.value,
+        github
+    );
+    assert!(storage::open().read_entry(gmail)[..28]
+        .iter()
+        .all(|b| *b == 0));
+    assert_eq!(get_index_set().unwrap().garbage, github - gmail);
+    assert_eq!(get_entry("github", "pw").unwrap().1, "g1thub");
+
+    assert_eq!(compact_vault("pw").unwrap(), github - gmail);
+    assert_eq!(get_index_set().unwrap().garbage, 0);
+    assert_eq!(get_index_set().unwrap().find("github").unwrap().1.value, 0);
+    assert_eq!(get_entry("github", "pw").unwrap().1, "g1thub");
+}
+
+#[test]
+fn test_automatic_compaction() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    config::set("compact.garbage_percent", "50").unwrap();
+
+    for domain in ["a", "b", "c", "d"] {
+        add_entry(&new_entry(domain, "s3cret"), "pw").unwrap();
+    }
+    let len = storage::open().records_len();
+
+    remove_entry("a", "pw").unwrap();
+    remove_entry("b", "pw").unwrap();
+    assert_eq!(storage::open().records_len(), len);
+
+    // Third removal pushes garbage over the half of the vault
+    remove_entry("c", "pw").unwrap();
+    assert_eq!(storage::open().records_len(), len / 4);
+    assert_eq!(get_index_set().unwrap().garbage, 0);
+    assert_eq!(get_entry("d", "pw").unwrap().1, "s3cret");
+}
+
+#[test]
+fn test_timestamps() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+
+    add_entry(&new_entry("gmail", "gm41l"), "master").unwrap();
+    let added = get_index_set().unwrap().find("gmail").unwrap().1.clone();
+    assert!(added.created > 0);
+    assert_eq!(added.modified, added.created);
+    assert_eq!(added.accessed, 0);
+
+    // Pretend the entry is old
+    let mut old = added.clone();
+    old.created = 1_000;
+    old.modified = 1_000;
+    let mut index_set = get_index_set().unwrap();
+    index_set.replace(&old);
+    storage::open().write_index(&index_set).unwrap();
+
+    mark_accessed("gmail").unwrap();
+    let tags = EntryChanges {
+        tags: Some(vec![String::from("mail")]),
+        ..Default::default()
+    };
+    edit_entry("gmail", &tags, "master").unwrap();
+    let tagged = get_index_set().unwrap().find("gmail").unwrap().1.clone();
+    assert!(tagged.accessed > 0);
+    assert_eq!(tagged.modified, 1_000);
+
+    // Username and password given as they already are, like the TUI edit dialog does
+    let unchanged = EntryChanges {
+        username: Some(String::from("john")),
+        password: Some(String::from("gm41l")),
+        tags: Some(vec![String::from("work")]),
+        ..Default::default()
+    };
+    edit_entry("gmail", &unchanged, "master").unwrap();
+    let retagged = get_index_set().unwrap().find("gmail").unwrap().1.clone();
+    assert_eq!(retagged.modified, 1_000);
+    assert_eq!(retagged.id, tagged.id);
+    assert_eq!(retagged.tags, vec!["work"]);
+
+    let password = EntryChanges {
+        password: Some(String::from("new")),
+        ..Default::default()
+    };
+    edit_entry("gmail", &password, "master").unwrap();
+    let changed = get_index_set().unwrap().find("gmail").unwrap().1.clone();
+    assert_eq!(changed.created, 1_000);
+    assert!(changed.modified > 1_000);
+}
+
+#[test]
+fn test_records_are_bound_to_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+
+    add_entry(&new_entry("gmail", "gm41l"), "pw").unwrap();
+    add_entry(&new_entry("github", "g1thub"), "pw").unwrap();
+    let gmail_record =
+        storage::open().read_entry(get_index_set().unwrap().find("gmail").unwrap().1.value);
+
+    // Offsets of the entries swapped in the index
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list().unwrap();
+            let mut gmail = index_set.find("gmail").unwrap().1.clone();
+            let mut github = index_set.find("github").unwrap().1.clone();
+            (gmail.value, github.value) = (github.value, gmail.value);
+            index_set.replace(&gmail);
+            index_set.replace(&github);
+            backend.write_index(&index_set)
+        })
+        .unwrap();
+
+    assert!(get_entry("gmail", "pw").is_err());
+    assert!(get_entry("github", "pw").is_err());
+
+    // Record of the same domain spliced in from another vault with the same password
+    let other = tempfile::tempdir().unwrap();
+    storage::use_memory(other.path());
+    add_entry(&new_entry("gmail", "other"), "pw").unwrap();
+
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list().unwrap();
+            let mut gmail = index_set.find("gmail").unwrap().1.clone();
+            gmail.value = backend.write_entry(&gmail_record)?;
+            index_set.replace(&gmail);
+            backend.write_index(&index_set)
+        })
+        .unwrap();
+
+    assert!(get_entry("gmail", "pw").is_err());
+}
+
+#[test]
+fn test_index_seal() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+
+    for domain in ["gmail", "github"] {
+        add_entry(&new_entry(domain, "s3cret"), "pw").unwrap();
+    }
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_ok());
+
+    // Metadata changed with the master password is sealed again
+    let tag = [String::from("work")];
+    change_index("gmail", "pw", &mut |set| {
+        set.add_tags("gmail", &tag);
+    })
+    .unwrap();
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_ok());
+
+    // URL, tags and expiry changed behind Dome's back
+    let (records, index) = storage::open().snapshot().unwrap();
+    let changes: [fn(&mut IndexEntry); 3] = [
+        |entry| entry.url = String::from("https://phishing.example.com"),
+        |entry| entry.tags.clear(),
+        |entry| entry.expiry_days = 10_000,
+    ];
+    for change in changes {
+        let mut index_set = IndexSet::try_from_binary(&index).unwrap();
+        let mut gmail = index_set.find("gmail").unwrap().1.clone();
+        change(&mut gmail);
+        index_set.replace(&gmail);
+        storage::open()
+            .replace(&records, &index_set.serialize())
+            .unwrap();
+        assert!(get_index_set().unwrap().verify_seal("pw", 0).is_err());
+    }
+    storage::open().replace(&records, &index).unwrap();
+
+    remove_entry("github", "pw").unwrap();
+    let (records, index) = storage::open().snapshot().unwrap();
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_ok());
+
+    // Entry dropped from the index behind Dome's back
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list().unwrap();
+            index_set.remove("gmail", 0);
+            backend.write_index(&index_set)
+        })
+        .unwrap();
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_err());
+
+    // MAC stripped from the index
+    let mut index_set = IndexSet::try_from_binary(&index).unwrap();
+    index_set.mac.clear();
+    storage::open()
+        .replace(&records, &index_set.serialize())
+        .unwrap();
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_err());
+}
+
+#[test]
+fn test_cipher_migration() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    config::set("compact.garbage_percent", "0").unwrap();
+
+    add_entry(&new_entry("gmail", "gm41l"), "pw").unwrap();
+    add_entry(&new_entry("github", "g1thub"), "pw").unwrap();
+
+    let reencrypted = reencrypt_vault("pw", "pw", Cipher::XChaCha20Poly1305).unwrap();
+    save_vault(&reencrypted.vault, &reencrypted.index_set).unwrap();
+    assert_eq!(get_index_set().unwrap().cipher, Cipher::XChaCha20Poly1305);
+
+    // New records use the cipher of the vault, old and new ones are read alike
+    add_entry(&new_entry("yahoo", "y4hoo"), "pw").unwrap();
+    for (domain, password) in [("gmail", "gm41l"), ("github", "g1thub"), ("yahoo", "y4hoo")] {
+        let (entry, text_password) = get_entry(domain, "pw").unwrap();
+        assert_eq!(entry.cipher, Cipher::XChaCha20Poly1305);
+        assert_eq!(text_password, password);
+    }
+
+    // Tombstones of the longer records keep their size
+    remove_entry("gmail", "pw").unwrap();
+    assert_eq!(get_entry("github", "pw").unwrap().1, "g1thub");
+    compact_vault("pw").unwrap();
+    assert_eq!(get_entry("yahoo", "pw").unwrap().1, "y4hoo");
+    assert!(get_index_set().unwrap().verify_seal("pw", 0).is_ok());
+}
+
+#[test]
+fn test_damaged_index_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    add_entry(&new_entry("gmail", "gm41l"), "pw").unwrap();
+
+    let mut index = fs::read(storage::index_file_path()).unwrap();
+    index.truncate(index.len() - 3);
+    fs::write(storage::index_file_path(), index).unwrap();
+
+    assert!(get_index_set().is_err());
+    assert!(verify_master_password("pw").is_err());
+    assert!(add_entry(&new_entry("github", "g1thub"), "pw").is_err());
+}
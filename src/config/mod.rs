@@ -0,0 +1,135 @@
+use crate::storage;
+use std::collections::BTreeMap;
+use std::fs;
+
+pub struct Setting {
+    pub key: &'static str,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+// Rotation policy of a tag, like "expiry.tag.bank = 30"
+pub const TAG_EXPIRY_PREFIX: &str = "expiry.tag.";
+
+pub const SETTINGS: [Setting; 9] = [
+    Setting {
+        key: "backup.keep",
+        default: "10",
+        description: "Number of newest backups that are kept.",
+    },
+    Setting {
+        key: "backup.max_age_days",
+        default: "0",
+        description:
+            "Older backups are deleted, the newest one always stays. 0 keeps them forever.",
+    },
+    Setting {
+        key: "compact.garbage_percent",
+        default: "25",
+        description:
+            "Vault is compacted once deleted entries take this percent of it. 0 disables it.",
+    },
+    Setting {
+        key: "strength.min_score",
+        default: "2",
+        description: "Passwords scored below this, from 0 to 4, are reported on add and edit.",
+    },
+    Setting {
+        key: "strength.block",
+        default: "0",
+        description: "1 refuses passwords below the minimum score, 0 only warns about them.",
+    },
+    Setting {
+        key: "expiry.days",
+        default: "0",
+        description: "Passwords are due for a change after this many days. 0 never expires them.",
+    },
+    Setting {
+        key: "attempts.limit",
+        default: "0",
+        description:
+            "Wrong master passwords in a row before the vault is locked. 0 never locks it.",
+    },
+    Setting {
+        key: "attempts.lockout_hours",
+        default: "24",
+        description: "How long the vault stays locked once the limit of attempts is reached.",
+    },
+    Setting {
+        key: "attempts.destroy",
+        default: "0",
+        description: "1 deletes the vault and its backups at the limit instead of locking it.",
+    },
+];
+
+// Config file has one "key = value" pair per line
+pub fn parse(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty() && !key.starts_with('#'))
+        .collect()
+}
+
+pub fn serialize(values: &BTreeMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(key, value)| format!("{} = {}\n", key, value))
+        .collect()
+}
+
+pub fn read() -> BTreeMap<String, String> {
+    let text = fs::read_to_string(storage::config_file_path()).unwrap_or_default();
+    parse(&text)
+}
+
+// Configured value, or the default of the setting
+pub fn get(key: &str) -> String {
+    match read().remove(key) {
+        Some(value) => value,
+        None => setting(key).map_or("", |s| s.default).to_string(),
+    }
+}
+
+pub fn get_number(key: &str) -> u64 {
+    get(key).parse().unwrap_or_default()
+}
+
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    if setting(key).is_none() && tag_of(key).is_none() {
+        return Err(format!("Unknown setting {}.", key));
+    }
+
+    if value.parse::<u64>().is_err() {
+        return Err(format!("Value of {} must be a number.", key));
+    }
+
+    let mut values = read();
+    values.insert(key.to_string(), value.to_string());
+    storage::replace_file(&storage::config_file_path(), serialize(&values).as_bytes())
+}
+
+// Days after which passwords with the tag expire, 0 if the tag has no policy
+pub fn tag_expiry_days(tag: &str) -> u64 {
+    get_number(&format!("{}{}", TAG_EXPIRY_PREFIX, tag))
+}
+
+// Tag policies that were set, by tag
+pub fn tag_expiry_policies() -> BTreeMap<String, u64> {
+    read()
+        .into_iter()
+        .filter_map(|(key, value)| Some((tag_of(&key)?.to_string(), value.parse().ok()?)))
+        .collect()
+}
+
+fn tag_of(key: &str) -> Option<&str> {
+    key.strip_prefix(TAG_EXPIRY_PREFIX)
+        .filter(|tag| !tag.is_empty())
+}
+
+fn setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+#[cfg(test)]
+mod test;
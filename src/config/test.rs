@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn test_parse() {
+    let values = parse("backup.keep = 3\n# comment = 1\n\nbroken line\nbackup.max_age_days=30");
+
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["backup.keep"], "3");
+    assert_eq!(values["backup.max_age_days"], "30");
+    assert_eq!(parse(&serialize(&values)), values);
+}
+
+#[test]
+fn test_set() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    assert_eq!(get_number("backup.keep"), 10);
+
+    set("backup.keep", "3").unwrap();
+    assert_eq!(get_number("backup.keep"), 3);
+
+    assert!(set("backup.keep", "many").is_err());
+    assert!(set("unknown", "1").is_err());
+    assert_eq!(read().len(), 1);
+}
+
+#[test]
+fn test_tag_expiry() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    assert_eq!(tag_expiry_days("bank"), 0);
+
+    set("expiry.tag.bank", "30").unwrap();
+    set("expiry.days", "365").unwrap();
+    assert_eq!(tag_expiry_days("bank"), 30);
+    assert_eq!(tag_expiry_days("mail"), 0);
+    assert_eq!(
+        tag_expiry_policies().into_iter().collect::<Vec<_>>(),
+        vec![(String::from("bank"), 30)]
+    );
+
+    assert!(set("expiry.tag.", "30").is_err());
+}
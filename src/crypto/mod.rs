@@ -1,11 +1,15 @@
-use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
 use argon2::Argon2;
 use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine as _};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 use rand::Rng;
 
+use crate::errs;
+
 pub const KEY_SIZE: usize = 32;
 pub const SALT_SIZE: usize = 16;
+pub const ID_SIZE: usize = 16;
 
 pub fn generate_salt() -> Vec<u8> {
     let mut salt = vec![0u8; SALT_SIZE];
@@ -13,6 +17,29 @@ pub fn generate_salt() -> Vec<u8> {
     salt
 }
 
+// Random ID of a vault record, a new one for every written record
+pub fn generate_id() -> Vec<u8> {
+    let mut id = vec![0u8; ID_SIZE];
+    rand::thread_rng().fill(&mut id[..]);
+    id
+}
+
+// Binds the cipher text to the entry it belongs to, so a record moved to another domain,
+// another username or another vault doesn't decrypt. Records written before they had an ID
+// were encrypted without associated data.
+pub fn associated_data(domain: &str, username: &str, id: &[u8]) -> Vec<u8> {
+    if id.is_empty() {
+        return Vec::new();
+    }
+
+    let mut data = Vec::new();
+    for part in [domain.as_bytes(), username.as_bytes(), id] {
+        data.extend((part.len() as u16).to_le_bytes());
+        data.extend(part);
+    }
+    data
+}
+
 pub fn derive_key(password: &str, salt: &[u8]) -> [u8; KEY_SIZE] {
     let argon2 = Argon2::default();
     let mut key: [u8; KEY_SIZE] = [0u8; KEY_SIZE];
@@ -22,31 +49,138 @@ pub fn derive_key(password: &str, salt: &[u8]) -> [u8; KEY_SIZE] {
     key
 }
 
-pub fn encrypt(key: &[u8; KEY_SIZE], password: &str) -> (String, Vec<u8>) {
-    let aes_key = Key::<Aes256Gcm>::from_slice(key);
-    let cipher = Aes256Gcm::new(aes_key);
+pub const NONCE_SIZE: usize = 12;
 
-    let binding = rand::random::<[u8; 12]>();
-    let nonce = Nonce::from_slice(&binding);
-    let cipher_text = cipher
-        .encrypt(nonce, password.as_bytes())
-        .expect("Encryption failed");
+// Cipher of vault records. Its ID is stored with every record, so a vault can hold records
+// of both while it is migrated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Cipher {
+    #[default]
+    Aes256Gcm,
+    // 192-bit nonces are safe to pick at random, and it is fast without AES instructions
+    XChaCha20Poly1305,
+}
 
-    (STANDARD_NO_PAD.encode(cipher_text), nonce.to_vec())
+impl Cipher {
+    pub const NAMES: [&'static str; 2] = ["aes-256-gcm", "xchacha20-poly1305"];
+
+    pub fn from_name(name: &str) -> Option<Cipher> {
+        match name.to_lowercase().as_str() {
+            "aes-256-gcm" => Some(Cipher::Aes256Gcm),
+            "xchacha20-poly1305" => Some(Cipher::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::XChaCha20Poly1305 => "xchacha20-poly1305",
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Cipher> {
+        match id {
+            0 => Some(Cipher::Aes256Gcm),
+            1 => Some(Cipher::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 0,
+            Cipher::XChaCha20Poly1305 => 1,
+        }
+    }
+
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            Cipher::Aes256Gcm => NONCE_SIZE,
+            Cipher::XChaCha20Poly1305 => 24,
+        }
+    }
 }
 
-pub fn decrypt(key: &[u8], nonce: &[u8], encoded_pwd: &str) -> String {
-    let aes_key = Key::<Aes256Gcm>::from_slice(key);
-    let cipher = Aes256Gcm::new(aes_key);
+pub fn encrypt(
+    cipher: Cipher,
+    key: &[u8; KEY_SIZE],
+    password: &str,
+    aad: &[u8],
+) -> (String, Vec<u8>) {
+    let (cipher_text, nonce) = encrypt_with_aad(cipher, key, password.as_bytes(), aad);
+    (STANDARD_NO_PAD.encode(cipher_text), nonce)
+}
 
+// Fails if the key is wrong, or the cipher text or associated data was modified
+pub fn decrypt(
+    cipher: Cipher,
+    key: &[u8],
+    nonce: &[u8],
+    encoded_pwd: &str,
+    aad: &[u8],
+) -> Result<String, String> {
     let decoded_cipher_text = STANDARD_NO_PAD
         .decode(encoded_pwd)
-        .expect("Base64 decoding failed.");
-    let plain_text = cipher
-        .decrypt(Nonce::from_slice(nonce), decoded_cipher_text.as_ref())
-        .expect("Decryption failed");
+        .map_err(|_| String::from("Base64 decoding failed."))?;
+    let plain_text = decrypt_with_aad(cipher, key, nonce, &decoded_cipher_text, aad)?;
+
+    String::from_utf8(plain_text).map_err(|_| String::from("Invalid UTF-8"))
+}
+
+pub fn encrypt_bytes(key: &[u8; KEY_SIZE], data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    encrypt_with_aad(Cipher::Aes256Gcm, key, data, &[])
+}
+
+pub fn decrypt_bytes(key: &[u8], nonce: &[u8], cipher_text: &[u8]) -> Result<Vec<u8>, String> {
+    decrypt_with_aad(Cipher::Aes256Gcm, key, nonce, cipher_text, &[])
+}
+
+pub fn encrypt_with_aad(
+    cipher: Cipher,
+    key: &[u8; KEY_SIZE],
+    data: &[u8],
+    aad: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let mut nonce = vec![0u8; cipher.nonce_size()];
+    rand::thread_rng().fill(&mut nonce[..]);
+    let payload = Payload { msg: data, aad };
+
+    let cipher_text = match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+            .encrypt(Nonce::from_slice(&nonce), payload),
+        Cipher::XChaCha20Poly1305 => {
+            XChaCha20Poly1305::new(key.into()).encrypt(XNonce::from_slice(&nonce), payload)
+        }
+    };
+
+    (cipher_text.expect("Encryption failed"), nonce)
+}
+
+pub fn decrypt_with_aad(
+    cipher: Cipher,
+    key: &[u8],
+    nonce: &[u8],
+    cipher_text: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, String> {
+    if key.len() != KEY_SIZE || nonce.len() != cipher.nonce_size() {
+        return Err(String::from(errs::DECRYPTION_FAILED));
+    }
+
+    let payload = Payload {
+        msg: cipher_text,
+        aad,
+    };
 
-    String::from_utf8(plain_text).expect("Invalid UTF-8")
+    match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+            .decrypt(Nonce::from_slice(nonce), payload),
+        Cipher::XChaCha20Poly1305 => {
+            XChaCha20Poly1305::new(key.into()).decrypt(XNonce::from_slice(nonce), payload)
+        }
+    }
+    .map_err(|_| String::from(errs::DECRYPTION_FAILED))
 }
 
 #[cfg(test)]
@@ -6,10 +6,77 @@ const DOMAIN_PASSWORD: &str = "encRyp73dPa55w0rd";
 fn test_encrypt_decrypt() {
     let salt = generate_salt();
     let key = derive_key(MASTER_PASSWORD, &salt);
-    let (hash, nonce) = encrypt(&key, DOMAIN_PASSWORD);
+    let (hash, nonce) = encrypt(Cipher::Aes256Gcm, &key, DOMAIN_PASSWORD, &[]);
 
     let second_key = derive_key(MASTER_PASSWORD, &salt);
-    let pwd = decrypt(&second_key, &nonce, &hash);
+    let pwd = decrypt(Cipher::Aes256Gcm, &second_key, &nonce, &hash, &[]).unwrap();
 
     assert_eq!(DOMAIN_PASSWORD, pwd);
 }
+
+#[test]
+fn test_decrypt_wrong_password() {
+    let salt = generate_salt();
+    let key = derive_key(MASTER_PASSWORD, &salt);
+    let (hash, nonce) = encrypt(Cipher::Aes256Gcm, &key, DOMAIN_PASSWORD, &[]);
+
+    let wrong_key = derive_key("wrong password", &salt);
+
+    assert!(decrypt(Cipher::Aes256Gcm, &wrong_key, &nonce, &hash, &[]).is_err());
+}
+
+#[test]
+fn test_associated_data() {
+    let salt = generate_salt();
+    let key = derive_key(MASTER_PASSWORD, &salt);
+    let id = generate_id();
+    let aad = associated_data("gmail", "john", &id);
+    let (hash, nonce) = encrypt(Cipher::Aes256Gcm, &key, DOMAIN_PASSWORD, &aad);
+
+    assert_eq!(
+        decrypt(Cipher::Aes256Gcm, &key, &nonce, &hash, &aad).unwrap(),
+        DOMAIN_PASSWORD
+    );
+
+    // Same record under another domain, username or ID doesn't decrypt
+    for other in [
+        associated_data("yahoo", "john", &id),
+        associated_data("gmail", "jane", &id),
+        associated_data("gmail", "john", &generate_id()),
+        associated_data("gmail", "john", &[]),
+    ] {
+        assert!(decrypt(Cipher::Aes256Gcm, &key, &nonce, &hash, &other).is_err());
+    }
+
+    // Parts are length prefixed, so moving bytes between them changes the data
+    assert_ne!(
+        associated_data("gmai", "ljohn", &id),
+        associated_data("gmail", "john", &id)
+    );
+    assert!(associated_data("gmail", "john", &[]).is_empty());
+}
+
+#[test]
+fn test_ciphers() {
+    let salt = generate_salt();
+    let key = derive_key(MASTER_PASSWORD, &salt);
+    let aad = associated_data("gmail", "john", &generate_id());
+
+    for cipher in [Cipher::Aes256Gcm, Cipher::XChaCha20Poly1305] {
+        let (hash, nonce) = encrypt(cipher, &key, DOMAIN_PASSWORD, &aad);
+        assert_eq!(nonce.len(), cipher.nonce_size());
+        assert_eq!(
+            decrypt(cipher, &key, &nonce, &hash, &aad).unwrap(),
+            DOMAIN_PASSWORD
+        );
+
+        assert_eq!(Cipher::from_id(cipher.id()), Some(cipher));
+        assert_eq!(Cipher::from_name(cipher.name()), Some(cipher));
+    }
+
+    // Records don't decrypt with the other cipher
+    let (hash, nonce) = encrypt(Cipher::XChaCha20Poly1305, &key, DOMAIN_PASSWORD, &aad);
+    assert!(decrypt(Cipher::Aes256Gcm, &key, &nonce, &hash, &aad).is_err());
+    assert!(decrypt(Cipher::Aes256Gcm, &key, &nonce[..NONCE_SIZE], &hash, &aad).is_err());
+    assert_eq!(Cipher::from_id(7), None);
+}
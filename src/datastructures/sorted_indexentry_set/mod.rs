@@ -1,11 +1,53 @@
-use crate::entry::{index_entry::IndexEntry, BinarySerialization, DOMAIN_SIZE};
-use cli_table::{print_stdout, Cell};
-use std::cmp::Ordering;
+use crate::crypto::{self, Cipher, KEY_SIZE};
+use crate::entry::{index_entry::IndexEntry, BinarySerialization, ByteReader, DOMAIN_SIZE};
+use cli_table::{print_stdout, Cell, Style, Table};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+pub const INDEX_MAGIC: &[u8; 4] = b"DIDX";
+pub const INDEX_VERSION: u8 = 8;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// MAC key with the salt and the digest of the master password it was derived from. Deriving
+// the key is slow on purpose, and a single command seals and verifies the index several times.
+struct MacKey {
+    salt: Vec<u8>,
+    digest: [u8; 32],
+    key: [u8; KEY_SIZE],
+}
+
+static MAC_KEY: Mutex<Option<MacKey>> = Mutex::new(None);
 
 #[derive(Debug)]
 pub struct IndexSet {
     pub size: usize,
     pub data: Vec<IndexEntry>,
+    // Bytes of the vault taken by deleted and replaced records, compaction frees them
+    pub garbage: u64,
+    // Wrong master passwords in a row and when the last one was entered, see throttle
+    pub failures: u32,
+    pub last_failure: u64,
+    // HMAC of the entries keyed by the master password, see seal. Empty until the index is
+    // first written with the master password.
+    pub mac_salt: Vec<u8>,
+    pub mac: Vec<u8>,
+    // Cipher new records are encrypted with, records keep their own until they are rewritten
+    pub cipher: Cipher,
+    // Failed attempt counter when the index was sealed. Attempts are counted without the
+    // master password, so the counter can only grow until the next seal.
+    pub sealed_failures: u32,
+    pub sealed_last_failure: u64,
+    // Lines of the access log when it last recorded an event with the master password, see
+    // log::verify
+    pub logged_events: u64,
+    // Index version the MAC was computed for, MAC of older indexes covers less.
+    // Kept until the index is sealed again.
+    mac_version: u8,
+    // Tag -> keys of entries with that tag, so lookups by tag don't scan the whole set
+    tags: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl IndexSet {
@@ -13,89 +55,208 @@ impl IndexSet {
         IndexSet {
             size: 0,
             data: Vec::new(),
+            garbage: 0,
+            failures: 0,
+            last_failure: 0,
+            mac_salt: Vec::new(),
+            mac: Vec::new(),
+            cipher: Cipher::Aes256Gcm,
+            sealed_failures: 0,
+            sealed_last_failure: 0,
+            logged_events: 0,
+            mac_version: INDEX_VERSION,
+            tags: BTreeMap::new(),
         }
     }
 
-    pub fn from_binary(data: &[u8]) -> IndexSet {
-        let length = data.len();
-        let step = DOMAIN_SIZE + std::mem::size_of::<usize>();
-
+    pub fn try_from_binary(data: &[u8]) -> Result<IndexSet, String> {
+        let damaged = || String::from("Index of the vault is damaged.");
         let mut set = IndexSet::new();
 
-        for i in (0..length).step_by(step) {
-            let slice = Vec::from(data.get(i..i + step).expect("Deserialization error."));
-            let entry: IndexEntry = IndexEntry::deserialize(&slice);
-            set.add(&entry);
-        }
+        if !data.starts_with(INDEX_MAGIC) {
+            let step = DOMAIN_SIZE + std::mem::size_of::<u64>();
+            if !data.len().is_multiple_of(step) {
+                return Err(damaged());
+            }
 
-        set
-    }
+            for slice in data.chunks(step) {
+                set.add(&IndexEntry::from_legacy(slice).ok_or_else(damaged)?);
+            }
 
-    pub fn add(&mut self, item: &IndexEntry) -> bool {
-        if self.size == 0 {
-            self.data.push(item.clone());
-            self.size += 1;
-            return true;
+            return Ok(set);
         }
 
-        if self.has(&item.key) {
-            return false;
+        let mut reader = ByteReader::new(&data[INDEX_MAGIC.len()..]);
+        let version = reader.read_u8().ok_or_else(damaged)?;
+        if version > INDEX_VERSION {
+            return Err(String::from(
+                "Index of the vault was created by a newer version of Dome.",
+            ));
         }
 
-        let last_index = self.data.len() - 1;
-        for (index, data_item) in self.data.iter_mut().enumerate() {
-            let cmp = item.key.cmp(&data_item.key);
+        // Version 1 didn't count garbage
+        if version >= 2 {
+            set.garbage = reader.read_u64().ok_or_else(damaged)?;
+        }
 
-            if cmp == Ordering::Less {
-                self.data.insert(index, item.clone());
-                self.size += 1;
-                return true;
-            } else if cmp == Ordering::Greater && index == last_index {
-                self.data.push(item.clone());
-                self.size += 1;
-                return true;
+        // Version 3 added the failed attempt counter
+        if version >= 3 {
+            set.failures = reader.read_u32().ok_or_else(damaged)?;
+            set.last_failure = reader.read_u64().ok_or_else(damaged)?;
+        }
+
+        // Version 4 added the MAC
+        if version >= 4 {
+            for field in [&mut set.mac_salt, &mut set.mac] {
+                let len = reader.read_u8().ok_or_else(damaged)? as usize;
+                *field = reader.read_bytes(len).ok_or_else(damaged)?.to_vec();
             }
         }
 
-        false
+        // Version 5 added the cipher, older vaults use AES-256-GCM
+        if version >= 5 {
+            let id = reader.read_u8().ok_or_else(damaged)?;
+            set.cipher = Cipher::from_id(id)
+                .ok_or_else(|| String::from("Index of the vault uses an unknown cipher."))?;
+        }
+
+        // Version 6 added the sealed counter and the version of the MAC
+        set.mac_version = version;
+        if version >= 6 {
+            set.sealed_failures = reader.read_u32().ok_or_else(damaged)?;
+            set.sealed_last_failure = reader.read_u64().ok_or_else(damaged)?;
+            set.mac_version = reader.read_u8().ok_or_else(damaged)?;
+        }
+
+        // Version 7 added the number of logged events. Version 8 only signs more, see mac_of.
+        if version >= 7 {
+            set.logged_events = reader.read_u64().ok_or_else(damaged)?;
+        }
+
+        while !reader.is_empty() {
+            let len = reader.read_u32().ok_or_else(damaged)? as usize;
+            let record = reader.read_bytes(len).ok_or_else(damaged)?;
+            set.add(&IndexEntry::read(record).ok_or_else(damaged)?);
+        }
+
+        Ok(set)
     }
 
-    pub fn has(&self, key: &str) -> bool {
-        for data_item in &self.data {
-            if data_item.key.cmp(&key.to_string()) == Ordering::Equal {
-                return true;
+    pub fn add(&mut self, item: &IndexEntry) -> bool {
+        match self.position(&item.key) {
+            Ok(_) => false,
+            Err(index) => {
+                self.index_tags(&item.key, &item.tags);
+                self.data.insert(index, item.clone());
+                self.size += 1;
+                true
             }
         }
+    }
 
-        false
+    pub fn has(&self, key: &str) -> bool {
+        self.position(key).is_ok()
     }
 
     pub fn find(&self, key: &str) -> Option<(usize, &IndexEntry)> {
-        for (index, item) in self.data.iter().enumerate() {
-            if item.key == key {
-                return Some((index, item));
-            }
+        match self.position(key) {
+            Ok(index) => Some((index, &self.data[index])),
+            Err(_) => None,
         }
-
-        None
     }
 
+    // Removes the entry, its vault record of given size becomes garbage. Offsets of other
+    // entries don't change.
     pub fn remove(&mut self, key: &str, size: u64) -> Option<IndexEntry> {
         let (index, entry) = match self.find(key) {
             None => return None,
             Some((index, entry)) => (index, entry.clone()),
         };
 
-        // Subtract the size of deleted entry from every entry further down in vault file
-        for current in &mut self.data {
-            if current.value > entry.value {
-                println!("SUBTRACTING {} by {}", current.value, size);
-                current.value -= size;
+        self.garbage += size;
+        self.unindex_tags(&entry.key, &entry.tags);
+        self.data.remove(index);
+        self.size -= 1;
+        Some(entry)
+    }
+
+    // Replaces entry with the same key. Returns false if the entry doesn't exist.
+    pub fn replace(&mut self, item: &IndexEntry) -> bool {
+        let index = match self.position(&item.key) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        let old_tags = self.data[index].tags.clone();
+        self.unindex_tags(&item.key, &old_tags);
+        self.index_tags(&item.key, &item.tags);
+        self.data[index] = item.clone();
+        true
+    }
+
+    // Adds tags to the entry. Returns false if the entry doesn't exist.
+    pub fn add_tags(&mut self, key: &str, tags: &[String]) -> bool {
+        let index = match self.position(key) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        let entry = &mut self.data[index];
+        let mut added = Vec::new();
+        for tag in tags {
+            if !entry.tags.contains(tag) {
+                entry.tags.push(tag.clone());
+                added.push(tag.clone());
             }
         }
+        entry.tags.sort();
 
-        self.data.remove(index);
-        Some(entry)
+        self.index_tags(key, &added);
+        true
+    }
+
+    // Removes tags from the entry. Returns false if the entry doesn't exist.
+    pub fn remove_tags(&mut self, key: &str, tags: &[String]) -> bool {
+        let index = match self.position(key) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+
+        self.data[index].tags.retain(|tag| !tags.contains(tag));
+        self.unindex_tags(key, tags);
+        true
+    }
+
+    // Moves the entry into the folder. Returns false if the entry doesn't exist.
+    pub fn move_to_folder(&mut self, key: &str, folder: &str) -> bool {
+        match self.position(key) {
+            Ok(index) => {
+                self.data[index].folder = String::from(folder);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn with_tag(&self, tag: &str) -> Vec<&IndexEntry> {
+        match self.tags.get(tag) {
+            Some(keys) => keys
+                .iter()
+                .filter_map(|key| self.find(key).map(|(_, entry)| entry))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn in_folder(&self, folder: &str) -> Vec<&IndexEntry> {
+        self.data
+            .iter()
+            .filter(|entry| entry.in_folder(folder))
+            .collect()
+    }
+
+    pub fn tag_names(&self) -> Vec<&String> {
+        self.tags.keys().collect()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -103,17 +264,191 @@ impl IndexSet {
     }
 
     pub fn print(&self) {
+        Self::print_entries(&self.data.iter().collect::<Vec<&IndexEntry>>());
+    }
+
+    pub fn print_entries(entries: &[&IndexEntry]) {
         let mut result = Vec::new();
 
-        for (index, item) in self.data.iter().enumerate() {
-            let vec = vec![index.cell(), item.key.clone().cell()];
+        for (index, item) in entries.iter().enumerate() {
+            let vec = vec![
+                index.cell(),
+                item.key.clone().cell(),
+                item.folder.clone().cell(),
+                item.tags.join(", ").cell(),
+            ];
             result.push(vec);
         }
 
-        let _ = print_stdout(result);
+        let table = result
+            .table()
+            .title(vec![
+                "".cell(),
+                "Domain".cell(),
+                "Folder".cell(),
+                "Tags".cell(),
+            ])
+            .bold(true);
+
+        let _ = print_stdout(table);
+    }
+
+    // Signs which entries the index has, which record each of them points to, and their
+    // URL, folder, tags, times and expiry. Only the time of the last access is left out.
+    // The version of the MAC, the failed attempt counter and the number of logged events in
+    // the header are signed too.
+    pub fn seal(&mut self, master_password: &str) {
+        if self.mac_salt.is_empty() {
+            self.mac_salt = crypto::generate_salt();
+        }
+
+        self.mac_version = INDEX_VERSION;
+        self.sealed_failures = self.failures;
+        self.sealed_last_failure = self.last_failure;
+
+        self.mac = self
+            .mac_of(master_password)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+    }
+
+    // Fails if entries were added, removed, renamed, swapped or changed since the index was
+    // sealed, or if the failed attempt counter went back. `sealed_version` is the version of
+    // the MAC the vault was last seen sealed with, 0 if it never was. The MAC can't be
+    // removed or replaced by an older one that covers less once the vault was sealed.
+    pub fn verify_seal(&self, master_password: &str, sealed_version: u8) -> Result<(), String> {
+        let tampered = || {
+            String::from("Index of the vault was changed outside of Dome, entries may have been swapped or removed.")
+        };
+
+        if self.seal_version() < sealed_version {
+            return Err(format!(
+                "Seal of the index was removed or rolled back outside of Dome, the vault was sealed with version {}.",
+                sealed_version
+            ));
+        }
+
+        // Only indexes from before the MAC may lack it, and their entries have no IDs
+        if self.mac.is_empty() {
+            return match self.data.iter().all(|entry| entry.id.is_empty()) {
+                true => Ok(()),
+                false => Err(tampered()),
+            };
+        }
+
+        self.mac_of(master_password)
+            .verify_slice(&self.mac)
+            .map_err(|_| tampered())?;
+
+        if self.failures < self.sealed_failures || self.last_failure < self.sealed_last_failure {
+            return Err(String::from(
+                "Failed attempt counter of the vault was turned back outside of Dome.",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Version of the MAC, 0 when the index isn't sealed
+    pub fn seal_version(&self) -> u8 {
+        match self.mac.is_empty() {
+            true => 0,
+            false => self.mac_version,
+        }
+    }
+
+    fn mac_of(&self, master_password: &str) -> HmacSha256 {
+        let key = mac_key(master_password, &self.mac_salt);
+        let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC accepts any key size");
+
+        if self.mac_version >= 8 {
+            mac.update(&[self.mac_version]);
+        }
+
+        mac.update(&(self.data.len() as u64).to_le_bytes());
+        for entry in &self.data {
+            mac.update(&(entry.key.len() as u16).to_le_bytes());
+            mac.update(entry.key.as_bytes());
+            mac.update(&[entry.id.len() as u8]);
+            mac.update(&entry.id);
+
+            if self.mac_version >= 8 {
+                mac.update(&entry.value.to_le_bytes());
+            }
+
+            if self.mac_version >= 6 {
+                let strings = [&entry.url, &entry.folder].into_iter().chain(&entry.tags);
+                mac.update(&(entry.tags.len() as u16).to_le_bytes());
+                for text in strings {
+                    mac.update(&(text.len() as u16).to_le_bytes());
+                    mac.update(text.as_bytes());
+                }
+                mac.update(&entry.created.to_le_bytes());
+                mac.update(&entry.modified.to_le_bytes());
+                mac.update(&entry.expiry_days.to_le_bytes());
+            }
+        }
+
+        if self.mac_version >= 6 {
+            mac.update(&self.sealed_failures.to_le_bytes());
+            mac.update(&self.sealed_last_failure.to_le_bytes());
+        }
+
+        if self.mac_version >= 7 {
+            mac.update(&self.logged_events.to_le_bytes());
+        }
+
+        mac
+    }
+
+    fn position(&self, key: &str) -> Result<usize, usize> {
+        self.data
+            .binary_search_by(|item| item.key.as_str().cmp(key))
+    }
+
+    fn index_tags(&mut self, key: &str, tags: &[String]) {
+        for tag in tags {
+            self.tags
+                .entry(tag.clone())
+                .or_default()
+                .insert(String::from(key));
+        }
+    }
+
+    fn unindex_tags(&mut self, key: &str, tags: &[String]) {
+        for tag in tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
     }
 }
 
+// Key of the MAC, derived again only when the password or the salt changes
+fn mac_key(master_password: &str, salt: &[u8]) -> [u8; KEY_SIZE] {
+    let digest: [u8; 32] = Sha256::digest(master_password.as_bytes()).into();
+    let mut cached = MAC_KEY.lock().unwrap();
+
+    if let Some(cached) = cached.as_ref() {
+        if cached.salt == salt && cached.digest == digest {
+            return cached.key;
+        }
+    }
+
+    let key = crypto::derive_key(master_password, salt);
+    *cached = Some(MacKey {
+        salt: salt.to_vec(),
+        digest,
+        key,
+    });
+    key
+}
+
 impl PartialEq for IndexSet {
     fn eq(&self, other: &Self) -> bool {
         if self.size != other.size {
@@ -131,16 +466,32 @@ impl PartialEq for IndexSet {
 impl BinarySerialization for IndexSet {
     fn serialize(&self) -> Vec<u8> {
         let mut data: Vec<u8> = Vec::new();
+        data.extend(INDEX_MAGIC);
+        data.push(INDEX_VERSION);
+        data.extend(self.garbage.to_le_bytes());
+        data.extend(self.failures.to_le_bytes());
+        data.extend(self.last_failure.to_le_bytes());
+        for field in [&self.mac_salt, &self.mac] {
+            data.push(field.len() as u8);
+            data.extend(field);
+        }
+        data.push(self.cipher.id());
+        data.extend(self.sealed_failures.to_le_bytes());
+        data.extend(self.sealed_last_failure.to_le_bytes());
+        data.push(self.mac_version);
+        data.extend(self.logged_events.to_le_bytes());
 
         for item in &self.data {
-            data.extend(item.serialize());
+            let record = item.serialize();
+            data.extend((record.len() as u32).to_le_bytes());
+            data.extend(record);
         }
 
         data
     }
 
     fn deserialize(data: &[u8]) -> Self {
-        IndexSet::from_binary(data)
+        IndexSet::try_from_binary(data).expect("Deserialization error.")
     }
 }
 
@@ -61,19 +61,206 @@ fn test_remove_key() {
         panic!("Entry was not deleted by remove method.");
     }
 
-    let (_, y_entry) = set.find("yahoo").expect("Entry not found.");
-    assert_eq!(y_entry.value, 60); // Yahoo stays at 60
-
+    // Removed record stays in the vault as garbage, other offsets don't move
     let (_, g_entry) = set.find("google").expect("Entry not found.");
-    assert_eq!(g_entry.value, 90); // 100 - 10
+    assert_eq!(g_entry.value, 100);
 
     set.remove("yahoo", 30);
     set.remove("gmail", 50);
 
-    let (_, f_entry) = set.find("facebook").expect("Entry not found");
-    assert_eq!(f_entry.value, 0); // 50 - 50
+    assert_eq!(set.size, 2);
+    assert_eq!(set.garbage, 90);
+    assert_eq!(
+        IndexSet::try_from_binary(&set.serialize()).unwrap().garbage,
+        90
+    );
+}
+
+#[test]
+fn test_failed_attempts() {
+    let mut set = prepare_set();
+    set.failures = 4;
+    set.last_failure = 1_700_000_000;
+
+    let new_set = IndexSet::try_from_binary(&set.serialize()).unwrap();
+    assert_eq!(new_set.failures, 4);
+    assert_eq!(new_set.last_failure, 1_700_000_000);
+
+    // Version 2 index has no counter, MAC, cipher, sealed counter or logged events
+    let mut data = set.serialize();
+    data[INDEX_MAGIC.len()] = 2;
+    data.drain(INDEX_MAGIC.len() + 9..INDEX_MAGIC.len() + 45);
+    let old_set = IndexSet::try_from_binary(&data).unwrap();
+    assert_eq!(old_set.failures, 0);
+    assert_eq!(old_set, set);
+    assert_eq!(old_set.cipher, Cipher::Aes256Gcm);
+
+    set.cipher = Cipher::XChaCha20Poly1305;
+    assert_eq!(
+        IndexSet::try_from_binary(&set.serialize()).unwrap().cipher,
+        set.cipher
+    );
+}
+
+#[test]
+fn test_damaged_index() {
+    let data = prepare_set().serialize();
+
+    assert!(IndexSet::try_from_binary(&data).is_ok());
+    assert!(IndexSet::try_from_binary(&data[..data.len() - 3]).is_err());
+    assert!(IndexSet::try_from_binary(&data[..INDEX_MAGIC.len()]).is_err());
+
+    let mut newer = data.clone();
+    newer[INDEX_MAGIC.len()] = INDEX_VERSION + 1;
+    assert!(IndexSet::try_from_binary(&newer)
+        .unwrap_err()
+        .contains("newer version"));
+
+    // Legacy index is a multiple of the fixed entry size
+    assert!(IndexSet::try_from_binary(&[1u8; 41]).is_err());
+}
+
+#[test]
+fn test_legacy_index() {
+    let mut data = Vec::new();
+    for (key, value) in [("yahoo", 60u64), ("gmail", 0u64)] {
+        let mut buffer = [0u8; DOMAIN_SIZE];
+        buffer[..key.len()].copy_from_slice(key.as_bytes());
+        data.extend(buffer);
+        data.extend(value.to_ne_bytes());
+    }
+
+    let set = IndexSet::try_from_binary(&data).unwrap();
+
+    assert_eq!(set.size, 2);
+    assert_eq!(set.data[0].key, "gmail");
+    assert_eq!(set.data[1].value, 60);
+}
+
+#[test]
+fn test_tags() {
+    let mut set = prepare_set();
+    let work = vec![String::from("work")];
+
+    set.add_tags("gmail", &work);
+    set.add_tags("adobe", &work);
+    set.add_tags("yahoo", &[String::from("personal")]);
+
+    let keys: Vec<&str> = set
+        .with_tag("work")
+        .iter()
+        .map(|e| e.key.as_str())
+        .collect();
+    assert_eq!(keys, vec!["adobe", "gmail"]);
+
+    set.remove_tags("gmail", &work);
+    set.remove("adobe", 10);
+
+    assert!(set.with_tag("work").is_empty());
+    assert_eq!(set.tag_names(), vec!["personal"]);
+
+    let new_set = IndexSet::deserialize(&set.serialize());
+    assert_eq!(new_set.with_tag("personal").len(), 1);
+}
+
+#[test]
+fn test_folders() {
+    let mut set = prepare_set();
+
+    set.move_to_folder("gmail", "mail");
+    set.move_to_folder("yahoo", "mail/old");
+    set.move_to_folder("google", "work");
+
+    let keys: Vec<&str> = set
+        .in_folder("mail")
+        .iter()
+        .map(|e| e.key.as_str())
+        .collect();
+    assert_eq!(keys, vec!["gmail", "yahoo"]);
+    assert_eq!(set.in_folder("mail/old").len(), 1);
+    assert_eq!(set.in_folder("").len(), 5);
+}
+
+#[test]
+fn test_replace() {
+    let mut set = prepare_set();
+    set.add_tags("gmail", &[String::from("mail")]);
+
+    let mut entry = IndexEntry::new("gmail", 500);
+    entry.tags = vec![String::from("personal")];
+
+    assert!(set.replace(&entry));
+    assert!(!set.replace(&IndexEntry::new("outlook", 0)));
+
+    assert_eq!(set.find("gmail").unwrap().1.value, 500);
+    assert!(set.with_tag("mail").is_empty());
+    assert_eq!(set.with_tag("personal").len(), 1);
+}
+
+#[test]
+fn test_seal() {
+    let mut set = prepare_set();
+    set.seal("pw");
+    assert!(set.verify_seal("pw", 0).is_ok());
+    assert!(set.verify_seal("wrong", 0).is_err());
+
+    // Wrong passwords are counted without the master password, the counter only grows
+    set.failures = 2;
+    set.last_failure = 1_700_000_000;
+    assert!(set.verify_seal("pw", 0).is_ok());
+
+    set.seal("pw");
+    set.failures = 0;
+    assert!(IndexSet::try_from_binary(&set.serialize())
+        .unwrap()
+        .verify_seal("pw", 0)
+        .unwrap_err()
+        .contains("counter"));
+}
+
+#[test]
+fn test_seal_of_version_5() {
+    let mut set = prepare_set();
+    set.seal("pw");
+    set.mac_version = 5;
+    set.mac = set.mac_of("pw").finalize().into_bytes().to_vec();
+
+    // MAC of older indexes doesn't cover the URL, until the index is sealed again
+    let mut gmail = set.find("gmail").unwrap().1.clone();
+    gmail.url = String::from("https://phishing.example.com");
+    set.replace(&gmail);
+    let old_set = IndexSet::try_from_binary(&set.serialize()).unwrap();
+    assert!(old_set.verify_seal("pw", 0).is_ok());
+
+    set.seal("pw");
+    gmail.url = String::from("https://mail.google.com");
+    set.replace(&gmail);
+    assert!(set.verify_seal("pw", 0).is_err());
+}
+
+#[test]
+fn test_seal_covers_locations_and_version() {
+    let mut set = prepare_set();
+    set.seal("pw");
+    assert!(set.verify_seal("pw", INDEX_VERSION).is_ok());
+
+    // Entry pointed at another record
+    let mut gmail = set.find("gmail").unwrap().1.clone();
+    gmail.value += 1;
+    let mut moved = IndexSet::try_from_binary(&set.serialize()).unwrap();
+    moved.replace(&gmail);
+    assert!(moved.verify_seal("pw", INDEX_VERSION).is_err());
+
+    // MAC rolled back to a version that covers less, or removed
+    let mut old = IndexSet::try_from_binary(&set.serialize()).unwrap();
+    old.mac_version = 5;
+    old.mac = old.mac_of("pw").finalize().into_bytes().to_vec();
+    assert!(old.verify_seal("pw", 5).is_ok());
+    assert!(old.verify_seal("pw", INDEX_VERSION).is_err());
 
-    let (_, g2_entry) = set.find("google").expect("Entry not found");
-    assert_eq!(g2_entry.value, 10); // 100 - 10 - 30 - 50
-                                    //
+    let mut stripped = IndexSet::new();
+    assert!(stripped.verify_seal("pw", 0).is_ok());
+    stripped.seal("pw");
+    stripped.mac.clear();
+    assert!(stripped.verify_seal("pw", INDEX_VERSION).is_err());
 }
@@ -1,9 +1,23 @@
+use super::{ByteReader, DOMAIN_SIZE};
 use std::mem;
 
 #[derive(Debug, Clone)]
 pub struct IndexEntry {
     pub key: String,
     pub value: u64,
+    pub folder: String,
+    pub tags: Vec<String>,
+    pub url: String,
+    // Seconds since the epoch, 0 for entries saved before they were tracked. Modified is the
+    // last change of the password or username.
+    pub created: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    // Days after the last modification the password should be changed, 0 uses the policy
+    // of the tags
+    pub expiry_days: u32,
+    // ID of the record, bound to its cipher text. Empty for records written before IDs.
+    pub id: Vec<u8>,
 }
 
 impl IndexEntry {
@@ -11,13 +25,103 @@ impl IndexEntry {
         IndexEntry {
             key: String::from(key),
             value,
+            folder: String::new(),
+            tags: Vec::new(),
+            url: String::new(),
+            created: 0,
+            modified: 0,
+            accessed: 0,
+            expiry_days: 0,
+            id: Vec::new(),
         }
     }
+
+    // Reads entry from the fixed size format used before index file header was introduced
+    pub fn from_legacy(data: &[u8]) -> Option<IndexEntry> {
+        if data.len() != DOMAIN_SIZE + mem::size_of::<u64>() {
+            return None;
+        }
+
+        let key = super::deserialize_string(0, DOMAIN_SIZE - 1, data);
+        let value = u64::from_ne_bytes(data[DOMAIN_SIZE..].try_into().ok()?);
+        Some(IndexEntry::new(&key, value))
+    }
+
+    // Reads the serialized entry. None when the data is damaged.
+    pub fn read(data: &[u8]) -> Option<IndexEntry> {
+        let mut reader = ByteReader::new(data);
+
+        let key = reader.read_string()?;
+        let value = reader.read_u64()?;
+
+        let mut entry = IndexEntry::new(&key, value);
+        entry.folder = reader.read_string().unwrap_or_default();
+
+        let tag_count = reader.read_u16().unwrap_or(0);
+        for _ in 0..tag_count {
+            entry.tags.push(reader.read_string()?);
+        }
+
+        entry.url = reader.read_string().unwrap_or_default();
+
+        entry.created = reader.read_u64().unwrap_or_default();
+        entry.modified = reader.read_u64().unwrap_or_default();
+        entry.accessed = reader.read_u64().unwrap_or_default();
+        entry.expiry_days = reader.read_u32().unwrap_or_default();
+
+        let id_len = reader.read_u8().unwrap_or(0) as usize;
+        entry.id = reader.read_bytes(id_len).unwrap_or_default().to_vec();
+
+        Some(entry)
+    }
+
+    // Entry is in the folder if it is stored directly in it or in any of its subfolders
+    pub fn in_folder(&self, folder: &str) -> bool {
+        let folder = normalize_folder(folder);
+
+        if folder.is_empty() {
+            return true;
+        }
+
+        self.folder == folder || self.folder.starts_with(&format!("{}/", folder))
+    }
+}
+
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+// Turns "/infra//db/" into "infra/db". Empty string is the root folder.
+pub fn normalize_folder(folder: &str) -> String {
+    folder
+        .split('/')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+// Splits comma separated list of tags given on command line
+pub fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(normalize_tag)
+        .filter(|tag| !tag.is_empty())
+        .collect()
 }
 
 impl PartialEq for IndexEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.key == other.key && self.value == other.value
+        self.key == other.key
+            && self.value == other.value
+            && self.folder == other.folder
+            && self.tags == other.tags
+            && self.url == other.url
+            && self.created == other.created
+            && self.modified == other.modified
+            && self.accessed == other.accessed
+            && self.expiry_days == other.expiry_days
+            && self.id == other.id
     }
 }
 
@@ -25,30 +129,30 @@ impl super::BinarySerialization for IndexEntry {
     fn serialize(&self) -> Vec<u8> {
         let mut binary_data: Vec<u8> = Vec::new();
 
-        let mut buffer = [0u8; super::DOMAIN_SIZE];
-        let bytes = self.key.as_bytes();
+        super::write_string(&mut binary_data, &self.key);
+        binary_data.extend(self.value.to_le_bytes());
+        super::write_string(&mut binary_data, &self.folder);
+
+        binary_data.extend((self.tags.len() as u16).to_le_bytes());
+        for tag in &self.tags {
+            super::write_string(&mut binary_data, tag);
+        }
+
+        super::write_string(&mut binary_data, &self.url);
 
-        let len = self.key.len().min(super::DOMAIN_SIZE);
-        buffer[..len].copy_from_slice(&bytes[..len]);
+        binary_data.extend(self.created.to_le_bytes());
+        binary_data.extend(self.modified.to_le_bytes());
+        binary_data.extend(self.accessed.to_le_bytes());
+        binary_data.extend(self.expiry_days.to_le_bytes());
 
-        binary_data.extend(buffer);
-        binary_data.extend(self.value.to_ne_bytes());
+        binary_data.push(self.id.len() as u8);
+        binary_data.extend(&self.id);
 
         binary_data
     }
 
     fn deserialize(data: &[u8]) -> IndexEntry {
-        let (k_start, k_end): (usize, usize) = (0, 31);
-
-        let key = super::deserialize_string(k_start, k_end, data);
-        let bytes = data.get((k_end + 1)..).unwrap();
-
-        if bytes.len() == mem::size_of::<usize>() {
-            let value = u64::from_ne_bytes(bytes.try_into().unwrap());
-            return IndexEntry::new(&key, value);
-        }
-
-        panic!("Deserialization error.");
+        IndexEntry::read(data).expect("Deserialization error.")
     }
 }
 
@@ -69,6 +173,69 @@ mod test {
         assert_eq!(new_entry.value, 789);
     }
 
+    #[test]
+    fn test_serialization_with_tags() {
+        let mut entry: IndexEntry = IndexEntry::new("a-domain-name-longer-than-32-bytes.com", 42);
+        entry.folder = String::from("infra/db");
+        entry.tags = vec![String::from("work"), String::from("db")];
+        entry.url = String::from("https://db.example.com");
+        entry.created = 1_700_000_000;
+        entry.modified = 1_750_000_000;
+        entry.accessed = 1_760_000_000;
+        entry.expiry_days = 90;
+        entry.id = vec![7; 16];
+
+        let new_entry = IndexEntry::deserialize(&entry.serialize());
+
+        assert_eq!(entry, new_entry);
+    }
+
+    #[test]
+    fn test_deserialization_without_trailing_fields() {
+        let mut entry: IndexEntry = IndexEntry::new("gmail", 789);
+        entry.folder = String::from("mail");
+        entry.created = 1_700_000_000;
+
+        let mut binary_data = entry.serialize();
+        binary_data.truncate(binary_data.len() - 33);
+        let new_entry = IndexEntry::deserialize(&binary_data);
+
+        assert_eq!(new_entry.folder, "mail");
+        assert!(new_entry.tags.is_empty());
+        assert!(new_entry.url.is_empty());
+        assert_eq!(new_entry.created, 0);
+    }
+
+    #[test]
+    fn test_legacy_deserialization() {
+        let mut data = vec![0u8; DOMAIN_SIZE];
+        data[..5].copy_from_slice(b"gmail");
+        data.extend(789u64.to_ne_bytes());
+
+        let entry = IndexEntry::from_legacy(&data).unwrap();
+
+        assert_eq!(entry.key, "gmail");
+        assert_eq!(entry.value, 789);
+        assert!(entry.tags.is_empty());
+    }
+
+    #[test]
+    fn test_folders() {
+        let mut entry: IndexEntry = IndexEntry::new("postgres", 0);
+        entry.folder = normalize_folder("/infra//db/");
+
+        assert_eq!(entry.folder, "infra/db");
+        assert!(entry.in_folder("infra"));
+        assert!(entry.in_folder("infra/db"));
+        assert!(!entry.in_folder("infra/d"));
+        assert!(!entry.in_folder("work"));
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tags("Work, db,,"), vec!["work", "db"]);
+    }
+
     #[test]
     fn test_equals() {
         let entry: IndexEntry = IndexEntry::new("gmail", 789);
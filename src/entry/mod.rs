@@ -21,3 +21,66 @@ fn deserialize_string(start: usize, end: usize, data: &[u8]) -> String {
     let s = String::from_utf8_lossy(string_bytes);
     s.trim_end_matches('\0').to_string()
 }
+
+// Writes string prefixed with its length as u16
+pub fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    let bytes = value.as_bytes();
+    let len = bytes.len().min(u16::MAX as usize);
+
+    buffer.extend((len as u16).to_le_bytes());
+    buffer.extend(&bytes[..len]);
+}
+
+// Cursor over length-prefixed binary data. Every read returns None when data runs out,
+// so records written by older versions (with fewer trailing fields) can still be read.
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position + len)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    pub fn read_rest(&mut self) -> &'a [u8] {
+        let bytes = self.data.get(self.position..).unwrap_or(&[]);
+        self.position = self.data.len();
+        bytes
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub fn read_u16(&mut self) -> Option<u16> {
+        let bytes = self.read_bytes(2)?;
+        Some(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.read_bytes(4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Option<u64> {
+        let bytes = self.read_bytes(8)?;
+        Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn read_string(&mut self) -> Option<String> {
+        let len = self.read_u16()? as usize;
+        let bytes = self.read_bytes(len)?;
+        Some(String::from_utf8_lossy(bytes).to_string())
+    }
+}
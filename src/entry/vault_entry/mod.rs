@@ -1,3 +1,9 @@
+use crate::crypto::Cipher;
+
+// Record of the vault. AES-256-GCM records are salt, nonce, then domain, username and
+// password prefixed with their length. Records of other ciphers keep the first 28 bytes
+// for the salt and zeros, set the length of the domain to 0 and put a header with the
+// cipher and its nonce before the real domain.
 #[derive(Debug)]
 pub struct Entry {
     pub domain: String,
@@ -5,16 +11,25 @@ pub struct Entry {
     pub password: String,
     pub nonce: Vec<u8>,
     pub salt: Vec<u8>,
+    pub cipher: Cipher,
 }
 
 impl Entry {
-    pub fn new(domain: &str, username: &str, password: &str, nonce: &[u8], salt: &[u8]) -> Entry {
+    pub fn new(
+        domain: &str,
+        username: &str,
+        password: &str,
+        nonce: &[u8],
+        salt: &[u8],
+        cipher: Cipher,
+    ) -> Entry {
         Entry {
             domain: String::from(domain),
             username: String::from(username),
             password: String::from(password),
             nonce: Vec::from(nonce),
             salt: Vec::from(salt),
+            cipher,
         }
     }
 }
@@ -27,8 +42,13 @@ impl super::BinarySerialization for Entry {
             binary_data.push(*byte);
         }
 
-        for byte in &self.nonce {
-            binary_data.push(*byte);
+        if self.cipher == Cipher::Aes256Gcm {
+            binary_data.extend(&self.nonce);
+        } else {
+            binary_data.extend([0u8; 13]);
+            binary_data.push(1 + self.nonce.len() as u8);
+            binary_data.push(self.cipher.id());
+            binary_data.extend(&self.nonce);
         }
 
         binary_data.push(self.domain.len() as u8);
@@ -54,10 +74,23 @@ impl super::BinarySerialization for Entry {
 
     fn deserialize(data: &[u8]) -> Entry {
         let salt = Vec::from(data.get(0..=15).expect("Deserialization error"));
-        let nonce = Vec::from(data.get(16..=27).expect("Deserialization error"));
 
-        let domain_size: usize = data[28] as usize;
-        let (d_start, d_end): (usize, usize) = (29, 29 + domain_size - 1);
+        let (cipher, nonce, start) = match data[28] {
+            0 => {
+                let end = 30 + data[29] as usize;
+                let header = data.get(30..end).expect("Deserialization error");
+                let cipher = Cipher::from_id(header[0]).expect("Record uses an unknown cipher.");
+                (cipher, Vec::from(&header[1..]), end)
+            }
+            _ => (
+                Cipher::Aes256Gcm,
+                Vec::from(data.get(16..=27).expect("Deserialization error")),
+                28,
+            ),
+        };
+
+        let domain_size: usize = data[start] as usize;
+        let (d_start, d_end): (usize, usize) = (start + 1, start + domain_size);
         let domain = super::deserialize_string(d_start, d_end, data);
 
         let username_size: usize = data[d_end + 1] as usize;
@@ -68,7 +101,7 @@ impl super::BinarySerialization for Entry {
         let (p_start, p_end): (usize, usize) = (u_end + 2, u_end + password_size + 1);
         let password = super::deserialize_string(p_start, p_end, data);
 
-        Entry::new(&domain, &username, &password, &nonce, &salt)
+        Entry::new(&domain, &username, &password, &nonce, &salt, cipher)
     }
 }
 
@@ -81,7 +114,14 @@ mod test {
     fn test_serialize_deserialize_entry() {
         let nonce: Vec<u8> = Vec::from([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
         let salt: Vec<u8> = Vec::from([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
-        let entry: Entry = Entry::new("gmail", "john.doe@gmail.com", "123", &nonce, &salt);
+        let entry: Entry = Entry::new(
+            "gmail",
+            "john.doe@gmail.com",
+            "123",
+            &nonce,
+            &salt,
+            Cipher::Aes256Gcm,
+        );
 
         let binary_data = entry.serialize();
         let new_entry = Entry::deserialize(&binary_data);
@@ -101,7 +141,7 @@ mod test {
         let nonce: Vec<u8> = Vec::from([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
         let salt: Vec<u8> = Vec::from([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
 
-        let entry: Entry = Entry::new(domain, username, password, &nonce, &salt);
+        let entry: Entry = Entry::new(domain, username, password, &nonce, &salt, Cipher::Aes256Gcm);
 
         let binary_data = entry.serialize();
 
@@ -110,4 +150,32 @@ mod test {
 
         assert_eq!(binary_data.len(), should_be_of_size);
     }
+
+    #[test]
+    fn test_cipher_header() {
+        let nonce: Vec<u8> = (0..24).collect();
+        let salt: Vec<u8> = vec![9; 16];
+        let entry = Entry::new(
+            "gmail",
+            "john",
+            "Pass123",
+            &nonce,
+            &salt,
+            Cipher::XChaCha20Poly1305,
+        );
+
+        let binary_data = entry.serialize();
+        assert_eq!(binary_data[28], 0);
+        assert_eq!(
+            crate::storage::record_size(&binary_data),
+            Some(binary_data.len())
+        );
+
+        let new_entry = Entry::deserialize(&binary_data);
+        assert_eq!(new_entry.cipher, Cipher::XChaCha20Poly1305);
+        assert_eq!(new_entry.nonce, nonce);
+        assert_eq!(new_entry.salt, salt);
+        assert_eq!(new_entry.domain, "gmail");
+        assert_eq!(new_entry.password, "Pass123");
+    }
 }
@@ -1,3 +1,6 @@
 pub const UNSUPPORTED_OS: &str = "Unsupported operating system.";
 pub const INVALID_COMMAND: &str =
     "Invalid command. Type `dome help` to see a list of availible commands.";
+pub const DECRYPTION_FAILED: &str = "Decryption failed. Master password is not correct.";
+pub const BACKUP_DECRYPTION_FAILED: &str =
+    "Master password is not correct or the backup was modified.";
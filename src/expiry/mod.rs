@@ -0,0 +1,37 @@
+use crate::config;
+use crate::entry::index_entry::IndexEntry;
+
+// Passwords expire a number of days after they were last changed. The entry can have its own
+// policy, otherwise the strictest policy of its tags applies, and expiry.days after that.
+pub const DAY: u64 = 24 * 60 * 60;
+
+// Days the password is valid for, 0 if it never expires
+pub fn policy_days(entry: &IndexEntry) -> u64 {
+    if entry.expiry_days > 0 {
+        return entry.expiry_days as u64;
+    }
+
+    entry
+        .tags
+        .iter()
+        .map(|tag| config::tag_expiry_days(tag))
+        .filter(|days| *days > 0)
+        .min()
+        .unwrap_or_else(|| config::get_number("expiry.days"))
+}
+
+// Time the password should be changed by, None if it never expires. Entries saved before
+// the time of change was tracked expire right away once they have a policy.
+pub fn expires_at(entry: &IndexEntry) -> Option<u64> {
+    match policy_days(entry) {
+        0 => None,
+        days => Some(entry.modified + days * DAY),
+    }
+}
+
+pub fn is_expired(entry: &IndexEntry, now: u64) -> bool {
+    expires_at(entry).is_some_and(|time| time <= now)
+}
+
+#[cfg(test)]
+mod test;
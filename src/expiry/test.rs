@@ -0,0 +1,40 @@
+use super::*;
+use crate::storage;
+
+fn entry(tags: &[&str], modified: u64) -> IndexEntry {
+    let mut entry = IndexEntry::new("gmail", 0);
+    entry.tags = tags.iter().map(|tag| tag.to_string()).collect();
+    entry.modified = modified;
+    entry
+}
+
+#[test]
+fn test_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    let now = 1_000 * DAY;
+
+    let mut gmail = entry(&["mail"], now - 100 * DAY);
+    assert_eq!(policy_days(&gmail), 0);
+    assert!(!is_expired(&gmail, now));
+
+    config::set("expiry.days", "365").unwrap();
+    assert_eq!(expires_at(&gmail), Some(now + 265 * DAY));
+    assert!(!is_expired(&gmail, now));
+
+    // The strictest tag wins over the default
+    config::set("expiry.tag.mail", "90").unwrap();
+    config::set("expiry.tag.work", "180").unwrap();
+    gmail.tags.push(String::from("work"));
+    assert_eq!(policy_days(&gmail), 90);
+    assert!(is_expired(&gmail, now));
+
+    // Policy of the entry wins over the tags
+    gmail.expiry_days = 120;
+    assert_eq!(policy_days(&gmail), 120);
+    assert!(!is_expired(&gmail, now));
+    assert!(is_expired(&gmail, now + 20 * DAY));
+
+    // Time of change is unknown for old entries
+    assert!(is_expired(&entry(&[], 0), now));
+}
@@ -0,0 +1,217 @@
+use crate::command::{self, NewEntry};
+use crate::crypto;
+use crate::entry::write_string;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+pub const FORMAT_NAMES: [&str; 4] = ["bitwarden", "keepass-csv", "kdbx", "dome"];
+
+// Header of Dome's encrypted export: magic, version, salt and nonce
+pub const DOME_EXPORT_MAGIC: &[u8; 7] = b"DOMEEXP";
+pub const DOME_EXPORT_VERSION: u8 = 1;
+
+// Name of Bitwarden custom field that keeps Dome tags
+pub const TAGS_FIELD: &str = "tags";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Bitwarden,
+    KeepassCsv,
+    Kdbx,
+    Dome,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_lowercase().as_str() {
+            "bitwarden" => Some(Format::Bitwarden),
+            "keepass-csv" => Some(Format::KeepassCsv),
+            "kdbx" => Some(Format::Kdbx),
+            "dome" => Some(Format::Dome),
+            _ => None,
+        }
+    }
+
+    pub fn is_plaintext(&self) -> bool {
+        *self != Format::Dome && *self != Format::Kdbx
+    }
+}
+
+// Decrypts every entry of the vault. Returns domains that can't be decrypted with the
+// master password separately.
+pub fn collect(master_password: &str) -> Result<(Vec<NewEntry>, Vec<String>), String> {
+    let index_set = command::get_index_set()?;
+    let mut entries = Vec::new();
+    let mut failed = Vec::new();
+
+    for index_entry in &index_set.data {
+        match command::get_entry(&index_entry.key, master_password) {
+            Ok((entry, password)) => entries.push(NewEntry {
+                domain: index_entry.key.clone(),
+                username: entry.username,
+                password,
+                url: index_entry.url.clone(),
+                tags: index_entry.tags.clone(),
+                folder: index_entry.folder.clone(),
+            }),
+            Err(_) => failed.push(index_entry.key.clone()),
+        }
+    }
+
+    Ok((entries, failed))
+}
+
+// Unencrypted JSON in the format of Bitwarden export
+pub fn to_bitwarden(entries: &[NewEntry]) -> String {
+    let mut folders: BTreeMap<&str, String> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.folder.is_empty()) {
+        folders.entry(&entry.folder).or_insert_with(random_uuid);
+    }
+
+    let items: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            let uris: Vec<Value> = match entry.url.as_str() {
+                "" => Vec::new(),
+                url => vec![json!({ "match": null, "uri": url })],
+            };
+
+            let fields: Vec<Value> = match entry.tags.is_empty() {
+                true => Vec::new(),
+                false => {
+                    vec![json!({ "name": TAGS_FIELD, "value": entry.tags.join(","), "type": 0 })]
+                }
+            };
+
+            json!({
+                "id": random_uuid(),
+                "organizationId": null,
+                "folderId": folders.get(entry.folder.as_str()),
+                "type": 1,
+                "reprompt": 0,
+                "name": entry.domain,
+                "notes": null,
+                "favorite": false,
+                "fields": fields,
+                "login": {
+                    "uris": uris,
+                    "username": entry.username,
+                    "password": entry.password,
+                    "totp": null,
+                },
+                "collectionIds": null,
+            })
+        })
+        .collect();
+
+    let folders: Vec<Value> = folders
+        .iter()
+        .map(|(name, id)| json!({ "id": id, "name": name }))
+        .collect();
+
+    let export = json!({ "encrypted": false, "folders": folders, "items": items });
+    serde_json::to_string_pretty(&export).unwrap()
+}
+
+// CSV with the columns of KeePassXC export, plus tags
+pub fn to_keepass_csv(entries: &[NewEntry]) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Always)
+        .from_writer(Vec::new());
+
+    let header = [
+        "Group", "Title", "Username", "Password", "URL", "Notes", "Tags",
+    ];
+    writer.write_record(header).unwrap();
+
+    for entry in entries {
+        // KeePass group path starts with the database root group
+        let group = match entry.folder.as_str() {
+            "" => String::from("Root"),
+            folder => format!("Root/{}", folder),
+        };
+
+        writer
+            .write_record([
+                group.as_str(),
+                &entry.domain,
+                &entry.username,
+                &entry.password,
+                &entry.url,
+                "",
+                &entry.tags.join(","),
+            ])
+            .unwrap();
+    }
+
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+// Entries encrypted with a key derived from the passphrase
+pub fn to_dome(entries: &[NewEntry], passphrase: &str) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.extend((entries.len() as u32).to_le_bytes());
+
+    for entry in entries {
+        write_string(&mut payload, &entry.domain);
+        write_string(&mut payload, &entry.username);
+        write_string(&mut payload, &entry.password);
+        write_string(&mut payload, &entry.url);
+        write_string(&mut payload, &entry.folder);
+        payload.extend((entry.tags.len() as u16).to_le_bytes());
+        for tag in &entry.tags {
+            write_string(&mut payload, tag);
+        }
+    }
+
+    let salt = crypto::generate_salt();
+    let key = crypto::derive_key(passphrase, &salt);
+    let (cipher_text, nonce) = crypto::encrypt_bytes(&key, &payload);
+
+    let mut data = Vec::new();
+    data.extend(DOME_EXPORT_MAGIC);
+    data.push(DOME_EXPORT_VERSION);
+    data.extend(salt);
+    data.extend(nonce);
+    data.extend(cipher_text);
+    data
+}
+
+// Creates the file readable only by the current user. Existing file is replaced.
+pub fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options
+        .open(path)
+        .map_err(|err| format!("Can't create {}: {}", path.display(), err))?;
+
+    file.write_all(data)
+        .map_err(|err| format!("Can't write {}: {}", path.display(), err))
+}
+
+fn random_uuid() -> String {
+    let bytes = rand::random::<[u8; 16]>();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,86 @@
+use super::*;
+use crate::import;
+use crate::storage;
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+const PASSPHRASE: &str = "export-passphrase";
+
+fn prepare_entries() -> Vec<NewEntry> {
+    vec![
+        NewEntry {
+            domain: String::from("gmail"),
+            username: String::from("john.doe@gmail.com"),
+            password: String::from("pass,with \"quotes\"\nand newline"),
+            url: String::from("https://mail.google.com"),
+            tags: vec![String::from("mail"), String::from("personal")],
+            folder: String::new(),
+        },
+        NewEntry {
+            domain: String::from("postgres"),
+            username: String::from("postgres"),
+            password: String::from("pg-s3cret"),
+            url: String::new(),
+            tags: Vec::new(),
+            folder: String::from("work/infra"),
+        },
+    ]
+}
+
+fn assert_same(entries: &[NewEntry], imported: &[NewEntry]) {
+    assert_eq!(entries.len(), imported.len());
+
+    for (entry, imported) in entries.iter().zip(imported) {
+        assert_eq!(entry.domain, imported.domain);
+        assert_eq!(entry.username, imported.username);
+        assert_eq!(entry.password, imported.password);
+        assert_eq!(entry.url, imported.url);
+        assert_eq!(entry.tags, imported.tags);
+        assert_eq!(entry.folder, imported.folder);
+    }
+}
+
+#[test]
+fn test_bitwarden_round_trip() {
+    let entries = prepare_entries();
+    let imported = import::bitwarden::parse(&to_bitwarden(&entries)).unwrap();
+
+    assert_same(&entries, &imported);
+}
+
+#[test]
+fn test_keepass_csv_round_trip() {
+    let entries = prepare_entries();
+    let imported =
+        import::csv_file::parse(&to_keepass_csv(&entries), import::Format::KeepassCsv).unwrap();
+
+    assert_same(&entries, &imported);
+}
+
+#[test]
+fn test_dome_round_trip() {
+    let entries = prepare_entries();
+    let data = to_dome(&entries, PASSPHRASE);
+
+    assert!(!data.windows(9).any(|window| window == b"pg-s3cret"));
+    assert_same(&entries, &import::dome::parse(&data, PASSPHRASE).unwrap());
+    assert!(import::dome::parse(&data, "wrong passphrase").is_err());
+}
+
+#[test]
+fn test_collect() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    for entry in prepare_entries() {
+        command::add_entry(&entry, MASTER_PASSWORD).unwrap();
+    }
+
+    let (entries, failed) = collect(MASTER_PASSWORD).unwrap();
+    assert_same(&prepare_entries(), &entries);
+    assert!(failed.is_empty());
+
+    let path = dir.path().join("export.dome");
+    write_file(&path, &to_dome(&entries, PASSPHRASE)).unwrap();
+    let imported = import::read(import::Format::Dome, &path, Some(PASSPHRASE)).unwrap();
+    assert_same(&entries, &imported);
+}
@@ -0,0 +1,66 @@
+use crate::command::NewEntry;
+use crate::entry::index_entry;
+use crate::export::TAGS_FIELD;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// Bitwarden item type of a login, other types are notes, cards and identities
+const LOGIN_TYPE: u64 = 1;
+
+// Parses unencrypted Bitwarden JSON export
+pub fn parse(data: &str) -> Result<Vec<NewEntry>, String> {
+    let json: Value =
+        serde_json::from_str(data).map_err(|err| format!("Invalid Bitwarden export: {}", err))?;
+
+    if json["encrypted"].as_bool() == Some(true) {
+        return Err(String::from(
+            "Encrypted Bitwarden exports are not supported. Export the vault as unencrypted JSON.",
+        ));
+    }
+
+    let items = json["items"]
+        .as_array()
+        .ok_or("Invalid Bitwarden export: missing items.")?;
+
+    let folders: HashMap<&str, &str> = json["folders"]
+        .as_array()
+        .map(|folders| {
+            folders
+                .iter()
+                .filter_map(|f| Some((f["id"].as_str()?, f["name"].as_str()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let entries = items
+        .iter()
+        .filter(|item| item["type"].as_u64() == Some(LOGIN_TYPE))
+        .map(|item| {
+            let login = &item["login"];
+            let url = login["uris"][0]["uri"].as_str().unwrap_or("");
+            let folder = item["folderId"]
+                .as_str()
+                .and_then(|id| folders.get(id))
+                .unwrap_or(&"");
+
+            // Tags are kept in a custom field by Dome's export
+            let tags = item["fields"]
+                .as_array()
+                .and_then(|fields| fields.iter().find(|f| f["name"] == TAGS_FIELD))
+                .and_then(|field| field["value"].as_str())
+                .map(index_entry::parse_tags)
+                .unwrap_or_default();
+
+            super::new_entry(
+                item["name"].as_str().unwrap_or(""),
+                login["username"].as_str().unwrap_or(""),
+                login["password"].as_str().unwrap_or(""),
+                url,
+                tags,
+                folder,
+            )
+        })
+        .collect();
+
+    Ok(entries)
+}
@@ -0,0 +1,69 @@
+use super::Format;
+use crate::command::NewEntry;
+use crate::entry::index_entry;
+
+// Column names used by different password managers, compared case-insensitively
+const NAME_COLUMNS: [&str; 3] = ["name", "title", "account"];
+const URL_COLUMNS: [&str; 4] = ["url", "login_uri", "web site", "website"];
+const USERNAME_COLUMNS: [&str; 4] = ["username", "login name", "login_username", "user name"];
+const PASSWORD_COLUMNS: [&str; 2] = ["password", "login_password"];
+const FOLDER_COLUMNS: [&str; 3] = ["grouping", "group", "folder"];
+const TAG_COLUMNS: [&str; 1] = ["tags"];
+
+// Parses CSV exports of KeePass, 1Password, LastPass, Chrome and Firefox. They differ
+// mostly in column names, which are looked up in the header.
+pub fn parse(data: &str, format: Format) -> Result<Vec<NewEntry>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|err| format!("Invalid CSV file: {}", err))?
+        .iter()
+        .map(|header| header.trim().to_lowercase())
+        .collect();
+
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let password_column =
+        column(&PASSWORD_COLUMNS).ok_or("Invalid CSV file: missing password column.")?;
+    let (name_column, url_column, username_column, folder_column, tag_column) = (
+        column(&NAME_COLUMNS),
+        column(&URL_COLUMNS),
+        column(&USERNAME_COLUMNS),
+        column(&FOLDER_COLUMNS),
+        column(&TAG_COLUMNS),
+    );
+
+    let mut entries = Vec::new();
+
+    for record in reader.records() {
+        let record = record.map_err(|err| format!("Invalid CSV file: {}", err))?;
+        let field = |column: Option<usize>| column.and_then(|c| record.get(c)).unwrap_or("");
+
+        let folder = match format {
+            // LastPass separates nested folders with backslash
+            Format::LastPass => field(folder_column).replace('\\', "/"),
+            // KeePass group path starts with the name of the database
+            Format::KeepassCsv => match field(folder_column).split_once('/') {
+                Some((_, rest)) => String::from(rest),
+                None => String::new(),
+            },
+            _ => String::from(field(folder_column)),
+        };
+
+        let tags = index_entry::parse_tags(&field(tag_column).replace(';', ","));
+
+        entries.push(super::new_entry(
+            field(name_column),
+            field(username_column),
+            field(Some(password_column)),
+            field(url_column),
+            tags,
+            &folder,
+        ));
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,53 @@
+use crate::command::NewEntry;
+use crate::crypto::{self, NONCE_SIZE, SALT_SIZE};
+use crate::entry::ByteReader;
+use crate::export::{DOME_EXPORT_MAGIC, DOME_EXPORT_VERSION};
+
+// Parses Dome's encrypted export
+pub fn parse(data: &[u8], passphrase: &str) -> Result<Vec<NewEntry>, String> {
+    if !data.starts_with(DOME_EXPORT_MAGIC) {
+        return Err(String::from("File is not a Dome export."));
+    }
+
+    let mut reader = ByteReader::new(&data[DOME_EXPORT_MAGIC.len()..]);
+    let invalid = || String::from("Dome export is damaged.");
+
+    let version = reader.read_u8().ok_or_else(invalid)?;
+    if version > DOME_EXPORT_VERSION {
+        return Err(String::from(
+            "Export was created by a newer version of Dome.",
+        ));
+    }
+
+    let salt = reader.read_bytes(SALT_SIZE).ok_or_else(invalid)?;
+    let nonce = reader.read_bytes(NONCE_SIZE).ok_or_else(invalid)?;
+    let cipher_text = reader.read_rest();
+
+    let key = crypto::derive_key(passphrase, salt);
+    let payload = crypto::decrypt_bytes(&key, nonce, cipher_text)
+        .map_err(|_| String::from("Passphrase is not correct or the export was modified."))?;
+
+    let mut reader = ByteReader::new(&payload);
+    let count = reader.read_u32().ok_or_else(invalid)?;
+    let mut entries = Vec::new();
+
+    for _ in 0..count {
+        let mut entry = NewEntry {
+            domain: reader.read_string().ok_or_else(invalid)?,
+            username: reader.read_string().ok_or_else(invalid)?,
+            password: reader.read_string().ok_or_else(invalid)?,
+            url: reader.read_string().ok_or_else(invalid)?,
+            folder: reader.read_string().ok_or_else(invalid)?,
+            tags: Vec::new(),
+        };
+
+        let tag_count = reader.read_u16().ok_or_else(invalid)?;
+        for _ in 0..tag_count {
+            entry.tags.push(reader.read_string().ok_or_else(invalid)?);
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,121 @@
+use crate::command::NewEntry;
+use crate::entry::index_entry;
+use base64::{engine::general_purpose, Engine as _};
+use roxmltree::{Document, Node, NodeId};
+use std::collections::HashMap;
+
+// Decrypts protected value in place
+type Unprotect<'a> = &'a mut dyn FnMut(&mut [u8]);
+
+// Parses XML export of KeePass 2 or KeePassXC
+pub fn parse(xml: &str) -> Result<Vec<NewEntry>, String> {
+    parse_document(xml, None)
+}
+
+// Parses XML of KDBX database, where protected values are XORed with the inner stream
+pub fn parse_protected(xml: &str, unprotect: Unprotect) -> Result<Vec<NewEntry>, String> {
+    parse_document(xml, Some(unprotect))
+}
+
+fn parse_document(xml: &str, unprotect: Option<Unprotect>) -> Result<Vec<NewEntry>, String> {
+    let document =
+        Document::parse(xml).map_err(|err| format!("Invalid KeePass XML export: {}", err))?;
+    let root = document.root_element();
+
+    if root.tag_name().name() != "KeePassFile" {
+        return Err(String::from(
+            "Invalid KeePass XML export: missing KeePassFile.",
+        ));
+    }
+
+    let recycle_bin = child(root, "Meta")
+        .and_then(|meta| child_text(meta, "RecycleBinUUID"))
+        .unwrap_or_default();
+
+    // Stream covers every protected value in document order, history entries included
+    let mut protected = HashMap::new();
+    if let Some(unprotect) = unprotect {
+        let values = document
+            .descendants()
+            .filter(|n| n.has_tag_name("Value") && n.attribute("Protected") == Some("True"));
+
+        for value in values {
+            let mut bytes = general_purpose::STANDARD
+                .decode(value.text().unwrap_or("").trim())
+                .map_err(|_| String::from("Invalid protected value in KeePass database."))?;
+            unprotect(&mut bytes);
+            protected.insert(value.id(), String::from_utf8_lossy(&bytes).to_string());
+        }
+    }
+
+    let mut entries = Vec::new();
+
+    // Top level group is the database itself, so it isn't used as a folder
+    if let Some(database) = child(root, "Root").and_then(|r| child(r, "Group")) {
+        walk_group(database, &[], &recycle_bin, &protected, &mut entries);
+    }
+
+    Ok(entries)
+}
+
+fn walk_group(
+    group: Node,
+    path: &[String],
+    recycle_bin: &str,
+    protected: &HashMap<NodeId, String>,
+    entries: &mut Vec<NewEntry>,
+) {
+    let folder = path.join("/");
+
+    for node in group.children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "Entry" => entries.push(parse_entry(node, &folder, protected)),
+            "Group" => {
+                let uuid = child_text(node, "UUID").unwrap_or_default();
+                if !recycle_bin.is_empty() && uuid == recycle_bin {
+                    continue;
+                }
+
+                let mut path = path.to_vec();
+                path.push(child_text(node, "Name").unwrap_or_default());
+                walk_group(node, &path, recycle_bin, protected, entries);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_entry(entry: Node, folder: &str, protected: &HashMap<NodeId, String>) -> NewEntry {
+    let mut fields = (String::new(), String::new(), String::new(), String::new());
+
+    for string in entry.children().filter(|n| n.has_tag_name("String")) {
+        let key = child_text(string, "Key").unwrap_or_default();
+        let value = match child(string, "Value") {
+            Some(value) if protected.contains_key(&value.id()) => protected[&value.id()].clone(),
+            Some(value) => value.text().unwrap_or("").to_string(),
+            None => String::new(),
+        };
+
+        match key.as_str() {
+            "Title" => fields.0 = value,
+            "UserName" => fields.1 = value,
+            "Password" => fields.2 = value,
+            "URL" => fields.3 = value,
+            _ => {}
+        }
+    }
+
+    let tags = child_text(entry, "Tags")
+        .map(|tags| index_entry::parse_tags(&tags.replace(';', ",")))
+        .unwrap_or_default();
+
+    super::new_entry(&fields.0, &fields.1, &fields.2, &fields.3, tags, folder)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name).map(|n| n.text().unwrap_or("").to_string())
+}
@@ -0,0 +1,258 @@
+use crate::command::{self, NewEntry};
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::index_entry;
+use crate::kdbx;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+pub mod bitwarden;
+pub mod csv_file;
+pub mod dome;
+pub mod keepass;
+pub mod pass;
+
+pub const FORMAT_NAMES: [&str; 10] = [
+    "bitwarden",
+    "kdbx",
+    "keepass-xml",
+    "keepass-csv",
+    "1password",
+    "lastpass",
+    "chrome",
+    "firefox",
+    "pass",
+    "dome",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Bitwarden,
+    Kdbx,
+    KeepassXml,
+    KeepassCsv,
+    OnePassword,
+    LastPass,
+    Chrome,
+    Firefox,
+    Pass,
+    Dome,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_lowercase().as_str() {
+            "bitwarden" => Some(Format::Bitwarden),
+            "kdbx" => Some(Format::Kdbx),
+            "keepass-xml" => Some(Format::KeepassXml),
+            "keepass-csv" => Some(Format::KeepassCsv),
+            "1password" => Some(Format::OnePassword),
+            "lastpass" => Some(Format::LastPass),
+            "chrome" => Some(Format::Chrome),
+            "firefox" => Some(Format::Firefox),
+            "pass" => Some(Format::Pass),
+            "dome" => Some(Format::Dome),
+            _ => None,
+        }
+    }
+}
+
+// What to do with an imported entry whose domain is already in the vault
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Duplicates {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl Duplicates {
+    pub fn from_name(name: &str) -> Option<Duplicates> {
+        match name.to_lowercase().as_str() {
+            "skip" => Some(Duplicates::Skip),
+            "overwrite" => Some(Duplicates::Overwrite),
+            "rename" => Some(Duplicates::Rename),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Add,
+    Overwrite,
+    Rename(String),
+    Skip(String),
+}
+
+pub struct PlannedEntry {
+    pub entry: NewEntry,
+    pub action: Action,
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub added: usize,
+    pub overwritten: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+    pub failed: Vec<(String, String)>,
+}
+
+// Reads entries from an export file, or from a directory for `pass`. Passphrase is
+// needed only for Dome's encrypted export and KeePass database.
+pub fn read(
+    format: Format,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<Vec<NewEntry>, String> {
+    if format == Format::Pass {
+        return pass::parse(path);
+    }
+
+    if format == Format::Dome || format == Format::Kdbx {
+        let data =
+            fs::read(path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+
+        return match format {
+            Format::Dome => dome::parse(&data, passphrase.unwrap_or_default()),
+            _ => kdbx::read(&data, passphrase.unwrap_or_default()),
+        };
+    }
+
+    let data = fs::read_to_string(path)
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+
+    match format {
+        Format::Bitwarden => bitwarden::parse(&data),
+        Format::KeepassXml => keepass::parse(&data),
+        _ => csv_file::parse(&data, format),
+    }
+}
+
+// Decides what happens with every imported entry, nothing is written yet
+pub fn plan(
+    entries: Vec<NewEntry>,
+    existing: &IndexSet,
+    duplicates: Duplicates,
+) -> Vec<PlannedEntry> {
+    let mut taken: HashSet<String> = existing.data.iter().map(|e| e.key.clone()).collect();
+    let mut planned = Vec::new();
+
+    for entry in entries {
+        let action = if entry.domain.is_empty() {
+            Action::Skip(String::from("no name or URL"))
+        } else if entry.password.is_empty() {
+            Action::Skip(String::from("no password"))
+        } else if !taken.contains(&entry.domain) {
+            Action::Add
+        } else {
+            match duplicates {
+                Duplicates::Skip => Action::Skip(String::from("already exists")),
+                Duplicates::Overwrite => Action::Overwrite,
+                Duplicates::Rename => Action::Rename(free_name(&entry.domain, &taken)),
+            }
+        };
+
+        match &action {
+            Action::Add => {
+                taken.insert(entry.domain.clone());
+            }
+            Action::Rename(name) => {
+                taken.insert(name.clone());
+            }
+            _ => {}
+        }
+
+        planned.push(PlannedEntry { entry, action });
+    }
+
+    planned
+}
+
+// Writes planned entries into the vault
+pub fn apply(planned: &[PlannedEntry], master_password: &str) -> Report {
+    let mut report = Report::default();
+
+    for item in planned {
+        let result = match &item.action {
+            Action::Skip(_) => {
+                report.skipped += 1;
+                continue;
+            }
+            Action::Add => command::add_entry(&item.entry, master_password),
+            Action::Overwrite => command::put_entry(&item.entry, master_password),
+            Action::Rename(name) => {
+                let entry = NewEntry {
+                    domain: name.clone(),
+                    ..item.entry.clone()
+                };
+                command::add_entry(&entry, master_password)
+            }
+        };
+
+        match (result, &item.action) {
+            (Err(err), _) => report.failed.push((item.entry.domain.clone(), err)),
+            (Ok(()), Action::Overwrite) => report.overwritten += 1,
+            (Ok(()), Action::Rename(_)) => report.renamed += 1,
+            (Ok(()), _) => report.added += 1,
+        }
+    }
+
+    report
+}
+
+// Builds entry from imported fields. Entries without name are named after the host of their URL.
+fn new_entry(
+    name: &str,
+    username: &str,
+    password: &str,
+    url: &str,
+    tags: Vec<String>,
+    folder: &str,
+) -> NewEntry {
+    let domain = match name.trim() {
+        "" => host_from_url(url),
+        name => String::from(name),
+    };
+
+    NewEntry {
+        domain,
+        username: String::from(username),
+        password: String::from(password),
+        url: String::from(url.trim()),
+        tags,
+        folder: index_entry::normalize_folder(folder),
+    }
+}
+
+// "https://www.github.com:443/login" -> "github.com"
+pub fn host_from_url(url: &str) -> String {
+    let without_scheme = match url.trim().split_once("://") {
+        Some((_, rest)) => rest,
+        None => url.trim(),
+    };
+
+    let host = without_scheme
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or("")
+        .rsplit('@')
+        .next()
+        .unwrap_or("")
+        .split(':')
+        .next()
+        .unwrap_or("");
+
+    host.trim_start_matches("www.").to_lowercase()
+}
+
+// "gmail" -> "gmail-2", or the next number that isn't taken
+fn free_name(domain: &str, taken: &HashSet<String>) -> String {
+    (2..)
+        .map(|num| format!("{}-{}", domain, num))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,101 @@
+use crate::command::NewEntry;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const USERNAME_KEYS: [&str; 4] = ["login", "username", "user", "email"];
+
+// Parses password store in the layout used by `pass`. Every file is one entry, the first
+// line is the password and following lines can contain `login:` and `url:`. Encrypted
+// .gpg files are decrypted with gpg, other files are read as they are.
+pub fn parse(dir: &Path) -> Result<Vec<NewEntry>, String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory.", dir.display()));
+    }
+
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let mut entries = Vec::new();
+
+    for file in files {
+        let relative = file.strip_prefix(dir).unwrap().with_extension("");
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let folder = relative
+            .parent()
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+
+        let content = read_file(&file)?;
+        let mut lines = content.lines();
+        let password = lines.next().unwrap_or("");
+        let (mut username, mut url) = ("", "");
+
+        for line in lines {
+            if let Some((key, value)) = line.split_once(':') {
+                let key = key.trim().to_lowercase();
+
+                if USERNAME_KEYS.contains(&key.as_str()) && username.is_empty() {
+                    username = value.trim();
+                } else if key == "url" && url.is_empty() {
+                    url = value.trim();
+                }
+            }
+        }
+
+        entries.push(super::new_entry(
+            &name,
+            username,
+            password,
+            url,
+            Vec::new(),
+            &folder,
+        ));
+    }
+
+    Ok(entries)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<(), String> {
+    let read_dir =
+        fs::read_dir(dir).map_err(|err| format!("Can't read {}: {}", dir.display(), err))?;
+
+    for item in read_dir.flatten() {
+        let path = item.path();
+
+        // Skips .git, .gpg-id and other metadata of the store
+        if item.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_file(file: &Path) -> Result<String, String> {
+    if file.extension().map(|e| e == "gpg").unwrap_or(false) {
+        let output = Command::new("gpg")
+            .args(["--quiet", "--decrypt"])
+            .arg(file)
+            .output()
+            .map_err(|err| format!("Can't run gpg to decrypt {}: {}", file.display(), err))?;
+
+        if !output.status.success() {
+            return Err(format!("gpg failed to decrypt {}.", file.display()));
+        }
+
+        return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+    }
+
+    fs::read_to_string(file).map_err(|err| format!("Can't read {}: {}", file.display(), err))
+}
@@ -0,0 +1,210 @@
+use super::*;
+use crate::entry::index_entry::IndexEntry;
+use crate::storage;
+use std::path::PathBuf;
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/import")
+        .join(name)
+}
+
+fn import(format: Format, name: &str) -> Vec<NewEntry> {
+    read(format, &fixture(name), None).unwrap()
+}
+
+fn entry(domain: &str, password: &str) -> NewEntry {
+    new_entry(domain, "", password, "", Vec::new(), "")
+}
+
+#[test]
+fn test_bitwarden() {
+    let entries = import(Format::Bitwarden, "bitwarden.json");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].domain, "gmail");
+    assert_eq!(entries[0].username, "john.doe@gmail.com");
+    assert_eq!(entries[0].password, "gm41l-pa55");
+    assert_eq!(entries[0].url, "https://mail.google.com");
+    assert_eq!(entries[1].domain, "db.example.com");
+    assert_eq!(entries[1].folder, "Work/Infra");
+}
+
+#[test]
+fn test_bitwarden_encrypted() {
+    let result = bitwarden::parse(r#"{"encrypted": true, "items": []}"#);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_keepass_xml() {
+    let entries = import(Format::KeepassXml, "keepass.xml");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].domain, "gmail");
+    assert_eq!(entries[0].password, "gm41l-pa55");
+    assert_eq!(entries[0].tags, vec!["mail", "personal"]);
+    assert_eq!(entries[0].folder, "");
+    assert_eq!(entries[1].domain, "postgres");
+    assert_eq!(entries[1].password, "pg-s3cret & more");
+    assert_eq!(entries[1].folder, "Work/Infra");
+}
+
+// Database was created by another KDBX 4 implementation, password is "refpass"
+#[test]
+fn test_kdbx() {
+    let entries = read(Format::Kdbx, &fixture("keepass.kdbx"), Some("refpass")).unwrap();
+    let gmail = entries.iter().find(|e| e.domain == "gmail").unwrap();
+    let postgres = entries.iter().find(|e| e.domain == "postgres").unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(gmail.username, "john.doe@gmail.com");
+    assert_eq!(gmail.password, "gm41l <pa55>");
+    assert_eq!(gmail.url, "https://mail.google.com");
+    assert_eq!(gmail.tags, vec!["mail"]);
+    assert_eq!(gmail.folder, "");
+    assert_eq!(postgres.password, "pg-s3cret");
+    assert_eq!(postgres.folder, "Work/Infra");
+}
+
+#[test]
+fn test_kdbx_wrong_password() {
+    assert!(read(Format::Kdbx, &fixture("keepass.kdbx"), Some("wrong")).is_err());
+}
+
+#[test]
+fn test_keepass_csv() {
+    let entries = import(Format::KeepassCsv, "keepass.csv");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].folder, "");
+    assert_eq!(entries[1].password, "pg-s3cret, with comma");
+    assert_eq!(entries[1].folder, "Work/Infra");
+}
+
+#[test]
+fn test_1password() {
+    let entries = import(Format::OnePassword, "1password.csv");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].url, "https://mail.google.com");
+    assert_eq!(entries[0].tags, vec!["mail", "personal"]);
+    assert_eq!(entries[1].username, "postgres");
+}
+
+#[test]
+fn test_lastpass() {
+    let entries = import(Format::LastPass, "lastpass.csv");
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1].domain, "postgres");
+    assert_eq!(entries[1].folder, "Work/Infra");
+
+    // Secure notes have no password and are skipped
+    let planned = plan(entries, &IndexSet::new(), Duplicates::Skip);
+    assert_eq!(planned[2].action, Action::Skip(String::from("no password")));
+}
+
+#[test]
+fn test_chrome() {
+    let entries = import(Format::Chrome, "chrome.csv");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].domain, "mail.google.com");
+    assert_eq!(entries[1].url, "https://db.example.com/login");
+}
+
+#[test]
+fn test_firefox() {
+    let entries = import(Format::Firefox, "firefox.csv");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].domain, "mail.google.com");
+    assert_eq!(entries[1].domain, "db.example.com");
+    assert_eq!(entries[1].password, "pg-s3cret");
+}
+
+#[test]
+fn test_pass() {
+    let entries = import(Format::Pass, "pass");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].domain, "gmail");
+    assert_eq!(entries[0].username, "john.doe@gmail.com");
+    assert_eq!(entries[0].url, "https://mail.google.com");
+    assert_eq!(entries[1].domain, "postgres");
+    assert_eq!(entries[1].username, "postgres");
+    assert_eq!(entries[1].folder, "work/infra");
+}
+
+#[test]
+fn test_host_from_url() {
+    assert_eq!(
+        host_from_url("https://www.GitHub.com:443/login?x=1"),
+        "github.com"
+    );
+    assert_eq!(
+        host_from_url("user@ftp.example.com/path"),
+        "ftp.example.com"
+    );
+    assert_eq!(host_from_url(""), "");
+}
+
+#[test]
+fn test_plan_duplicates() {
+    let mut existing = IndexSet::new();
+    existing.add(&IndexEntry::new("gmail", 0));
+    existing.add(&IndexEntry::new("gmail-2", 10));
+
+    let entries = || {
+        vec![
+            entry("gmail", "a"),
+            entry("github", "b"),
+            entry("github", "c"),
+        ]
+    };
+
+    let actions: Vec<Action> = plan(entries(), &existing, Duplicates::Skip)
+        .into_iter()
+        .map(|p| p.action)
+        .collect();
+    assert_eq!(actions[0], Action::Skip(String::from("already exists")));
+    assert_eq!(actions[1], Action::Add);
+    assert_eq!(actions[2], Action::Skip(String::from("already exists")));
+
+    let planned = plan(entries(), &existing, Duplicates::Rename);
+    assert_eq!(planned[0].action, Action::Rename(String::from("gmail-3")));
+    assert_eq!(planned[2].action, Action::Rename(String::from("github-2")));
+
+    let planned = plan(entries(), &existing, Duplicates::Overwrite);
+    assert_eq!(planned[0].action, Action::Overwrite);
+    assert_eq!(planned[2].action, Action::Overwrite);
+}
+
+#[test]
+fn test_apply() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    command::add_entry(&entry("gmail", "old"), MASTER_PASSWORD).unwrap();
+
+    let entries = import(Format::Bitwarden, "bitwarden.json");
+    let planned = plan(
+        entries,
+        &command::get_index_set().unwrap(),
+        Duplicates::Overwrite,
+    );
+    let report = apply(&planned, MASTER_PASSWORD);
+
+    assert_eq!(report.added, 1);
+    assert_eq!(report.overwritten, 1);
+    assert!(report.failed.is_empty());
+
+    let (_, password) = command::get_entry("gmail", MASTER_PASSWORD).unwrap();
+    assert_eq!(password, "gm41l-pa55");
+    let index_set = command::get_index_set().unwrap();
+    assert_eq!(index_set.in_folder("work").len(), 0);
+    assert_eq!(index_set.in_folder("Work/Infra").len(), 1);
+}
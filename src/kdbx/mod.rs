@@ -0,0 +1,661 @@
+use crate::command::NewEntry;
+use crate::entry::ByteReader;
+use crate::import::keepass;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20::cipher::StreamCipher;
+use chacha20::ChaCha20;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const VERSION_MAJOR: u16 = 4;
+const VERSION_MINOR: u16 = 0;
+
+// Outer header field IDs
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION_FLAGS: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+// Inner header field IDs
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+
+const CHACHA20_STREAM: u32 = 3;
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+const AES256_UUID: [u8; 16] = [
+    0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff,
+];
+const CHACHA20_UUID: [u8; 16] = [
+    0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5, 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a,
+];
+const ARGON2D_UUID: [u8; 16] = [
+    0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c,
+];
+const ARGON2ID_UUID: [u8; 16] = [
+    0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73, 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6,
+];
+const AES_KDF_UUID: [u8; 16] = [
+    0xc9, 0xd9, 0xf3, 0x9a, 0x62, 0x8a, 0x44, 0x60, 0xbf, 0x74, 0x0d, 0x08, 0xc1, 0x8a, 0x4f, 0xea,
+];
+
+// Seconds between 0001-01-01 and 1970-01-01, KDBX 4 times count from the former
+const EPOCH_OFFSET: i64 = 62_135_596_800;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cipher {
+    Aes256,
+    ChaCha20,
+}
+
+impl Cipher {
+    pub fn from_name(name: &str) -> Option<Cipher> {
+        match name.to_lowercase().as_str() {
+            "aes" | "aes256" => Some(Cipher::Aes256),
+            "chacha20" => Some(Cipher::ChaCha20),
+            _ => None,
+        }
+    }
+
+    fn uuid(&self) -> [u8; 16] {
+        match self {
+            Cipher::Aes256 => AES256_UUID,
+            Cipher::ChaCha20 => CHACHA20_UUID,
+        }
+    }
+
+    fn iv_size(&self) -> usize {
+        match self {
+            Cipher::Aes256 => 16,
+            Cipher::ChaCha20 => 12,
+        }
+    }
+}
+
+// Argon2d parameters of written databases, memory in KiB
+#[derive(Debug, Clone, Copy)]
+pub struct KdfSettings {
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfSettings {
+    // Same as the defaults of KeePassXC
+    fn default() -> Self {
+        KdfSettings {
+            memory: 64 * 1024,
+            iterations: 10,
+            parallelism: 2,
+        }
+    }
+}
+
+enum Kdf {
+    Argon2 {
+        algorithm: Algorithm,
+        salt: Vec<u8>,
+        memory: u32,
+        iterations: u32,
+        parallelism: u32,
+        version: Version,
+    },
+    Aes {
+        seed: Vec<u8>,
+        rounds: u64,
+    },
+}
+
+// Reads KeePass database in KDBX 4 format
+pub fn read(data: &[u8], password: &str) -> Result<Vec<NewEntry>, String> {
+    let invalid = || String::from("KeePass database is damaged.");
+    let mut reader = ByteReader::new(data);
+
+    if reader.read_u32() != Some(SIGNATURE_1) || reader.read_u32() != Some(SIGNATURE_2) {
+        return Err(String::from("File is not a KeePass database."));
+    }
+
+    let _minor = reader.read_u16().ok_or_else(invalid)?;
+    let major = reader.read_u16().ok_or_else(invalid)?;
+    if major != VERSION_MAJOR {
+        return Err(format!(
+            "KDBX {} is not supported, save the database in KDBX 4 format.",
+            major
+        ));
+    }
+
+    let mut cipher = None;
+    let mut compressed = false;
+    let mut master_seed = Vec::new();
+    let mut iv = Vec::new();
+    let mut kdf = None;
+
+    loop {
+        let id = reader.read_u8().ok_or_else(invalid)?;
+        let size = reader.read_u32().ok_or_else(invalid)? as usize;
+        let value = reader.read_bytes(size).ok_or_else(invalid)?;
+
+        match id {
+            END_OF_HEADER => break,
+            CIPHER_ID if value == AES256_UUID => cipher = Some(Cipher::Aes256),
+            CIPHER_ID if value == CHACHA20_UUID => cipher = Some(Cipher::ChaCha20),
+            CIPHER_ID => return Err(String::from("Cipher of the database is not supported.")),
+            COMPRESSION_FLAGS => compressed = value.first().is_some_and(|flag| *flag == 1),
+            MASTER_SEED => master_seed = value.to_vec(),
+            ENCRYPTION_IV => iv = value.to_vec(),
+            KDF_PARAMETERS => kdf = Some(read_kdf(value)?),
+            _ => {}
+        }
+    }
+
+    let header = &data[..data.len() - reader.read_rest().len()];
+    let mut reader = ByteReader::new(&data[header.len()..]);
+    let cipher = cipher.ok_or_else(invalid)?;
+    let kdf = kdf.ok_or_else(invalid)?;
+
+    if master_seed.len() != 32 || iv.len() != cipher.iv_size() {
+        return Err(invalid());
+    }
+
+    let header_hash = reader.read_bytes(32).ok_or_else(invalid)?;
+    if Sha256::digest(header).as_slice() != header_hash {
+        return Err(invalid());
+    }
+
+    let transformed = transform_key(&composite_key(password), &kdf)?;
+    let hmac_key = hmac_key(&master_seed, &transformed);
+
+    // Header MAC depends on the key, so this is where a wrong password is noticed
+    let header_hmac = reader.read_bytes(32).ok_or_else(invalid)?;
+    block_mac(&hmac_key, u64::MAX)
+        .chain_update(header)
+        .verify_slice(header_hmac)
+        .map_err(|_| String::from("Password is not correct or the database was modified."))?;
+
+    let mut encrypted = Vec::new();
+    for index in 0.. {
+        let mac = reader.read_bytes(32).ok_or_else(invalid)?;
+        let size = reader.read_u32().ok_or_else(invalid)?;
+        let block = reader.read_bytes(size as usize).ok_or_else(invalid)?;
+
+        block_mac(&hmac_key, index)
+            .chain_update(index.to_le_bytes())
+            .chain_update(size.to_le_bytes())
+            .chain_update(block)
+            .verify_slice(mac)
+            .map_err(|_| String::from("KeePass database was modified."))?;
+
+        if block.is_empty() {
+            break;
+        }
+        encrypted.extend_from_slice(block);
+    }
+
+    let key = encryption_key(&master_seed, &transformed);
+    let mut payload = match cipher {
+        Cipher::Aes256 => Aes256CbcDec::new(key.as_slice().into(), iv.as_slice().into())
+            .decrypt_padded_vec_mut::<Pkcs7>(&encrypted)
+            .map_err(|_| invalid())?,
+        Cipher::ChaCha20 => {
+            let mut stream = ChaCha20::new(key.as_slice().into(), iv.as_slice().into());
+            stream.apply_keystream(&mut encrypted);
+            encrypted
+        }
+    };
+
+    if compressed {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(payload.as_slice())
+            .read_to_end(&mut decompressed)
+            .map_err(|_| invalid())?;
+        payload = decompressed;
+    }
+
+    let mut reader = ByteReader::new(&payload);
+    let mut stream_id = 0;
+    let mut stream_key = Vec::new();
+
+    loop {
+        let id = reader.read_u8().ok_or_else(invalid)?;
+        let size = reader.read_u32().ok_or_else(invalid)? as usize;
+        let value = reader.read_bytes(size).ok_or_else(invalid)?;
+
+        match id {
+            END_OF_HEADER => break,
+            INNER_STREAM_ID => {
+                stream_id = ByteReader::new(value).read_u32().ok_or_else(invalid)?;
+            }
+            INNER_STREAM_KEY => stream_key = value.to_vec(),
+            _ => {}
+        }
+    }
+
+    if stream_id != CHACHA20_STREAM {
+        return Err(String::from(
+            "Protection of the database values is not supported.",
+        ));
+    }
+
+    let xml = std::str::from_utf8(reader.read_rest()).map_err(|_| invalid())?;
+    let mut stream = inner_stream(&stream_key);
+
+    keepass::parse_protected(xml, &mut |value| stream.apply_keystream(value))
+}
+
+// Writes entries as KeePass database in KDBX 4 format. Folders become groups.
+pub fn write(
+    entries: &[NewEntry],
+    password: &str,
+    cipher: Cipher,
+    settings: KdfSettings,
+) -> Result<Vec<u8>, String> {
+    let master_seed = random_bytes(32);
+    let iv = random_bytes(cipher.iv_size());
+    let kdf = Kdf::Argon2 {
+        algorithm: Algorithm::Argon2d,
+        salt: random_bytes(32),
+        memory: settings.memory,
+        iterations: settings.iterations,
+        parallelism: settings.parallelism,
+        version: Version::V0x13,
+    };
+
+    let mut header = Vec::new();
+    header.extend(SIGNATURE_1.to_le_bytes());
+    header.extend(SIGNATURE_2.to_le_bytes());
+    header.extend(VERSION_MINOR.to_le_bytes());
+    header.extend(VERSION_MAJOR.to_le_bytes());
+    write_field(&mut header, CIPHER_ID, &cipher.uuid());
+    write_field(&mut header, COMPRESSION_FLAGS, &1u32.to_le_bytes());
+    write_field(&mut header, MASTER_SEED, &master_seed);
+    write_field(&mut header, ENCRYPTION_IV, &iv);
+    write_field(&mut header, KDF_PARAMETERS, &write_kdf(&kdf));
+    write_field(&mut header, END_OF_HEADER, b"\r\n\r\n");
+
+    let transformed = transform_key(&composite_key(password), &kdf)?;
+    let hmac_key = hmac_key(&master_seed, &transformed);
+
+    let stream_key = random_bytes(64);
+    let mut payload = Vec::new();
+    write_field(
+        &mut payload,
+        INNER_STREAM_ID,
+        &CHACHA20_STREAM.to_le_bytes(),
+    );
+    write_field(&mut payload, INNER_STREAM_KEY, &stream_key);
+    write_field(&mut payload, END_OF_HEADER, &[]);
+
+    let mut stream = inner_stream(&stream_key);
+    payload.extend(to_xml(entries, &mut |value| stream.apply_keystream(value)).into_bytes());
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&payload).map_err(|err| err.to_string())?;
+    let mut payload = encoder.finish().map_err(|err| err.to_string())?;
+
+    let key = encryption_key(&master_seed, &transformed);
+    let encrypted = match cipher {
+        Cipher::Aes256 => Aes256CbcEnc::new(key.as_slice().into(), iv.as_slice().into())
+            .encrypt_padded_vec_mut::<Pkcs7>(&payload),
+        Cipher::ChaCha20 => {
+            let mut stream = ChaCha20::new(key.as_slice().into(), iv.as_slice().into());
+            stream.apply_keystream(&mut payload);
+            payload
+        }
+    };
+
+    let mut data = header.clone();
+    data.extend(Sha256::digest(&header));
+    let header_mac = block_mac(&hmac_key, u64::MAX).chain_update(&header);
+    data.extend(header_mac.finalize().into_bytes());
+
+    // The last block is empty and marks the end of the data
+    let blocks = encrypted.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+    for (index, block) in blocks.enumerate() {
+        let size = (block.len() as u32).to_le_bytes();
+        let mac = block_mac(&hmac_key, index as u64)
+            .chain_update((index as u64).to_le_bytes())
+            .chain_update(size)
+            .chain_update(block);
+
+        data.extend(mac.finalize().into_bytes());
+        data.extend(size);
+        data.extend_from_slice(block);
+    }
+
+    Ok(data)
+}
+
+// Password is the only part of the composite key, key files aren't supported
+fn composite_key(password: &str) -> [u8; 32] {
+    let password_hash = Sha256::digest(password.as_bytes());
+    Sha256::digest(password_hash).into()
+}
+
+fn transform_key(composite: &[u8; 32], kdf: &Kdf) -> Result<[u8; 32], String> {
+    let mut transformed = [0u8; 32];
+
+    match kdf {
+        Kdf::Argon2 {
+            algorithm,
+            salt,
+            memory,
+            iterations,
+            parallelism,
+            version,
+        } => {
+            let params = Params::new(*memory, *iterations, *parallelism, Some(32))
+                .map_err(|err| format!("Invalid key derivation parameters: {}", err))?;
+
+            Argon2::new(*algorithm, *version, params)
+                .hash_password_into(composite, salt, &mut transformed)
+                .map_err(|err| format!("Key derivation failed: {}", err))?;
+        }
+        Kdf::Aes { seed, rounds } => {
+            let cipher = aes::Aes256::new_from_slice(seed)
+                .map_err(|_| String::from("Invalid key derivation parameters."))?;
+
+            transformed.copy_from_slice(composite);
+            for _ in 0..*rounds {
+                for block in transformed.chunks_mut(16) {
+                    cipher.encrypt_block(block.into());
+                }
+            }
+            transformed = Sha256::digest(transformed).into();
+        }
+    }
+
+    Ok(transformed)
+}
+
+fn encryption_key(master_seed: &[u8], transformed: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .finalize()
+        .to_vec()
+}
+
+fn hmac_key(master_seed: &[u8], transformed: &[u8]) -> Vec<u8> {
+    Sha512::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .chain_update([1])
+        .finalize()
+        .to_vec()
+}
+
+// Every block has its own MAC key, header uses the block index u64::MAX
+fn block_mac(hmac_key: &[u8], index: u64) -> HmacSha256 {
+    let block_key = Sha512::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(hmac_key)
+        .finalize();
+
+    <HmacSha256 as Mac>::new_from_slice(&block_key).unwrap()
+}
+
+// Keystream that protects passwords inside XML, shared by all values in document order
+fn inner_stream(stream_key: &[u8]) -> ChaCha20 {
+    let hash = Sha512::digest(stream_key);
+    ChaCha20::new(hash[..32].into(), hash[32..44].into())
+}
+
+fn write_field(buffer: &mut Vec<u8>, id: u8, value: &[u8]) {
+    buffer.push(id);
+    buffer.extend((value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value);
+}
+
+// KDF parameters are stored as KeePass VariantDictionary
+fn read_kdf(data: &[u8]) -> Result<Kdf, String> {
+    let invalid = || String::from("Invalid key derivation parameters.");
+    let mut reader = ByteReader::new(data);
+    let mut values: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    let version = reader.read_u16().ok_or_else(invalid)?;
+    if version >> 8 != 1 {
+        return Err(invalid());
+    }
+
+    loop {
+        let kind = reader.read_u8().ok_or_else(invalid)?;
+        if kind == 0 {
+            break;
+        }
+
+        let name_size = reader.read_u32().ok_or_else(invalid)? as usize;
+        let name = reader.read_bytes(name_size).ok_or_else(invalid)?;
+        let value_size = reader.read_u32().ok_or_else(invalid)? as usize;
+        let value = reader.read_bytes(value_size).ok_or_else(invalid)?;
+
+        values.insert(String::from_utf8_lossy(name).to_string(), value.to_vec());
+    }
+
+    let bytes = |name: &str| values.get(name).cloned().ok_or_else(invalid);
+    let number = |name: &str| -> Result<u64, String> {
+        let value = bytes(name)?;
+        let mut reader = ByteReader::new(&value);
+        match value.len() {
+            4 => reader.read_u32().map(u64::from),
+            8 => reader.read_u64(),
+            _ => None,
+        }
+        .ok_or_else(invalid)
+    };
+
+    let uuid = bytes("$UUID")?;
+    if uuid == AES_KDF_UUID {
+        return Ok(Kdf::Aes {
+            seed: bytes("S")?,
+            rounds: number("R")?,
+        });
+    }
+
+    let algorithm = match uuid {
+        uuid if uuid == ARGON2D_UUID => Algorithm::Argon2d,
+        uuid if uuid == ARGON2ID_UUID => Algorithm::Argon2id,
+        _ => {
+            return Err(String::from(
+                "Key derivation of the database is not supported.",
+            ))
+        }
+    };
+
+    let version = match number("V")? {
+        0x10 => Version::V0x10,
+        0x13 => Version::V0x13,
+        _ => return Err(invalid()),
+    };
+
+    Ok(Kdf::Argon2 {
+        algorithm,
+        salt: bytes("S")?,
+        memory: u32::try_from(number("M")? / 1024).map_err(|_| invalid())?,
+        iterations: u32::try_from(number("I")?).map_err(|_| invalid())?,
+        parallelism: u32::try_from(number("P")?).map_err(|_| invalid())?,
+        version,
+    })
+}
+
+fn write_kdf(kdf: &Kdf) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend(0x0100u16.to_le_bytes());
+
+    let mut item = |kind: u8, name: &str, value: &[u8]| {
+        data.push(kind);
+        data.extend((name.len() as u32).to_le_bytes());
+        data.extend(name.as_bytes());
+        data.extend((value.len() as u32).to_le_bytes());
+        data.extend(value);
+    };
+
+    // Value types: 0x04 UInt32, 0x05 UInt64, 0x42 byte array
+    match kdf {
+        Kdf::Argon2 {
+            algorithm,
+            salt,
+            memory,
+            iterations,
+            parallelism,
+            version,
+        } => {
+            let uuid = match algorithm {
+                Algorithm::Argon2id => ARGON2ID_UUID,
+                _ => ARGON2D_UUID,
+            };
+            item(0x42, "$UUID", &uuid);
+            item(0x42, "S", salt);
+            item(0x05, "M", &(*memory as u64 * 1024).to_le_bytes());
+            item(0x05, "I", &(*iterations as u64).to_le_bytes());
+            item(0x04, "P", &parallelism.to_le_bytes());
+            item(0x04, "V", &(*version as u32).to_le_bytes());
+        }
+        Kdf::Aes { seed, rounds } => {
+            item(0x42, "$UUID", &AES_KDF_UUID);
+            item(0x42, "S", seed);
+            item(0x05, "R", &rounds.to_le_bytes());
+        }
+    }
+
+    data.push(0);
+    data
+}
+
+// XML document of the database. Passwords are protected with the inner stream.
+fn to_xml(entries: &[NewEntry], protect: &mut dyn FnMut(&mut [u8])) -> String {
+    let mut folders: BTreeMap<&str, Vec<&NewEntry>> = BTreeMap::new();
+    for entry in entries {
+        folders.entry(&entry.folder).or_default().push(entry);
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n");
+    xml.push_str("<KeePassFile>\n<Meta>\n<Generator>Dome</Generator>\n");
+    xml.push_str("<DatabaseName>Dome</DatabaseName>\n");
+    xml.push_str("<MemoryProtection><ProtectPassword>True</ProtectPassword></MemoryProtection>\n");
+    xml.push_str("<RecycleBinEnabled>False</RecycleBinEnabled>\n</Meta>\n<Root>\n");
+    write_group(&mut xml, "Dome", "", &folders, protect);
+    xml.push_str("<DeletedObjects/>\n</Root>\n</KeePassFile>\n");
+    xml
+}
+
+fn write_group(
+    xml: &mut String,
+    name: &str,
+    path: &str,
+    folders: &BTreeMap<&str, Vec<&NewEntry>>,
+    protect: &mut dyn FnMut(&mut [u8]),
+) {
+    xml.push_str(&format!(
+        "<Group>\n<UUID>{}</UUID>\n<Name>{}</Name>\n{}<IsExpanded>True</IsExpanded>\n",
+        random_uuid(),
+        escape(name),
+        times()
+    ));
+
+    for entry in folders.get(path).into_iter().flatten() {
+        let mut password = entry.password.clone().into_bytes();
+        protect(&mut password);
+
+        xml.push_str(&format!("<Entry>\n<UUID>{}</UUID>\n", random_uuid()));
+        xml.push_str(&format!("<Tags>{}</Tags>\n", escape(&entry.tags.join(";"))));
+        xml.push_str(&times());
+        xml.push_str(&string_field("Title", &escape(&entry.domain), false));
+        xml.push_str(&string_field("UserName", &escape(&entry.username), false));
+        xml.push_str(&string_field(
+            "Password",
+            &general_purpose::STANDARD.encode(password),
+            true,
+        ));
+        xml.push_str(&string_field("URL", &escape(&entry.url), false));
+        xml.push_str("</Entry>\n");
+    }
+
+    // Direct subfolders of the path, "infra/db" is nested into "infra"
+    let mut children: Vec<&str> = Vec::new();
+    for folder in folders.keys().filter(|f| !f.is_empty()) {
+        let relative = match path {
+            "" => *folder,
+            path => match folder.strip_prefix(path).and_then(|f| f.strip_prefix('/')) {
+                Some(relative) => relative,
+                None => continue,
+            },
+        };
+
+        let child = relative.split('/').next().unwrap();
+        if !children.contains(&child) {
+            children.push(child);
+        }
+    }
+
+    for child in children {
+        let child_path = match path {
+            "" => String::from(child),
+            path => format!("{}/{}", path, child),
+        };
+        write_group(xml, child, &child_path, folders, protect);
+    }
+
+    xml.push_str("</Group>\n");
+}
+
+fn string_field(key: &str, value: &str, protected: bool) -> String {
+    let attribute = if protected { " Protected=\"True\"" } else { "" };
+    format!(
+        "<String><Key>{}</Key><Value{}>{}</Value></String>\n",
+        key, attribute, value
+    )
+}
+
+fn times() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let time = general_purpose::STANDARD.encode((now + EPOCH_OFFSET).to_le_bytes());
+
+    format!(
+        "<Times><CreationTime>{0}</CreationTime><LastModificationTime>{0}</LastModificationTime>\
+         <LastAccessTime>{0}</LastAccessTime><ExpiryTime>{0}</ExpiryTime><Expires>False</Expires>\
+         <UsageCount>0</UsageCount><LocationChanged>{0}</LocationChanged></Times>\n",
+        time
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn random_uuid() -> String {
+    general_purpose::STANDARD.encode(random_bytes(16))
+}
+
+fn random_bytes(size: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod test;
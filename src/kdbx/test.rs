@@ -0,0 +1,123 @@
+use super::*;
+
+const PASSWORD: &str = "kdbx-pa55word";
+
+// Cheap key derivation keeps tests fast
+const SETTINGS: KdfSettings = KdfSettings {
+    memory: 64,
+    iterations: 1,
+    parallelism: 1,
+};
+
+fn prepare_entries() -> Vec<NewEntry> {
+    vec![
+        NewEntry {
+            domain: String::from("gmail"),
+            username: String::from("john.doe@gmail.com"),
+            password: String::from("gm41l <pa55> & \"quotes\""),
+            url: String::from("https://mail.google.com"),
+            tags: vec![String::from("mail"), String::from("personal")],
+            folder: String::new(),
+        },
+        NewEntry {
+            domain: String::from("postgres"),
+            username: String::from("postgres"),
+            password: String::from("pg-s3cret"),
+            url: String::new(),
+            tags: Vec::new(),
+            folder: String::from("work/infra"),
+        },
+        NewEntry {
+            domain: String::from("jenkins"),
+            username: String::from("admin"),
+            password: String::from("j3nk1ns"),
+            url: String::new(),
+            tags: vec![String::from("ci")],
+            folder: String::from("work"),
+        },
+    ]
+}
+
+fn assert_same(entries: &[NewEntry], read: &[NewEntry]) {
+    assert_eq!(entries.len(), read.len());
+
+    for entry in entries {
+        let read = read.iter().find(|e| e.domain == entry.domain).unwrap();
+        assert_eq!(entry.username, read.username);
+        assert_eq!(entry.password, read.password);
+        assert_eq!(entry.url, read.url);
+        assert_eq!(entry.tags, read.tags);
+        assert_eq!(entry.folder, read.folder);
+    }
+}
+
+#[test]
+fn test_aes_round_trip() {
+    let entries = prepare_entries();
+    let data = write(&entries, PASSWORD, Cipher::Aes256, SETTINGS).unwrap();
+
+    assert_same(&entries, &read(&data, PASSWORD).unwrap());
+}
+
+#[test]
+fn test_chacha20_round_trip() {
+    let entries = prepare_entries();
+    let data = write(&entries, PASSWORD, Cipher::ChaCha20, SETTINGS).unwrap();
+
+    assert_same(&entries, &read(&data, PASSWORD).unwrap());
+}
+
+#[test]
+fn test_passwords_are_protected() {
+    let entries = prepare_entries();
+    let mut stream = inner_stream(&[7; 64]);
+    let xml = to_xml(&entries, &mut |value| stream.apply_keystream(value));
+
+    assert!(!xml.contains("pg-s3cret"));
+    assert!(xml.contains("<Value Protected=\"True\">"));
+
+    let mut stream = inner_stream(&[7; 64]);
+    let read = keepass::parse_protected(&xml, &mut |value| stream.apply_keystream(value));
+    assert_same(&entries, &read.unwrap());
+}
+
+#[test]
+fn test_wrong_password() {
+    let data = write(&prepare_entries(), PASSWORD, Cipher::Aes256, SETTINGS).unwrap();
+
+    assert!(read(&data, "wrong").is_err());
+}
+
+#[test]
+fn test_modified_database() {
+    let mut data = write(&prepare_entries(), PASSWORD, Cipher::Aes256, SETTINGS).unwrap();
+    let last = data.len() - 40;
+    data[last] ^= 1;
+
+    assert_eq!(
+        read(&data, PASSWORD).err().unwrap(),
+        "KeePass database was modified."
+    );
+}
+
+#[test]
+fn test_not_kdbx() {
+    assert!(read(b"DOMEEXP", PASSWORD).is_err());
+    assert!(read(&[], PASSWORD).is_err());
+}
+
+#[test]
+fn test_aes_kdf_parameters() {
+    let kdf = Kdf::Aes {
+        seed: vec![3; 32],
+        rounds: 10,
+    };
+
+    match read_kdf(&write_kdf(&kdf)).unwrap() {
+        Kdf::Aes { seed, rounds } => {
+            assert_eq!(seed, vec![3; 32]);
+            assert_eq!(rounds, 10);
+        }
+        _ => panic!("Expected AES-KDF"),
+    }
+}
@@ -0,0 +1,95 @@
+use crate::storage;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Key file is a second secret next to the master password. Any file can be used, only the
+// hash of its contents is mixed into the password before the key is derived, so the vault
+// opens only with both.
+pub const GENERATED_KEY_SIZE: usize = 32;
+
+// Separates the typed password from the key file hash. Master passwords that contain it
+// are refused, see check_password.
+const SEPARATOR: char = '\u{1f}';
+
+// Path of the key file the vault requires, if any
+pub fn configured() -> Option<PathBuf> {
+    let text = fs::read_to_string(storage::key_file_setting_path()).ok()?;
+    let path = text.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+// Remembers the key file. Path is stored absolute so dome works from any directory.
+pub fn set(path: &Path) -> Result<(), String> {
+    let path = fs::canonicalize(path)
+        .map_err(|_| format!("Key file {} doesn't exist.", path.display()))?;
+    storage::replace_file(
+        &storage::key_file_setting_path(),
+        path.to_string_lossy().as_bytes(),
+    )
+}
+
+pub fn clear() -> Result<(), String> {
+    match fs::remove_file(storage::key_file_setting_path()) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("Key file setting can't be removed: {}", err)),
+    }
+}
+
+// Writes a new random 256-bit key as hex. Existing files are never overwritten.
+pub fn generate(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("File {} already exists.", path.display()));
+    }
+
+    let mut key = [0u8; GENERATED_KEY_SIZE];
+    rand::thread_rng().fill(&mut key[..]);
+    let text: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+
+    fs::write(path, format!("{}\n", text))
+        .map_err(|err| format!("Key file {} can't be written: {}", path.display(), err))
+}
+
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|_| format!("Key file {} can't be read.", path.display()))?;
+
+    if data.is_empty() {
+        return Err(format!("Key file {} is empty.", path.display()));
+    }
+
+    Ok(data)
+}
+
+// Password that is passed to the key derivation when a key file is used
+pub fn composite(password: &str, key_file: &[u8]) -> String {
+    let hash: String = Sha256::digest(key_file)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}{}{}", password, SEPARATOR, hash)
+}
+
+// Typed password followed by the separator could pass for a composite one
+pub fn check_password(password: &str) -> Result<(), String> {
+    match password.contains(SEPARATOR) {
+        true => Err(String::from(
+            "Master password can't contain the unit separator character.",
+        )),
+        false => Ok(()),
+    }
+}
+
+// Combines the typed password with the configured key file, if there is one
+pub fn unlock(password: &str) -> Result<String, String> {
+    check_password(password)?;
+
+    match configured() {
+        Some(path) => Ok(composite(password, &read(&path)?)),
+        None => Ok(password.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test;
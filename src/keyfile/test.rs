@@ -0,0 +1,89 @@
+use super::*;
+use crate::command::{add_entry, get_entry, unlock_password, NewEntry};
+
+#[test]
+fn test_composite() {
+    let first = composite("master", b"key one");
+
+    assert_eq!(first, composite("master", b"key one"));
+    assert_ne!(first, composite("master", b"key two"));
+    assert_ne!(first, composite("other", b"key one"));
+    assert_ne!(first, "master");
+}
+
+#[test]
+fn test_generate() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dome.key");
+
+    generate(&path).unwrap();
+    let key = fs::read_to_string(&path).unwrap();
+    assert_eq!(key.trim().len(), GENERATED_KEY_SIZE * 2);
+    assert!(key.trim().chars().all(|c| c.is_ascii_hexdigit()));
+
+    // Existing key is kept
+    assert!(generate(&path).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), key);
+}
+
+#[test]
+fn test_unlock_requires_key_file() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(&dir.path().join("dome"));
+    let path = dir.path().join("dome.key");
+    generate(&path).unwrap();
+
+    assert_eq!(unlock("master").unwrap(), "master");
+
+    set(&path).unwrap();
+    let master_password = unlock("master").unwrap();
+    assert_eq!(configured().unwrap(), fs::canonicalize(&path).unwrap());
+
+    let entry = NewEntry {
+        domain: String::from("gmail"),
+        username: String::from("john"),
+        password: String::from("gm41l"),
+        url: String::new(),
+        tags: Vec::new(),
+        folder: String::new(),
+    };
+    add_entry(&entry, &master_password).unwrap();
+
+    // Password alone doesn't open the entry
+    assert!(get_entry("gmail", "master").is_err());
+    assert_eq!(
+        get_entry("gmail", &unlock("master").unwrap()).unwrap().1,
+        "gm41l"
+    );
+
+    // Neither does a different key file
+    fs::write(&path, "changed").unwrap();
+    assert!(get_entry("gmail", &unlock("master").unwrap()).is_err());
+
+    // Missing key file stops the unlock instead of falling back to the password alone
+    fs::remove_file(&path).unwrap();
+    assert!(unlock("master").is_err());
+    assert!(unlock_password(String::from("master")).is_err());
+
+    clear().unwrap();
+    assert_eq!(unlock("master").unwrap(), "master");
+    assert!(configured().is_none());
+}
+
+#[test]
+fn test_empty_key_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("empty.key");
+    fs::write(&path, "").unwrap();
+
+    assert!(read(&path).is_err());
+}
+
+#[test]
+fn test_password_with_separator() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    assert!(check_password("master").is_ok());
+    assert!(unlock(&format!("master{}", SEPARATOR)).is_err());
+}
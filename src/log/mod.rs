@@ -0,0 +1,340 @@
+use crate::crypto::{self, NONCE_SIZE, SALT_SIZE};
+use crate::entry::ByteReader;
+use crate::storage;
+use crate::time;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::ErrorKind;
+
+// Append-only log of access to the vault. Every line carries an HMAC of its contents and of
+// the previous line's MAC, so lines can't be changed, reordered or removed without breaking
+// the chain. The key moves forward after every line and the old key is forgotten: whoever
+// gets hold of the files later can't forge earlier lines. Only the first key is kept,
+// encrypted with the master password, to verify the whole log.
+type HmacSha256 = Hmac<Sha256>;
+
+const KEY_MAGIC: &[u8; 8] = b"DOMELOGK";
+const KEY_VERSION: u8 = 1;
+const KEY_SIZE: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Add,
+    Get,
+    Edit,
+    Remove,
+    Export,
+    Unlock,
+    UnlockFailed,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Add => "add",
+            Event::Get => "get",
+            Event::Edit => "edit",
+            Event::Remove => "remove",
+            Event::Export => "export",
+            Event::Unlock => "unlock",
+            Event::UnlockFailed => "unlock-failed",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub time: u64,
+    pub event: String,
+    pub domain: String,
+    mac: [u8; 32],
+}
+
+// Key file: the first key encrypted with the master password, then the current key and
+// the number of lines written with the keys before it
+struct KeyState {
+    first: Vec<u8>,
+    current: [u8; KEY_SIZE],
+    count: u64,
+}
+
+impl Line {
+    fn text(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.time,
+            self.event,
+            self.domain,
+            hex(&self.mac)
+        )
+    }
+
+    fn parse(text: &str) -> Option<Line> {
+        let mut parts = text.split('\t');
+        let line = Line {
+            time: parts.next()?.parse().ok()?,
+            event: parts.next()?.to_string(),
+            domain: parts.next()?.to_string(),
+            mac: unhex(parts.next()?)?.try_into().ok()?,
+        };
+        parts.next().is_none().then_some(line)
+    }
+
+    fn expected_mac(&self, key: &[u8; KEY_SIZE], previous: &[u8; 32]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(key).unwrap();
+        mac.update(previous);
+        mac.update(&self.time.to_le_bytes());
+        mac.update(self.event.as_bytes());
+        mac.update(&[0]);
+        mac.update(self.domain.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+}
+
+// Key of the next line, the previous key can't be computed back from it
+fn next_key(key: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dome-log-key");
+    hasher.update(key);
+    hasher.finalize().into()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Tabs and line breaks would split the line
+fn clean(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+impl KeyState {
+    fn create(master_password: &str) -> KeyState {
+        let first: [u8; KEY_SIZE] = rand::random();
+        KeyState {
+            first: encrypt_key(&first, master_password),
+            current: first,
+            count: 0,
+        }
+    }
+
+    fn read() -> Result<Option<KeyState>, String> {
+        let data = match fs::read(storage::log_key_file_path()) {
+            Ok(data) => data,
+            Err(_) => return Ok(None),
+        };
+
+        let damaged = || String::from("Key file of the log is damaged.");
+        if !data.starts_with(KEY_MAGIC) {
+            return Err(damaged());
+        }
+
+        let mut reader = ByteReader::new(&data[KEY_MAGIC.len()..]);
+        if reader.read_u8() != Some(KEY_VERSION) {
+            return Err(damaged());
+        }
+
+        let len = reader.read_u16().ok_or_else(damaged)? as usize;
+        let first = reader.read_bytes(len).ok_or_else(damaged)?.to_vec();
+        let current = reader.read_bytes(KEY_SIZE).ok_or_else(damaged)?;
+        let count = reader.read_u64().ok_or_else(damaged)?;
+
+        Ok(Some(KeyState {
+            first,
+            current: current.try_into().unwrap(),
+            count,
+        }))
+    }
+
+    fn write(&self) -> Result<(), String> {
+        storage::replace_file(&storage::log_key_file_path(), &self.encode())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = KEY_MAGIC.to_vec();
+        data.push(KEY_VERSION);
+        data.extend((self.first.len() as u16).to_le_bytes());
+        data.extend(&self.first);
+        data.extend(self.current);
+        data.extend(self.count.to_le_bytes());
+        data
+    }
+}
+
+fn encrypt_key(key: &[u8; KEY_SIZE], master_password: &str) -> Vec<u8> {
+    let salt = crypto::generate_salt();
+    let (cipher, nonce) = crypto::encrypt_bytes(&crypto::derive_key(master_password, &salt), key);
+
+    let mut data = salt;
+    data.extend(nonce);
+    data.extend(cipher);
+    data
+}
+
+fn decrypt_key(data: &[u8], master_password: &str) -> Result<[u8; KEY_SIZE], String> {
+    let damaged = || String::from("Key file of the log is damaged.");
+    let mut reader = ByteReader::new(data);
+    let salt = reader.read_bytes(SALT_SIZE).ok_or_else(damaged)?;
+    let nonce = reader.read_bytes(NONCE_SIZE).ok_or_else(damaged)?;
+
+    let key = crypto::derive_key(master_password, salt);
+    crypto::decrypt_bytes(&key, nonce, reader.read_rest())
+        .map_err(|_| String::from("Master password doesn't open the key of the log."))?
+        .try_into()
+        .map_err(|_| damaged())
+}
+
+fn last_mac() -> Result<[u8; 32], String> {
+    match read()?.last() {
+        Some(line) => Ok(line.mac),
+        None => Ok([0; 32]),
+    }
+}
+
+// Appends the event. The log is started on the first event, with the master password that
+// unlocked it. Number of lines is sealed in the index, so the log can't be deleted or cut
+// back to fewer lines unnoticed. The seal reuses the key the command already derived. When
+// it can't be written, the log is only ahead of the sealed number, which verify accepts.
+pub fn record(event: Event, domain: &str, master_password: &str) -> Result<(), String> {
+    let count = KeyState::read()
+        .and_then(|state| {
+            let state = state.unwrap_or_else(|| KeyState::create(master_password));
+            append(state, event, domain)
+        })
+        .map_err(|err| format!("Event was not logged: {}", err))?;
+
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list()?;
+            index_set.logged_events = count;
+            index_set.seal(master_password);
+            backend.write_index(&index_set)
+        })
+        .map_err(|err| format!("Event was logged, but not sealed in the index: {}", err))
+}
+
+// Failed attempts can't start the log, because the password that came with them is wrong.
+// They can't update the sealed number of lines either.
+pub fn record_failure(event: Event, domain: &str) -> Result<(), String> {
+    match KeyState::read()? {
+        Some(state) => append(state, event, domain).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// Returns the number of lines in the log. The line and the next key are written as one
+// change, so an interrupted append can't leave the key behind or ahead of the lines.
+fn append(mut state: KeyState, event: Event, domain: &str) -> Result<u64, String> {
+    let mut line = Line {
+        time: time::now(),
+        event: event.name().to_string(),
+        domain: clean(domain),
+        mac: [0; 32],
+    };
+    line.mac = line.expected_mac(&state.current, &last_mac()?);
+
+    let mut log = match fs::read(storage::log_file_path()) {
+        Ok(log) => log,
+        Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(format!("Log can't be opened: {}", err)),
+    };
+    log.extend(line.text().as_bytes());
+
+    state.current = next_key(&state.current);
+    state.count += 1;
+    storage::replace_files(&[
+        (storage::log_file_path(), log),
+        (storage::log_key_file_path(), state.encode()),
+    ])?;
+    Ok(state.count)
+}
+
+pub fn read() -> Result<Vec<Line>, String> {
+    let text = match fs::read_to_string(storage::log_file_path()) {
+        Ok(text) => text,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    text.lines()
+        .enumerate()
+        .map(|(index, text)| {
+            Line::parse(text).ok_or_else(|| format!("Line {} of the log is damaged.", index + 1))
+        })
+        .collect()
+}
+
+// Checks the whole chain from the first key, and that the log has at least as many lines as
+// the index was sealed with. The index has to be verified first. Returns the number of lines.
+pub fn verify(master_password: &str) -> Result<usize, String> {
+    let sealed = storage::open().list()?.logged_events;
+
+    let state = match KeyState::read()? {
+        Some(state) => state,
+        None if sealed > 0 => {
+            return Err(format!(
+                "Log was deleted, the vault recorded {} events.",
+                sealed
+            ))
+        }
+        None if read()?.is_empty() => return Ok(0),
+        None => return Err(String::from("Key of the log is missing.")),
+    };
+
+    let mut key = decrypt_key(&state.first, master_password)?;
+    let mut previous = [0; 32];
+    let lines = read()?;
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.mac != line.expected_mac(&key, &previous) {
+            return Err(format!(
+                "Line {} of the log was changed, moved or forged.",
+                index + 1
+            ));
+        }
+        previous = line.mac;
+        key = next_key(&key);
+    }
+
+    if (lines.len() as u64) < state.count {
+        return Err(format!(
+            "{} lines were removed from the end of the log.",
+            state.count - lines.len() as u64
+        ));
+    }
+
+    if lines.len() as u64 > state.count || key != state.current {
+        return Err(String::from("Key of the log doesn't match its lines."));
+    }
+
+    // Log and its key rolled back together to an older copy
+    if (lines.len() as u64) < sealed {
+        return Err(format!(
+            "{} lines were removed from the end of the log.",
+            sealed - lines.len() as u64
+        ));
+    }
+
+    Ok(lines.len())
+}
+
+// First key follows the master password when it changes
+pub fn reencrypt_key(current: &str, new: &str) -> Result<(), String> {
+    let Some(mut state) = KeyState::read()? else {
+        return Ok(());
+    };
+
+    let first = decrypt_key(&state.first, current)?;
+    state.first = encrypt_key(&first, new);
+    state.write()
+}
+
+#[cfg(test)]
+mod test;
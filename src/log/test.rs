@@ -0,0 +1,140 @@
+use super::*;
+use std::path::PathBuf;
+
+fn write_lines(lines: &[&str]) {
+    fs::write(storage::log_file_path(), lines.join("\n") + "\n").unwrap();
+}
+
+fn log_with_events() -> String {
+    record(Event::Add, "gmail", "master").unwrap();
+    record(Event::Get, "gmail", "master").unwrap();
+    record_failure(Event::UnlockFailed, "").unwrap();
+    record(Event::Remove, "gmail", "master").unwrap();
+    fs::read_to_string(storage::log_file_path()).unwrap()
+}
+
+#[test]
+fn test_record_and_verify() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    // Failures don't start the log
+    record_failure(Event::UnlockFailed, "").unwrap();
+    assert!(read().unwrap().is_empty());
+    assert_eq!(verify("master").unwrap(), 0);
+
+    log_with_events();
+    let events: Vec<String> = read().unwrap().into_iter().map(|l| l.event).collect();
+    assert_eq!(events, vec!["add", "get", "unlock-failed", "remove"]);
+
+    assert_eq!(verify("master").unwrap(), 4);
+    assert!(verify("wrong").is_err());
+
+    reencrypt_key("master", "new").unwrap();
+    assert_eq!(verify("new").unwrap(), 4);
+}
+
+#[test]
+fn test_tampering() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    let text = log_with_events();
+    let lines: Vec<&str> = text.lines().collect();
+
+    let changed = lines[1].replace("gmail", "yahoo");
+    write_lines(&[lines[0], &changed, lines[2], lines[3]]);
+    assert!(verify("master").unwrap_err().contains("Line 2"));
+
+    write_lines(&[lines[0], lines[2], lines[1], lines[3]]);
+    assert!(verify("master").is_err());
+
+    write_lines(&[lines[0], lines[2], lines[3]]);
+    assert!(verify("master").is_err());
+
+    write_lines(&lines[..3]);
+    assert!(verify("master").unwrap_err().contains("removed"));
+
+    fs::remove_file(storage::log_file_path()).unwrap();
+    assert!(verify("master").is_err());
+
+    write_lines(&lines);
+    assert_eq!(verify("master").unwrap(), 4);
+}
+
+#[test]
+fn test_deleted_log() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    log_with_events();
+    assert_eq!(storage::open().list().unwrap().logged_events, 4);
+    assert!(storage::open()
+        .list()
+        .unwrap()
+        .verify_seal("master", 0)
+        .is_ok());
+
+    let key = fs::read(storage::log_key_file_path()).unwrap();
+    let text = fs::read_to_string(storage::log_file_path()).unwrap();
+
+    // Log and its key deleted together
+    fs::remove_file(storage::log_file_path()).unwrap();
+    fs::remove_file(storage::log_key_file_path()).unwrap();
+    assert!(verify("master").unwrap_err().contains("deleted"));
+
+    // Or rolled back together to an older copy
+    let lines: Vec<&str> = text.lines().collect();
+    write_lines(&lines);
+    fs::write(storage::log_key_file_path(), &key).unwrap();
+    record(Event::Get, "gmail", "master").unwrap();
+    write_lines(&lines);
+    fs::write(storage::log_key_file_path(), &key).unwrap();
+    assert!(verify("master").unwrap_err().contains("removed"));
+
+    // Failed attempts don't move the sealed number of lines
+    record(Event::Get, "gmail", "master").unwrap();
+    record_failure(Event::UnlockFailed, "").unwrap();
+    assert_eq!(verify("master").unwrap(), 6);
+    assert_eq!(storage::open().list().unwrap().logged_events, 5);
+}
+
+#[test]
+fn test_interrupted_append() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    log_with_events();
+
+    // Crash after the next line and key were written next to the log, before the commit
+    let temp = |path: PathBuf| PathBuf::from(format!("{}.tmp", path.display()));
+    for path in [storage::log_file_path(), storage::log_key_file_path()] {
+        fs::write(temp(path), b"next").unwrap();
+    }
+
+    storage::recover_transaction().unwrap();
+    assert!(!temp(storage::log_file_path()).exists());
+    assert!(!temp(storage::log_key_file_path()).exists());
+    assert_eq!(verify("master").unwrap(), 4);
+}
+
+#[test]
+fn test_forged_line() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    log_with_events();
+
+    // Someone with the files knows only the current key, lines after it don't match the count
+    let mut state = KeyState::read().unwrap().unwrap();
+    state.count -= 1;
+    state.write().unwrap();
+
+    assert!(verify("master").is_err());
+}
+
+#[test]
+fn test_clean() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    record(Event::Add, "bad\tdomain\nname", "master").unwrap();
+    assert_eq!(read().unwrap()[0].domain, "bad domain name");
+    assert_eq!(verify("master").unwrap(), 1);
+}
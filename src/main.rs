@@ -1,73 +1,42 @@
 use std::env;
+mod audit;
+mod backup;
+mod breach;
 mod cli;
 mod command;
+mod config;
 mod crypto;
 mod datastructures;
 mod entry;
 mod errs;
+mod expiry;
+mod export;
+mod import;
+mod kdbx;
+mod keyfile;
+mod log;
+mod recovery;
+mod search;
+mod shell;
 mod storage;
+mod strength;
+mod sync;
+mod team;
+mod throttle;
+mod time;
+mod tui;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    storage::initialize_files();
+    if let Err(err) = storage::initialize_files() {
+        return cli::error(format!("[ERROR] {}", err).as_str());
+    }
 
     match command::parse_args(&args) {
         Err(err) => {
             println!("{}", err);
         }
-        Ok(c) => {
-            if c.command == "--version" {
-                command::command_version()
-            }
-
-            if c.command == "help" {
-                command::command_help()
-            }
-
-            if c.command == "list" {
-                command::command_list()
-            }
-
-            if c.command == "add" {
-                match c.args.first() {
-                    Some(domain) => {
-                        let username = match c.flags.get("-u") {
-                            Some(val) => val,
-                            None => "",
-                        };
-
-                        command::command_add(domain, username)
-                    }
-                    None => println!("Domain not specified."),
-                }
-            }
-
-            if c.command == "remove" {
-                match c.args.first() {
-                    Some(domain) => command::command_remove(domain),
-                    None => println!("Domain not specified."),
-                }
-            }
-
-            if c.command == "get" {
-                match c.args.first() {
-                    Some(domain) => command::command_get(domain),
-                    None => println!("Domain not specified."),
-                }
-            }
-
-            if c.command == "gen" {
-                let len = match c.args.first() {
-                    Some(value) => match value.parse::<u8>() {
-                        Ok(num) => num,
-                        Err(_) => panic!("Length must be a number between 0 and 255"),
-                    },
-                    None => 8,
-                };
-
-                command::command_generate(len);
-            }
-        }
+        Ok(c) => command::execute(&c),
     }
 }
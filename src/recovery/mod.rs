@@ -0,0 +1,271 @@
+use crate::storage;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+// Shamir's secret sharing over GF(256). Every byte of the secret is the constant term of
+// its own random polynomial of degree threshold - 1, and a share is the value of all the
+// polynomials at one point. Any `threshold` shares give back the secret, fewer shares say
+// nothing about it.
+const SHARE_PREFIX: &str = "dome";
+const BLOCK_SIZE: usize = 32;
+const CHECKSUM_SIZE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+    pub threshold: u8,
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+
+impl Share {
+    // "dome-3-1-8f2a-91c0-...-c4d1", the last group is a checksum that catches typos
+    pub fn to_text(&self) -> String {
+        let mut groups = vec![
+            SHARE_PREFIX.to_string(),
+            self.threshold.to_string(),
+            self.x.to_string(),
+        ];
+        groups.extend(self.y.chunks(2).map(hex));
+        groups.push(hex(&self.checksum()));
+        groups.join("-")
+    }
+
+    pub fn parse(text: &str) -> Result<Share, String> {
+        let invalid = || String::from("Share is not valid, check that it was typed correctly.");
+        let text: String = text.split_whitespace().collect::<String>().to_lowercase();
+        let mut groups = text.split('-');
+
+        if groups.next() != Some(SHARE_PREFIX) {
+            return Err(invalid());
+        }
+
+        let threshold = groups
+            .next()
+            .and_then(|g| g.parse().ok())
+            .ok_or_else(invalid)?;
+        let x = groups
+            .next()
+            .and_then(|g| g.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut bytes = groups
+            .map(unhex)
+            .collect::<Option<Vec<Vec<u8>>>>()
+            .ok_or_else(invalid)?
+            .concat();
+
+        if bytes.len() < 2 || x == 0 || threshold == 0 {
+            return Err(invalid());
+        }
+
+        let checksum = bytes.split_off(bytes.len() - 2);
+        let share = Share {
+            threshold,
+            x,
+            y: bytes,
+        };
+
+        if share.checksum() != checksum {
+            return Err(invalid());
+        }
+
+        Ok(share)
+    }
+
+    fn checksum(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update([self.threshold, self.x]);
+        hasher.update(&self.y);
+        hasher.finalize()[..2].to_vec()
+    }
+}
+
+// Splits the master password. Password is padded, so shares don't tell its length, and
+// carries a checksum, so shares that don't belong together are detected.
+pub fn split_password(password: &str, shares: u8, threshold: u8) -> Result<Vec<Share>, String> {
+    let password = password.as_bytes();
+    if password.len() > u16::MAX as usize - CHECKSUM_SIZE {
+        return Err(String::from("Master password is too long to be split."));
+    }
+
+    let mut secret = (password.len() as u16).to_le_bytes().to_vec();
+    secret.extend(password);
+    secret.extend(&Sha256::digest(password)[..CHECKSUM_SIZE]);
+
+    let padded = secret.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    let mut rng = rand::thread_rng();
+    secret.extend((secret.len()..padded).map(|_| rng.gen::<u8>()));
+
+    split(&secret, shares, threshold)
+}
+
+pub fn combine_password(shares: &[Share]) -> Result<String, String> {
+    let mismatch = || String::from("Shares don't belong together or some of them are damaged.");
+    let secret = combine(shares)?;
+
+    let len = u16::from_le_bytes([secret[0], secret.get(1).copied().unwrap_or_default()]) as usize;
+    let password = secret.get(2..2 + len).ok_or_else(mismatch)?;
+    let checksum = secret
+        .get(2 + len..2 + len + CHECKSUM_SIZE)
+        .ok_or_else(mismatch)?;
+
+    if Sha256::digest(password)[..CHECKSUM_SIZE] != *checksum {
+        return Err(mismatch());
+    }
+
+    String::from_utf8(password.to_vec()).map_err(|_| mismatch())
+}
+
+// Number of shares and threshold of the last split. Shares hold the master password itself,
+// so they have to be made again once it changes.
+pub fn issued() -> Option<(u8, u8)> {
+    let text = fs::read_to_string(storage::recovery_file_path()).ok()?;
+    let mut parts = text.split_whitespace();
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+pub fn record_issued(shares: u8, threshold: u8) -> Result<(), String> {
+    let text = format!("{} {}\n", shares, threshold);
+    storage::replace_file(&storage::recovery_file_path(), text.as_bytes())
+}
+
+pub fn forget_issued() -> Result<(), String> {
+    match fs::remove_file(storage::recovery_file_path()) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Recovery setting can't be removed: {}", err))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<Share>, String> {
+    if threshold == 0 || threshold > shares {
+        return Err(String::from(
+            "Threshold must be at least 1 and at most the number of shares.",
+        ));
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut result: Vec<Share> = (1..=shares)
+        .map(|x| Share {
+            threshold,
+            x,
+            y: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    for byte in secret {
+        let mut coefficients = vec![*byte];
+        coefficients.extend((1..threshold).map(|_| rng.gen::<u8>()));
+
+        for share in result.iter_mut() {
+            share.y.push(evaluate(&coefficients, share.x));
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, String> {
+    let first = shares
+        .first()
+        .ok_or_else(|| String::from("No shares were given."))?;
+    let threshold = first.threshold as usize;
+
+    let mut used: Vec<&Share> = Vec::new();
+    for share in shares {
+        if share.threshold != first.threshold || share.y.len() != first.y.len() {
+            return Err(String::from("Shares don't belong together."));
+        }
+        if !used.iter().any(|s| s.x == share.x) {
+            used.push(share);
+        }
+    }
+
+    if used.len() < threshold {
+        return Err(format!(
+            "{} different shares are needed, {} were given.",
+            threshold,
+            used.len()
+        ));
+    }
+
+    Ok(interpolate(&used[..threshold]))
+}
+
+// Value of the polynomial at zero from its values at the points of the shares
+fn interpolate(shares: &[&Share]) -> Vec<u8> {
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.x != share.x)
+                .fold(1, |weight, other| {
+                    mul(weight, div(other.x, other.x ^ share.x))
+                })
+        })
+        .collect();
+
+    (0..shares[0].y.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0, |sum, (share, weight)| sum ^ mul(share.y[i], *weight))
+        })
+        .collect()
+}
+
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |value, coefficient| mul(value, x) ^ coefficient)
+}
+
+// Multiplication in GF(256) with the polynomial of AES
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b > 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+// a^254 is the inverse of a
+fn div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = mul(inverse, b);
+    }
+    mul(a, inverse)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.is_empty() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,109 @@
+use super::*;
+
+// Every subset of the shares, as lists of indexes
+fn subsets(count: usize) -> Vec<Vec<usize>> {
+    (1..1u32 << count)
+        .map(|mask| (0..count).filter(|i| mask & (1 << i) != 0).collect())
+        .collect()
+}
+
+#[test]
+fn test_field() {
+    assert_eq!(mul(0x57, 0x83), 0xc1);
+    assert_eq!(mul(7, 0), 0);
+
+    for a in 1..=255u8 {
+        assert_eq!(mul(div(1, a), a), 1);
+    }
+}
+
+#[test]
+fn test_every_threshold_combination() {
+    let secret = b"correct horse battery staple";
+
+    for count in 1..=5u8 {
+        for threshold in 1..=count {
+            let shares = split(secret, count, threshold).unwrap();
+
+            for subset in subsets(count as usize) {
+                let chosen: Vec<Share> = subset.iter().map(|i| shares[*i].clone()).collect();
+                let result = combine(&chosen);
+
+                if subset.len() >= threshold as usize {
+                    assert_eq!(result.unwrap(), secret, "{} of {}", threshold, count);
+                } else {
+                    assert!(result.is_err());
+
+                    // Even when forced, fewer shares don't give the secret
+                    let refs: Vec<&Share> = chosen.iter().collect();
+                    assert_ne!(interpolate(&refs), secret);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_invalid_parameters() {
+    assert!(split(b"secret", 3, 0).is_err());
+    assert!(split(b"secret", 3, 4).is_err());
+    assert!(combine(&[]).is_err());
+
+    // The same share twice counts once
+    let shares = split(b"secret", 3, 2).unwrap();
+    assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+}
+
+#[test]
+fn test_password() {
+    let shares = split_password("mAste&rPass123word", 5, 3).unwrap();
+
+    assert_eq!(shares[0].y.len(), BLOCK_SIZE);
+    assert_eq!(
+        combine_password(&shares[2..]).unwrap(),
+        "mAste&rPass123word"
+    );
+
+    // Shares of different splits don't combine
+    let other = split_password("mAste&rPass123word", 5, 3).unwrap();
+    let mixed = vec![shares[0].clone(), shares[1].clone(), other[2].clone()];
+    assert!(combine_password(&mixed).is_err());
+}
+
+#[test]
+fn test_text() {
+    let share = split_password("pw", 5, 3).unwrap().remove(1);
+    let text = share.to_text();
+
+    assert!(text.starts_with("dome-3-2-"));
+    assert_eq!(Share::parse(&text).unwrap(), share);
+    assert_eq!(
+        Share::parse(&format!("  {} ", text.to_uppercase())).unwrap(),
+        share
+    );
+
+    // One mistyped character is caught by the checksum
+    let position = text.len() - 7;
+    let wrong = if &text[position..position + 1] == "0" {
+        "1"
+    } else {
+        "0"
+    };
+    let typo = format!("{}{}{}", &text[..position], wrong, &text[position + 1..]);
+    assert!(Share::parse(&typo).is_err());
+    assert!(Share::parse("dome-3-0-abcd").is_err());
+}
+
+#[test]
+fn test_issued_split() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    assert_eq!(issued(), None);
+
+    record_issued(5, 3).unwrap();
+    assert_eq!(issued(), Some((5, 3)));
+
+    forget_issued().unwrap();
+    forget_issued().unwrap();
+    assert_eq!(issued(), None);
+}
@@ -0,0 +1,197 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// Top result is picked automatically only if it beats the second one by this much
+const AMBIGUITY_MARGIN: i64 = 100;
+// Subsequence and typo matches are never picked automatically, user has to choose them
+const MIN_BEST_SCORE: i64 = 500;
+
+const EXACT_SCORE: i64 = 1000;
+const PREFIX_SCORE: i64 = 900;
+const SUBSTRING_SCORE: i64 = 700;
+const CONTAINED_SCORE: i64 = 600;
+const SUBSEQUENCE_SCORE: i64 = 300;
+const TYPO_SCORE: i64 = 250;
+
+// Searchable fields of one vault entry. Key is the domain the entry is saved under.
+pub struct SearchItem {
+    pub key: String,
+    pub username: String,
+    pub url: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SearchResult {
+    pub key: String,
+    pub score: i64,
+    pub field: &'static str,
+}
+
+// Scores every item against the query and returns the matching ones, best first
+pub fn search(query: &str, items: &[SearchItem]) -> Vec<SearchResult> {
+    let query = normalize(query);
+    let mut results = Vec::new();
+
+    for item in items {
+        let mut fields: Vec<(&'static str, &str, i64)> = vec![
+            ("domain", &item.key, 10),
+            ("url", &item.url, 9),
+            ("username", &item.username, 8),
+        ];
+        for tag in &item.tags {
+            fields.push(("tag", tag, 9));
+        }
+
+        let best = fields
+            .iter()
+            .filter_map(|(field, value, weight)| {
+                score_normalized(&query, &normalize(value)).map(|s| (s * weight / 10, *field))
+            })
+            .max_by_key(|(s, _)| *s);
+
+        if let Some((score, field)) = best {
+            results.push(SearchResult {
+                key: item.key.clone(),
+                score,
+                field,
+            });
+        }
+    }
+
+    results.sort_by(|a, b| b.score.cmp(&a.score).then(a.key.cmp(&b.key)));
+    results
+}
+
+// Returns the top result if it's a strong match and clearly better than the rest
+pub fn best_match(results: &[SearchResult]) -> Option<&SearchResult> {
+    match results {
+        [first, ..] if first.score < MIN_BEST_SCORE => None,
+        [] => None,
+        [only] => Some(only),
+        [first, second, ..] if first.score >= second.score + AMBIGUITY_MARGIN => Some(first),
+        _ => None,
+    }
+}
+
+// Lowercases the text and strips diacritics, so "Café" matches "cafe"
+fn normalize(text: &str) -> Vec<char> {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+// Score of how well the candidate matches the query. None if it doesn't match.
+fn score_normalized(query: &[char], candidate: &[char]) -> Option<i64> {
+    if query.is_empty() || candidate.is_empty() {
+        return None;
+    }
+
+    if query == candidate {
+        return Some(EXACT_SCORE);
+    }
+
+    let (q_len, c_len) = (query.len() as i64, candidate.len() as i64);
+
+    if candidate.starts_with(query) {
+        return Some(PREFIX_SCORE - (c_len - q_len).min(100));
+    }
+
+    if let Some(position) = find(candidate, query) {
+        return Some(SUBSTRING_SCORE - (position as i64).min(100));
+    }
+
+    // Query is more specific than what was saved, e.g. "gmail.com" for "gmail"
+    if candidate.len() >= 3 && find(query, candidate).is_some() {
+        return Some(CONTAINED_SCORE - (q_len - c_len).min(100));
+    }
+
+    if let Some(score) = subsequence_score(query, candidate) {
+        return Some(SUBSEQUENCE_SCORE + score);
+    }
+
+    let allowed_typos = (query.len() / 4).max(1);
+    let prefix = &candidate[..candidate.len().min(query.len())];
+    let distance = edit_distance(query, prefix).min(edit_distance(query, candidate));
+    if query.len() >= 3 && distance <= allowed_typos {
+        return Some(TYPO_SCORE - 50 * distance as i64);
+    }
+
+    None
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// Every query character has to appear in the candidate in order. Consecutive matches
+// and matches at the start of a word are rewarded, gaps between matches are penalized.
+fn subsequence_score(query: &[char], candidate: &[char]) -> Option<i64> {
+    let mut score: i64 = 0;
+    let mut query_index = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (index, c) in candidate.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+
+        if *c != query[query_index] {
+            continue;
+        }
+
+        score += 10;
+
+        if index == 0 || !candidate[index - 1].is_alphanumeric() {
+            score += 10;
+        }
+
+        match last_match {
+            Some(last) if last + 1 == index => score += 15,
+            Some(last) => score -= ((index - last - 1) as i64).min(5),
+            None => {}
+        }
+
+        last_match = Some(index);
+        query_index += 1;
+    }
+
+    if query_index < query.len() {
+        return None;
+    }
+
+    Some(score.clamp(0, 199))
+}
+
+// Optimal string alignment distance (Levenshtein with adjacent transpositions)
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test;
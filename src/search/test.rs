@@ -0,0 +1,91 @@
+use super::*;
+
+fn item(key: &str, username: &str, url: &str, tags: &[&str]) -> SearchItem {
+    SearchItem {
+        key: String::from(key),
+        username: String::from(username),
+        url: String::from(url),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    }
+}
+
+fn score(query: &str, candidate: &str) -> Option<i64> {
+    score_normalized(&normalize(query), &normalize(candidate))
+}
+
+fn prepare_items() -> Vec<SearchItem> {
+    vec![
+        item(
+            "gmail",
+            "john.doe@gmail.com",
+            "https://mail.google.com",
+            &["mail"],
+        ),
+        item("github", "johndoe", "https://github.com", &["work", "dev"]),
+        item("gitlab", "jdoe", "", &["work"]),
+        item("Café Müller", "", "", &[]),
+    ]
+}
+
+#[test]
+fn test_score_order() {
+    let exact = score("gmail", "gmail").unwrap();
+    let prefix = score("gma", "gmail").unwrap();
+    let substring = score("mail", "gmail").unwrap();
+    let contained = score("gmail.com", "gmail").unwrap();
+    let subsequence = score("gml", "gmail").unwrap();
+
+    assert!(exact > prefix);
+    assert!(prefix > substring);
+    assert!(substring > contained);
+    assert!(contained > subsequence);
+}
+
+#[test]
+fn test_case_and_unicode() {
+    assert_eq!(score("GMAIL", "gmail"), Some(EXACT_SCORE));
+    assert_eq!(score("cafe muller", "Café Müller"), Some(EXACT_SCORE));
+    assert!(score("ΣΟΦΙΑ", "σοφια").is_some());
+}
+
+#[test]
+fn test_typos() {
+    assert!(score("gmial", "gmail").is_some());
+    assert!(score("githbu", "github").is_some());
+    assert_eq!(score("yahoo", "gmail"), None);
+}
+
+#[test]
+fn test_weak_match_is_not_picked() {
+    let items = prepare_items();
+
+    let results = search("gmial", &items);
+    assert_eq!(results.len(), 1);
+    assert_eq!(best_match(&results), None);
+}
+
+#[test]
+fn test_search_fields() {
+    let items = prepare_items();
+
+    let results = search("gmail.com", &items);
+    assert_eq!(results[0].key, "gmail");
+    assert_eq!(best_match(&results).unwrap().key, "gmail");
+
+    let results = search("work", &items);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].field, "tag");
+    assert_eq!(best_match(&results), None);
+
+    let results = search("jdoe", &items);
+    assert_eq!(results[0].key, "gitlab");
+    assert_eq!(results[0].field, "username");
+}
+
+#[test]
+fn test_no_match() {
+    let items = prepare_items();
+
+    assert!(search("zzz", &items).is_empty());
+    assert!(search("", &items).is_empty());
+}
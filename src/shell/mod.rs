@@ -0,0 +1,253 @@
+use crate::cli;
+use crate::command::{self, COMMANDS};
+use crate::log::Event;
+use crate::throttle;
+use crate::time;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT_MINUTES: u64 = 5;
+
+const BUILTINS: [&str; 4] = ["exit", "quit", "lock", "unlock"];
+const NESTED_COMMANDS: [&str; 2] = ["shell", "--version"];
+
+// Reads commands in a loop with the vault unlocked by given master password. Vault is
+// locked again after `timeout_minutes` without input, zero means it never locks.
+pub fn run(master_password: String, timeout_minutes: u64) {
+    let mut editor: Editor<DomeHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(err) => return cli::error(format!("[ERROR] Can't start shell: {}", err).as_str()),
+    };
+
+    command::set_session_password(Some(master_password));
+    editor.set_helper(Some(DomeHelper::new()));
+
+    let last_activity = Arc::new(Mutex::new(Instant::now()));
+    let running = Arc::new(AtomicBool::new(true));
+    if timeout_minutes > 0 {
+        spawn_locker(
+            Duration::from_secs(timeout_minutes * 60),
+            Arc::clone(&last_activity),
+            Arc::clone(&running),
+        );
+    }
+
+    println!("Vault is unlocked. Type `help` to see commands or `exit` to leave.");
+
+    loop {
+        let prompt = match command::session_password() {
+            Some(_) => "dome> ",
+            None => "dome (locked)> ",
+        };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(_) => break,
+        };
+
+        *last_activity.lock().unwrap() = Instant::now();
+
+        let words = split_line(&line);
+        let name = match words.first() {
+            Some(name) => name.as_str(),
+            None => continue,
+        };
+
+        let _ = editor.add_history_entry(line.as_str());
+
+        match name {
+            "exit" | "quit" => break,
+            "lock" => {
+                command::set_session_password(None);
+                println!("Vault was locked.");
+                continue;
+            }
+            "unlock" => {
+                unlock();
+                continue;
+            }
+            _ => {}
+        }
+
+        if NESTED_COMMANDS.contains(&name) {
+            println!("Command {} is not available inside the shell.", name);
+            continue;
+        }
+
+        if command::session_password().is_none() && !unlock() {
+            continue;
+        }
+
+        let mut args = vec![String::from("dome")];
+        args.extend(words);
+
+        match command::parse_args(&args) {
+            Ok(c) => command::execute(&c),
+            Err(err) => println!("{}", err),
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh();
+        }
+    }
+
+    running.store(false, Ordering::Relaxed);
+    command::set_session_password(None);
+}
+
+fn unlock() -> bool {
+    if let Err(err) = throttle::check(time::now()) {
+        cli::error(&err);
+        return false;
+    }
+
+    let master_password = command::unlock_password(cli::read_password("Enter master password: "));
+
+    match master_password.and_then(command::try_master_password) {
+        Ok(master_password) => {
+            command::log_event(Event::Unlock, "", &master_password);
+            command::set_session_password(Some(master_password));
+            true
+        }
+        Err(err) => {
+            cli::error(&err);
+            false
+        }
+    }
+}
+
+// Forgets the session password once there was no input for the timeout
+fn spawn_locker(timeout: Duration, last_activity: Arc<Mutex<Instant>>, running: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_secs(1));
+
+            if last_activity.lock().unwrap().elapsed() >= timeout {
+                command::set_session_password(None);
+            }
+        }
+    });
+}
+
+// Splits line into words. Words can be quoted with " or ' to contain spaces.
+pub fn split_line(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_word = false;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(current);
+    }
+
+    words
+}
+
+// First word completes to command names, the following ones to domain names
+fn complete_word(line: &str, pos: usize, domains: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .rfind(char::is_whitespace)
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let word = &before[start..];
+
+    let candidates: Vec<String> = if before[..start].trim().is_empty() {
+        COMMANDS
+            .iter()
+            .chain(BUILTINS.iter())
+            .filter(|name| !NESTED_COMMANDS.contains(name))
+            .map(|name| name.to_string())
+            .collect()
+    } else if word.starts_with('-') {
+        Vec::new()
+    } else {
+        domains.to_vec()
+    };
+
+    let mut matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+    matches.sort();
+
+    (start, matches)
+}
+
+struct DomeHelper {
+    domains: Vec<String>,
+}
+
+impl DomeHelper {
+    fn new() -> DomeHelper {
+        let mut helper = DomeHelper {
+            domains: Vec::new(),
+        };
+        helper.refresh();
+        helper
+    }
+
+    fn refresh(&mut self) {
+        // Completion goes without domains while the index can't be read
+        self.domains = command::get_index_set()
+            .map(|index_set| index_set.data.into_iter().map(|entry| entry.key).collect())
+            .unwrap_or_default();
+    }
+}
+
+impl Completer for DomeHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete_word(line, pos, &self.domains))
+    }
+}
+
+impl Hinter for DomeHelper {
+    type Hint = String;
+}
+
+impl Highlighter for DomeHelper {}
+
+impl Validator for DomeHelper {}
+
+impl Helper for DomeHelper {}
+
+#[cfg(test)]
+mod test;
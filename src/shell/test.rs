@@ -0,0 +1,49 @@
+use super::*;
+
+#[test]
+fn test_split_line() {
+    assert_eq!(split_line("  get   gmail "), vec!["get", "gmail"]);
+    assert_eq!(
+        split_line(r#"add "my site" -u 'john doe'"#),
+        vec!["add", "my site", "-u", "john doe"]
+    );
+    assert_eq!(split_line(r#"add "" -u x"#), vec!["add", "", "-u", "x"]);
+    assert!(split_line("   ").is_empty());
+}
+
+#[test]
+fn test_split_line_parses_like_arguments() {
+    let mut args = vec![String::from("dome")];
+    args.extend(split_line("add gmail -u john --tag work,mail"));
+
+    let command = command::parse_args(&args).unwrap();
+
+    assert_eq!(command.command, "add");
+    assert_eq!(command.args, vec!["gmail"]);
+    assert_eq!(command.flags.get("--tag").unwrap(), "work,mail");
+}
+
+#[test]
+fn test_complete_commands() {
+    let (start, matches) = complete_word("ge", 2, &[]);
+
+    assert_eq!(start, 0);
+    assert_eq!(matches, vec!["gen", "get"]);
+    assert!(complete_word("she", 3, &[]).1.is_empty());
+}
+
+#[test]
+fn test_complete_domains() {
+    let domains = vec![
+        String::from("github"),
+        String::from("gitlab"),
+        String::from("gmail"),
+    ];
+
+    let (start, matches) = complete_word("get gi", 6, &domains);
+    assert_eq!(start, 4);
+    assert_eq!(matches, vec!["github", "gitlab"]);
+
+    let (_, matches) = complete_word("add gmail -", 11, &domains);
+    assert!(matches.is_empty());
+}
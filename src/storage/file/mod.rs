@@ -0,0 +1,130 @@
+use super::{index, vault, StorageBackend};
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::BinarySerialization;
+use std::fs;
+
+// Index and records in two files of Dome directory, the original layout
+pub struct FileBackend;
+
+impl StorageBackend for FileBackend {
+    fn list(&self) -> Result<IndexSet, String> {
+        IndexSet::try_from_binary(&index::index_read())
+    }
+
+    fn read_entry(&self, location: u64) -> Vec<u8> {
+        vault::vault_read(location)
+    }
+
+    fn write_entry(&mut self, record: &[u8]) -> Result<u64, String> {
+        let location = vault::vault_len();
+        vault::vault_add(record);
+        Ok(location)
+    }
+
+    fn delete_entry(&mut self, location: u64) -> Result<u64, String> {
+        Ok(vault::vault_tombstone(location))
+    }
+
+    fn write_index(&mut self, index_set: &IndexSet) -> Result<(), String> {
+        index::index_write(&index_set.serialize())
+    }
+
+    fn records_len(&self) -> u64 {
+        vault::vault_len()
+    }
+
+    fn snapshot(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let records = fs::read(super::vault_file_path()).unwrap_or_default();
+        Ok((records, index::index_read()))
+    }
+
+    fn replace(&mut self, records: &[u8], index: &[u8]) -> Result<(), String> {
+        super::replace_files(&[
+            (super::vault_file_path(), records.to_vec()),
+            (super::index_file_path(), index.to_vec()),
+        ])
+    }
+
+    // New records are appended right away, the index is written once at the end and
+    // records are tombstoned only after that. Until the index is renamed into place the
+    // old one still points to the old records. Failed changes cut the appended records off
+    // again, records appended before a crash are unreferenced and compaction drops them.
+    fn transaction(
+        &mut self,
+        changes: &mut dyn FnMut(&mut dyn StorageBackend) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut staged = Staged {
+            vault_len: vault::vault_len(),
+            index: None,
+            deleted: Vec::new(),
+        };
+
+        if let Err(err) = changes(&mut staged) {
+            vault::vault_truncate(staged.vault_len)?;
+            return Err(err);
+        }
+
+        if let Some(index) = &staged.index {
+            if let Err(err) = index::index_write(index) {
+                vault::vault_truncate(staged.vault_len)?;
+                return Err(err);
+            }
+        }
+
+        for location in staged.deleted {
+            vault::vault_tombstone(location);
+        }
+
+        Ok(())
+    }
+}
+
+// Changes of a transaction on the files that aren't visible to other readers yet
+struct Staged {
+    vault_len: u64,
+    index: Option<Vec<u8>>,
+    deleted: Vec<u64>,
+}
+
+impl StorageBackend for Staged {
+    fn list(&self) -> Result<IndexSet, String> {
+        match &self.index {
+            Some(index) => IndexSet::try_from_binary(index),
+            None => FileBackend.list(),
+        }
+    }
+
+    fn read_entry(&self, location: u64) -> Vec<u8> {
+        vault::vault_read(location)
+    }
+
+    fn write_entry(&mut self, record: &[u8]) -> Result<u64, String> {
+        FileBackend.write_entry(record)
+    }
+
+    fn delete_entry(&mut self, location: u64) -> Result<u64, String> {
+        let size = vault::vault_read(location).len() as u64;
+        self.deleted.push(location);
+        Ok(size)
+    }
+
+    fn write_index(&mut self, index_set: &IndexSet) -> Result<(), String> {
+        self.index = Some(index_set.serialize());
+        Ok(())
+    }
+
+    fn records_len(&self) -> u64 {
+        vault::vault_len()
+    }
+
+    fn snapshot(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let (records, index) = FileBackend.snapshot()?;
+        Ok((records, self.index.clone().unwrap_or(index)))
+    }
+
+    fn replace(&mut self, _records: &[u8], _index: &[u8]) -> Result<(), String> {
+        Err(String::from(
+            "Vault can't be replaced inside a transaction.",
+        ))
+    }
+}
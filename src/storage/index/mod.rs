@@ -1,5 +1,5 @@
-use std::fs::File;
-use std::io::{Read, Write};
+use std::fs::{self, File};
+use std::io::Read;
 
 pub fn index_read() -> Vec<u8> {
     let path = super::index_file_path();
@@ -9,8 +9,28 @@ pub fn index_read() -> Vec<u8> {
     buffer
 }
 
-pub fn index_write(data: &[u8]) {
-    let path = super::index_file_path();
-    let mut file = File::create(path).unwrap();
-    file.write_all(data).unwrap();
+// Index is written next to the old one and renamed over it, so it is never half written
+pub fn index_write(data: &[u8]) -> Result<(), String> {
+    super::replace_file(&super::index_file_path(), data)
+}
+
+// Version of the MAC the index was last seen sealed with, 0 before the first seal. It is
+// kept outside the index, so a removed MAC or one rolled back to an older version is noticed.
+pub fn sealed_version() -> u8 {
+    fs::read_to_string(super::seal_file_path())
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+// Only moves forward, the index is never sealed with an older version again
+pub fn record_seal(version: u8) -> Result<(), String> {
+    if version <= sealed_version() {
+        return Ok(());
+    }
+
+    super::replace_file(
+        &super::seal_file_path(),
+        format!("{}\n", version).as_bytes(),
+    )
 }
@@ -0,0 +1,75 @@
+use super::StorageBackend;
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::BinarySerialization;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Records and index kept in memory. Clones share the same data, so a test can open the
+// backend several times and see its own changes.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    records: Vec<u8>,
+    index: Vec<u8>,
+}
+
+impl MemoryBackend {
+    pub fn from_parts(records: Vec<u8>, index: Vec<u8>) -> MemoryBackend {
+        MemoryBackend {
+            state: Rc::new(RefCell::new(State { records, index })),
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn list(&self) -> Result<IndexSet, String> {
+        IndexSet::try_from_binary(&self.state.borrow().index)
+    }
+
+    fn read_entry(&self, location: u64) -> Vec<u8> {
+        let state = self.state.borrow();
+        let data = &state.records[location as usize..];
+        let size = super::record_size(data).expect("Record is outside of the vault.");
+        data[..size].to_vec()
+    }
+
+    fn write_entry(&mut self, record: &[u8]) -> Result<u64, String> {
+        let mut state = self.state.borrow_mut();
+        let location = state.records.len() as u64;
+        state.records.extend(record);
+        Ok(location)
+    }
+
+    fn delete_entry(&mut self, location: u64) -> Result<u64, String> {
+        let record = self.read_entry(location);
+        let start = location as usize;
+        self.state.borrow_mut().records[start..start + record.len()]
+            .copy_from_slice(&super::tombstone(&record));
+        Ok(record.len() as u64)
+    }
+
+    fn write_index(&mut self, index_set: &IndexSet) -> Result<(), String> {
+        self.state.borrow_mut().index = index_set.serialize();
+        Ok(())
+    }
+
+    fn records_len(&self) -> u64 {
+        self.state.borrow().records.len() as u64
+    }
+
+    fn snapshot(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let state = self.state.borrow();
+        Ok((state.records.clone(), state.index.clone()))
+    }
+
+    fn replace(&mut self, records: &[u8], index: &[u8]) -> Result<(), String> {
+        let mut state = self.state.borrow_mut();
+        state.records = records.to_vec();
+        state.index = index.to_vec();
+        Ok(())
+    }
+}
@@ -1,15 +1,224 @@
+use crate::datastructures::sorted_indexentry_set::IndexSet;
 use crate::errs;
+use file::FileBackend;
+use memory::MemoryBackend;
+use single_file::SingleFileBackend;
 use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+#[cfg(test)]
+use std::cell::RefCell;
+
+pub mod file;
 pub mod index;
+pub mod memory;
+pub mod single_file;
 pub mod vault;
 
 const INDEX_FILE: &str = "index.bin";
 const VAULT_FILE: &str = "vault.bin";
+const SINGLE_FILE: &str = "vault.db";
+const CONFIG_FILE: &str = "config";
+const BACKUP_FOLDER: &str = "backups";
+const TRANSACTION_FILE: &str = "transaction";
+const SYNC_FOLDER: &str = "sync";
+const SYNC_STATE_FILE: &str = "sync-state";
+const CONFLICTS_FILE: &str = "conflicts";
+const IDENTITY_FILE: &str = "identity";
+const TEAM_FILE: &str = "team";
+const TEAM_PINS_FILE: &str = "team-pins";
+const KEY_FILE_SETTING: &str = "keyfile";
+const LOG_FILE: &str = "audit.log";
+const LOG_KEY_FILE: &str = "audit.key";
+const ATTEMPTS_FILE: &str = "attempts";
+const SEAL_FILE: &str = "seal";
+const RECOVERY_FILE: &str = "recovery";
 const DOME_FOLDER: &str = "dome";
+const DOME_DIR_VAR: &str = "DOME_DIR";
+
+// Encrypted records and the index that points into them. Records are addressed by their
+// location, which is the offset inside all records of the backend.
+pub trait StorageBackend {
+    // Fails when the index is damaged, instead of reading it as an empty vault that the
+    // next change would save over it
+    fn list(&self) -> Result<IndexSet, String>;
+
+    fn read_entry(&self, location: u64) -> Vec<u8>;
+
+    // Appends the record and returns its location
+    fn write_entry(&mut self, record: &[u8]) -> Result<u64, String>;
+
+    // Turns the record into a tombstone and returns its size
+    fn delete_entry(&mut self, location: u64) -> Result<u64, String>;
+
+    fn write_index(&mut self, index_set: &IndexSet) -> Result<(), String>;
+
+    // Size of all records, tombstones included
+    fn records_len(&self) -> u64;
+
+    // Raw records and serialized index
+    fn snapshot(&self) -> Result<(Vec<u8>, Vec<u8>), String>;
+
+    // Replaces records and index at once. Interrupted replace leaves the old ones.
+    fn replace(&mut self, records: &[u8], index: &[u8]) -> Result<(), String>;
+
+    // Runs the changes on a copy in memory and saves the result with a single replace.
+    // Backends that can change records in place override it.
+    fn transaction(
+        &mut self,
+        changes: &mut dyn FnMut(&mut dyn StorageBackend) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let (records, index) = self.snapshot()?;
+        let mut staged = MemoryBackend::from_parts(records, index);
+        changes(&mut staged)?;
+
+        let (records, index) = staged.snapshot()?;
+        self.replace(&records, &index)
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static DOME_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    static MEMORY_OVERRIDE: RefCell<Option<MemoryBackend>> = const { RefCell::new(None) };
+}
+
+// Makes storage functions on the current thread use given directory, so tests don't touch the real vault
+#[cfg(test)]
+pub fn use_dir(path: &Path) {
+    DOME_DIR_OVERRIDE.with(|dir| *dir.borrow_mut() = Some(path.to_path_buf()));
+    MEMORY_OVERRIDE.with(|memory| *memory.borrow_mut() = None);
+    initialize_files().unwrap();
+}
+
+// Keeps the vault of the current thread in memory, config still comes from the directory
+#[cfg(test)]
+pub fn use_memory(path: &Path) {
+    use_dir(path);
+    MEMORY_OVERRIDE.with(|memory| *memory.borrow_mut() = Some(MemoryBackend::default()));
+}
+
+// Backend of the vault in Dome directory. Single-file vault is used once it exists.
+pub fn open() -> Box<dyn StorageBackend> {
+    #[cfg(test)]
+    if let Some(memory) = MEMORY_OVERRIDE.with(|memory| memory.borrow().clone()) {
+        return Box::new(memory);
+    }
+
+    let single_file = single_file_path();
+    if single_file.exists() {
+        return Box::new(SingleFileBackend::new(&single_file));
+    }
+
+    Box::new(FileBackend)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Layout {
+    // index.bin and vault.bin
+    Files,
+    // Everything in vault.db
+    SingleFile,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "files" => Some(Layout::Files),
+            "single-file" => Some(Layout::SingleFile),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Files => "files",
+            Layout::SingleFile => "single-file",
+        }
+    }
+}
+
+pub fn layout() -> Layout {
+    if single_file_path().exists() {
+        Layout::SingleFile
+    } else {
+        Layout::Files
+    }
+}
+
+// Moves the vault into the other layout. Files of the old layout are removed only after
+// the new ones are written, and the single file wins while both exist.
+pub fn convert(to: Layout) -> Result<(), String> {
+    if layout() == to {
+        return Err(format!("Vault already uses the {} layout.", to.name()));
+    }
+
+    let (records, index) = open().snapshot()?;
+    let remove = |path: PathBuf| {
+        fs::remove_file(&path).map_err(|err| format!("Can't remove {}: {}", path.display(), err))
+    };
+
+    match to {
+        Layout::SingleFile => {
+            SingleFileBackend::new(&single_file_path()).replace(&records, &index)?;
+            remove(index_file_path())?;
+            remove(vault_file_path())
+        }
+        Layout::Files => {
+            FileBackend.replace(&records, &index)?;
+            remove(single_file_path())
+        }
+    }
+}
+
+// Size of the record at the start of data: salt, nonce, then domain, username and password
+// prefixed with their length. Records with the cipher header have an empty domain in front
+// of it, see vault_entry.
+pub fn record_size(data: &[u8]) -> Option<usize> {
+    let mut position = fields_start(data)?;
+
+    for _ in 0..3 {
+        position += 1 + *data.get(position)? as usize;
+    }
+
+    (position <= data.len()).then_some(position)
+}
+
+// Everything except the length bytes is overwritten with zeros, so the password can't be
+// recovered and the record keeps its size
+pub fn tombstone(record: &[u8]) -> Vec<u8> {
+    let mut tombstone = vec![0u8; record.len()];
+
+    let mut position = fields_start(record).unwrap();
+    if position > 28 {
+        tombstone[29] = record[29];
+    }
+    for _ in 0..3 {
+        tombstone[position] = record[position];
+        position += 1 + record[position] as usize;
+    }
+
+    tombstone
+}
+
+fn fields_start(record: &[u8]) -> Option<usize> {
+    match *record.get(28)? {
+        0 => Some(30 + *record.get(29)? as usize),
+        _ => Some(28),
+    }
+}
 
 pub fn dome_dir_path() -> PathBuf {
+    #[cfg(test)]
+    if let Some(path) = DOME_DIR_OVERRIDE.with(|dir| dir.borrow().clone()) {
+        return path;
+    }
+
+    if let Ok(path) = std::env::var(DOME_DIR_VAR) {
+        return PathBuf::from(path);
+    }
+
     let destination: PathBuf = appdata_dir_path().unwrap();
     destination.join(DOME_FOLDER)
 }
@@ -24,10 +233,167 @@ pub fn vault_file_path() -> PathBuf {
     Path::new(&dome_dir).join(VAULT_FILE)
 }
 
-pub fn initialize_files() {
+pub fn single_file_path() -> PathBuf {
+    dome_dir_path().join(SINGLE_FILE)
+}
+
+pub fn config_file_path() -> PathBuf {
+    dome_dir_path().join(CONFIG_FILE)
+}
+
+pub fn backup_dir_path() -> PathBuf {
+    dome_dir_path().join(BACKUP_FOLDER)
+}
+
+pub fn sync_dir_path() -> PathBuf {
+    dome_dir_path().join(SYNC_FOLDER)
+}
+
+pub fn sync_state_file_path() -> PathBuf {
+    dome_dir_path().join(SYNC_STATE_FILE)
+}
+
+pub fn conflicts_file_path() -> PathBuf {
+    dome_dir_path().join(CONFLICTS_FILE)
+}
+
+pub fn identity_file_path() -> PathBuf {
+    dome_dir_path().join(IDENTITY_FILE)
+}
+
+// File with the path of the team vault
+pub fn team_file_path() -> PathBuf {
+    dome_dir_path().join(TEAM_FILE)
+}
+
+// Member keys of the team this user checked
+pub fn team_pins_file_path() -> PathBuf {
+    dome_dir_path().join(TEAM_PINS_FILE)
+}
+
+// File with the path of the key file the vault requires
+pub fn key_file_setting_path() -> PathBuf {
+    dome_dir_path().join(KEY_FILE_SETTING)
+}
+
+pub fn log_file_path() -> PathBuf {
+    dome_dir_path().join(LOG_FILE)
+}
+
+pub fn log_key_file_path() -> PathBuf {
+    dome_dir_path().join(LOG_KEY_FILE)
+}
+
+pub fn attempts_file_path() -> PathBuf {
+    dome_dir_path().join(ATTEMPTS_FILE)
+}
+
+// File with the number of recovery shares and their threshold, once they were handed out
+pub fn recovery_file_path() -> PathBuf {
+    dome_dir_path().join(RECOVERY_FILE)
+}
+
+// File with the version of the MAC the index was last sealed with
+pub fn seal_file_path() -> PathBuf {
+    dome_dir_path().join(SEAL_FILE)
+}
+
+// Writes data next to the file first and then renames it, so the file is never half written
+pub fn replace_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    write_temp(path, data)?;
+    fs::rename(temp_path(path), path).map_err(|err| {
+        let _ = fs::remove_file(temp_path(path));
+        format!("Can't write {}: {}", path.display(), err)
+    })
+}
+
+// Replaces several files so that either all or none of them change. Once every new file
+// is written, their paths are committed into the transaction file. Renames that were
+// interrupted after the commit are finished by `recover_transaction`.
+pub fn replace_files(files: &[(PathBuf, Vec<u8>)]) -> Result<(), String> {
+    for (path, data) in files {
+        if let Err(err) = write_temp(path, data) {
+            for (path, _) in files {
+                let _ = fs::remove_file(temp_path(path));
+            }
+            return Err(err);
+        }
+    }
+
+    let journal: String = files
+        .iter()
+        .map(|(path, _)| format!("{}\n", path.display()))
+        .collect();
+    replace_file(&transaction_file_path(), journal.as_bytes())?;
+
+    finish_transaction()
+}
+
+// Finishes a transaction that was committed but not completed, and throws away new files
+// of a transaction that wasn't committed
+pub fn recover_transaction() -> Result<(), String> {
+    if transaction_file_path().exists() {
+        return finish_transaction()
+            .map_err(|err| format!("Unfinished change of the vault can't be completed: {}", err));
+    }
+
+    for path in [
+        index_file_path(),
+        vault_file_path(),
+        single_file_path(),
+        config_file_path(),
+        log_file_path(),
+        log_key_file_path(),
+    ] {
+        let _ = fs::remove_file(temp_path(&path));
+    }
+
+    Ok(())
+}
+
+fn finish_transaction() -> Result<(), String> {
+    let journal = fs::read_to_string(transaction_file_path()).map_err(|err| err.to_string())?;
+
+    for path in journal.lines().map(PathBuf::from) {
+        if temp_path(&path).exists() {
+            fs::rename(temp_path(&path), &path)
+                .map_err(|err| format!("Can't write {}: {}", path.display(), err))?;
+        }
+    }
+
+    fs::remove_file(transaction_file_path()).map_err(|err| err.to_string())
+}
+
+fn transaction_file_path() -> PathBuf {
+    dome_dir_path().join(TRANSACTION_FILE)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn write_temp(path: &Path, data: &[u8]) -> Result<(), String> {
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(temp_path(path))?;
+        file.write_all(data)?;
+        file.sync_all()
+    };
+
+    write().map_err(|err| format!("Can't write {}: {}", path.display(), err))
+}
+
+pub fn initialize_files() -> Result<(), String> {
     let dome_dir = dome_dir_path();
 
-    fs::create_dir_all(&dome_dir).unwrap();
+    fs::create_dir_all(&dome_dir)
+        .map_err(|err| format!("{} can't be created: {}", dome_dir.display(), err))?;
+    recover_transaction()?;
+
+    if single_file_path().exists() {
+        return Ok(());
+    }
 
     let index_path = index_file_path();
     if !index_path.exists() {
@@ -38,6 +404,8 @@ pub fn initialize_files() {
     if !vault_file.exists() {
         File::create(&vault_file).unwrap();
     }
+
+    Ok(())
 }
 
 fn appdata_dir_path() -> Result<PathBuf, &'static str> {
@@ -65,3 +433,6 @@ fn get_os() -> Result<&'static str, &'static str> {
         Err(errs::UNSUPPORTED_OS)
     }
 }
+
+#[cfg(test)]
+mod test;
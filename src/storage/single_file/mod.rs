@@ -0,0 +1,188 @@
+use super::memory::MemoryBackend;
+use super::StorageBackend;
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::{write_string, ByteReader};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Simple single-file layout, not a page-based database: header with magic, version and
+// number of sections, then the table of contents with name, position, length and checksum
+// of every section, then checksum of header and table, then the sections. The whole file
+// is written next to the old one and renamed over it on every change, so it is always
+// consistent. Vaults are small enough that this costs less than updating pages in place.
+pub const SINGLE_FILE_MAGIC: &[u8; 8] = b"DOMEFILE";
+pub const SINGLE_FILE_VERSION: u8 = 2;
+
+const INDEX_SECTION: &str = "index";
+const RECORDS_SECTION: &str = "records";
+
+pub struct SingleFileBackend {
+    path: PathBuf,
+}
+
+impl SingleFileBackend {
+    pub fn new(path: &Path) -> SingleFileBackend {
+        SingleFileBackend {
+            path: path.to_path_buf(),
+        }
+    }
+
+    // Changes are made in memory and the file is written once
+    fn update<T>(
+        &mut self,
+        change: impl FnOnce(&mut MemoryBackend) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut memory = self.memory()?;
+        let result = change(&mut memory)?;
+
+        let (records, index) = memory.snapshot()?;
+        self.replace(&records, &index)?;
+        Ok(result)
+    }
+
+    fn memory(&self) -> Result<MemoryBackend, String> {
+        let (records, index) = self.snapshot()?;
+        Ok(MemoryBackend::from_parts(records, index))
+    }
+}
+
+pub fn encode(records: &[u8], index: &[u8]) -> Vec<u8> {
+    let sections = [(INDEX_SECTION, index), (RECORDS_SECTION, records)];
+
+    let mut data = Vec::new();
+    data.extend(SINGLE_FILE_MAGIC);
+    data.push(SINGLE_FILE_VERSION);
+    data.push(sections.len() as u8);
+
+    // Sections start right after the table and its checksum
+    let table_len: usize = sections
+        .iter()
+        .map(|(name, _)| 2 + name.len() + 8 + 8 + 32)
+        .sum();
+    let mut position = (data.len() + table_len + 32) as u64;
+
+    for (name, section) in sections {
+        write_string(&mut data, name);
+        data.extend(position.to_le_bytes());
+        data.extend((section.len() as u64).to_le_bytes());
+        data.extend(Sha256::digest(section));
+        position += section.len() as u64;
+    }
+
+    data.extend(Sha256::digest(&data));
+    for (_, section) in sections {
+        data.extend(section);
+    }
+
+    data
+}
+
+// Records and index of the file
+pub fn decode(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    if !data.starts_with(SINGLE_FILE_MAGIC) {
+        return Err(String::from("File is not a Dome vault."));
+    }
+
+    let mut reader = ByteReader::new(&data[SINGLE_FILE_MAGIC.len()..]);
+    let damaged = || String::from("Vault file is damaged.");
+
+    let version = reader.read_u8().ok_or_else(damaged)?;
+    if version > SINGLE_FILE_VERSION {
+        return Err(String::from(
+            "Vault file was created by a newer version of Dome.",
+        ));
+    }
+
+    // Version 1 had only the length of the index, the index and the records
+    if version == 1 {
+        let len = reader.read_u64().ok_or_else(damaged)?;
+        let index = reader.read_bytes(len as usize).ok_or_else(damaged)?;
+        return Ok((reader.read_rest().to_vec(), index.to_vec()));
+    }
+
+    let count = reader.read_u8().ok_or_else(damaged)?;
+    let mut table = Vec::new();
+    for _ in 0..count {
+        let name = reader.read_string().ok_or_else(damaged)?;
+        let position = reader.read_u64().ok_or_else(damaged)? as usize;
+        let len = reader.read_u64().ok_or_else(damaged)? as usize;
+        let checksum = reader.read_bytes(32).ok_or_else(damaged)?;
+        table.push((name, position, len, checksum));
+    }
+
+    let table_end = data.len() - reader.read_rest().len();
+    if data.get(table_end..table_end + 32) != Some(Sha256::digest(&data[..table_end]).as_slice()) {
+        return Err(String::from(
+            "Table of contents of the vault file is damaged.",
+        ));
+    }
+
+    let (mut index, mut records) = (None, None);
+    for (name, position, len, checksum) in table {
+        let section = position
+            .checked_add(len)
+            .and_then(|end| data.get(position..end))
+            .ok_or_else(damaged)?;
+
+        if Sha256::digest(section).as_slice() != checksum {
+            return Err(format!(
+                "Checksum of {} in the vault file doesn't match.",
+                name
+            ));
+        }
+
+        // Sections this version doesn't know are skipped
+        match name.as_str() {
+            INDEX_SECTION => index = Some(section.to_vec()),
+            RECORDS_SECTION => records = Some(section.to_vec()),
+            _ => {}
+        }
+    }
+
+    Ok((records.ok_or_else(damaged)?, index.ok_or_else(damaged)?))
+}
+
+impl StorageBackend for SingleFileBackend {
+    fn list(&self) -> Result<IndexSet, String> {
+        self.memory()?.list()
+    }
+
+    // Locations come from the index, which can't be listed from a damaged file
+    fn read_entry(&self, location: u64) -> Vec<u8> {
+        self.memory()
+            .map(|memory| memory.read_entry(location))
+            .unwrap_or_default()
+    }
+
+    fn write_entry(&mut self, record: &[u8]) -> Result<u64, String> {
+        self.update(|memory| memory.write_entry(record))
+    }
+
+    fn delete_entry(&mut self, location: u64) -> Result<u64, String> {
+        self.update(|memory| memory.delete_entry(location))
+    }
+
+    fn write_index(&mut self, index_set: &IndexSet) -> Result<(), String> {
+        self.update(|memory| memory.write_index(index_set))
+    }
+
+    fn records_len(&self) -> u64 {
+        self.memory()
+            .map(|memory| memory.records_len())
+            .unwrap_or_default()
+    }
+
+    fn snapshot(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let data = fs::read(&self.path).unwrap_or_default();
+        if data.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        decode(&data).map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+
+    fn replace(&mut self, records: &[u8], index: &[u8]) -> Result<(), String> {
+        super::replace_file(&self.path, &encode(records, index))
+    }
+}
@@ -0,0 +1,264 @@
+use super::*;
+use crate::entry::index_entry::IndexEntry;
+
+#[test]
+fn test_replace_files() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    replace_files(&[
+        (index_file_path(), b"new index".to_vec()),
+        (vault_file_path(), b"new vault".to_vec()),
+    ])
+    .unwrap();
+
+    assert_eq!(fs::read(index_file_path()).unwrap(), b"new index");
+    assert_eq!(fs::read(vault_file_path()).unwrap(), b"new vault");
+    assert!(!transaction_file_path().exists());
+    assert!(!temp_path(&index_file_path()).exists());
+}
+
+#[test]
+fn test_recover_committed_transaction() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    // Interrupted after the commit and the first rename
+    fs::write(index_file_path(), b"new index").unwrap();
+    fs::write(temp_path(&vault_file_path()), b"new vault").unwrap();
+    let journal = format!(
+        "{}\n{}\n",
+        index_file_path().display(),
+        vault_file_path().display()
+    );
+    fs::write(transaction_file_path(), journal).unwrap();
+
+    initialize_files().unwrap();
+
+    assert_eq!(fs::read(index_file_path()).unwrap(), b"new index");
+    assert_eq!(fs::read(vault_file_path()).unwrap(), b"new vault");
+    assert!(!transaction_file_path().exists());
+}
+
+#[test]
+fn test_recover_uncommitted_transaction() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+    fs::write(vault_file_path(), b"old vault").unwrap();
+
+    // Interrupted while new files were written
+    fs::write(temp_path(&vault_file_path()), b"new vault").unwrap();
+
+    initialize_files().unwrap();
+
+    assert_eq!(fs::read(vault_file_path()).unwrap(), b"old vault");
+    assert!(!temp_path(&vault_file_path()).exists());
+}
+
+// Salt and nonce, then domain, username and password with their length
+fn record(domain: &str) -> Vec<u8> {
+    let mut record = vec![7u8; 28];
+    for part in [domain, "john", "cipher"] {
+        record.push(part.len() as u8);
+        record.extend(part.as_bytes());
+    }
+    record
+}
+
+fn backends(dir: &Path) -> Vec<Box<dyn StorageBackend>> {
+    use_dir(dir);
+    fs::write(dir.join("other.dome"), b"").unwrap();
+
+    vec![
+        Box::new(FileBackend),
+        Box::new(MemoryBackend::default()),
+        Box::new(SingleFileBackend::new(&dir.join("other.dome"))),
+    ]
+}
+
+#[test]
+fn test_backend_entries() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for mut backend in backends(dir.path()) {
+        let gmail = backend.write_entry(&record("gmail")).unwrap();
+        let github = backend.write_entry(&record("github")).unwrap();
+
+        assert_eq!(gmail, 0);
+        assert_eq!(github, record("gmail").len() as u64);
+        assert_eq!(backend.read_entry(github), record("github"));
+
+        let size = backend.delete_entry(gmail).unwrap();
+        assert_eq!(size, record("gmail").len() as u64);
+        assert_eq!(backend.read_entry(gmail), tombstone(&record("gmail")));
+        assert_eq!(backend.read_entry(github), record("github"));
+        assert_eq!(
+            backend.records_len(),
+            github + record("github").len() as u64
+        );
+    }
+}
+
+#[test]
+fn test_backend_index_and_transaction() {
+    let dir = tempfile::tempdir().unwrap();
+
+    for mut backend in backends(dir.path()) {
+        let mut index_set = IndexSet::new();
+        index_set.add(&IndexEntry::new("gmail", 0));
+        backend.write_entry(&record("gmail")).unwrap();
+        backend.write_index(&index_set).unwrap();
+        assert!(backend.list().unwrap().has("gmail"));
+
+        // Failed transaction changes nothing
+        let records_len = backend.records_len();
+        let result = backend.transaction(&mut |staged| {
+            staged.delete_entry(0)?;
+            staged.write_entry(&record("github"))?;
+            staged.write_index(&IndexSet::new())?;
+            Err(String::from("failed"))
+        });
+        assert!(result.is_err());
+        assert!(backend.list().unwrap().has("gmail"));
+        assert_eq!(backend.read_entry(0), record("gmail"));
+        assert_eq!(backend.records_len(), records_len);
+
+        backend
+            .transaction(&mut |staged| {
+                let location = staged.write_entry(&record("github"))?;
+                let mut index_set = staged.list().unwrap();
+                index_set.add(&IndexEntry::new("github", location));
+                staged.write_index(&index_set)
+            })
+            .unwrap();
+        assert!(backend.list().unwrap().has("github"));
+
+        backend
+            .transaction(&mut |staged| {
+                let size = staged.delete_entry(0)?;
+                let mut index_set = staged.list().unwrap();
+                index_set.remove("gmail", size);
+                staged.write_index(&index_set)
+            })
+            .unwrap();
+        assert!(!backend.list().unwrap().has("gmail"));
+        assert_eq!(backend.read_entry(0), tombstone(&record("gmail")));
+
+        let (records, index) = backend.snapshot().unwrap();
+        backend.replace(&records[..0], &index).unwrap();
+        assert_eq!(backend.records_len(), 0);
+        assert_eq!(backend.list().unwrap().data.len(), 1);
+    }
+}
+
+#[test]
+fn test_single_file_layout() {
+    let data = single_file::encode(b"records", b"index");
+
+    assert_eq!(
+        single_file::decode(&data).unwrap(),
+        (b"records".to_vec(), b"index".to_vec())
+    );
+    assert!(single_file::decode(b"vault").is_err());
+    assert!(single_file::decode(&data[..12]).is_err());
+    assert!(single_file::decode(&data[..data.len() - 1]).is_err());
+
+    // Version 1 had no table of contents
+    let mut old = b"DOMEFILE\x01".to_vec();
+    old.extend(5u64.to_le_bytes());
+    old.extend(b"indexrecords");
+    assert_eq!(
+        single_file::decode(&old).unwrap(),
+        (b"records".to_vec(), b"index".to_vec())
+    );
+}
+
+#[test]
+fn test_single_file_checksums() {
+    let data = single_file::encode(b"records", b"index");
+
+    let mut damaged = data.clone();
+    let last = damaged.len() - 1;
+    damaged[last] ^= 1;
+    assert_eq!(
+        single_file::decode(&damaged).err().unwrap(),
+        "Checksum of records in the vault file doesn't match."
+    );
+
+    // Length of the index section in the table
+    let mut damaged = data.clone();
+    damaged[18 + 8] ^= 1;
+    assert_eq!(
+        single_file::decode(&damaged).err().unwrap(),
+        "Table of contents of the vault file is damaged."
+    );
+}
+
+#[test]
+fn test_convert() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    let mut index_set = IndexSet::new();
+    index_set.add(&IndexEntry::new("gmail", 0));
+    open().write_entry(&record("gmail")).unwrap();
+    open().write_index(&index_set).unwrap();
+    let before = open().snapshot().unwrap();
+
+    assert!(convert(Layout::Files).is_err());
+    convert(Layout::SingleFile).unwrap();
+    assert_eq!(layout(), Layout::SingleFile);
+    assert!(!index_file_path().exists() && !vault_file_path().exists());
+    assert_eq!(open().snapshot().unwrap(), before);
+
+    // Starting Dome again doesn't bring the two files back
+    initialize_files().unwrap();
+    assert!(!index_file_path().exists());
+
+    convert(Layout::Files).unwrap();
+    assert_eq!(layout(), Layout::Files);
+    assert!(!single_file_path().exists());
+    assert_eq!(open().snapshot().unwrap(), before);
+}
+
+#[test]
+fn test_open_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    open().write_entry(&record("gmail")).unwrap();
+    fs::write(single_file_path(), b"").unwrap();
+    open().write_entry(&record("github")).unwrap();
+
+    assert_eq!(open().read_entry(0), record("github"));
+    assert_eq!(FileBackend.read_entry(0), record("gmail"));
+}
+
+#[test]
+fn test_damaged_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    let mut data = single_file::encode(&record("gmail"), b"index");
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    fs::write(single_file_path(), data).unwrap();
+
+    assert!(open().snapshot().is_err());
+    assert!(open().list().is_err());
+    assert!(open().write_entry(&record("github")).is_err());
+    assert!(open().read_entry(0).is_empty());
+}
+
+#[test]
+fn test_damaged_transaction() {
+    let dir = tempfile::tempdir().unwrap();
+    use_dir(dir.path());
+
+    fs::write(transaction_file_path(), [0xff, 0xfe]).unwrap();
+
+    assert!(initialize_files()
+        .err()
+        .unwrap()
+        .starts_with("Unfinished change of the vault can't be completed"));
+}
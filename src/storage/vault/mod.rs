@@ -12,46 +12,41 @@ pub fn vault_read(start_byte: u64) -> Vec<u8> {
     let mut file = File::open(path).unwrap();
     file.seek(SeekFrom::Start(start_byte)).unwrap();
 
-    let mut salt = [0u8; 16];
-    file.read_exact(&mut salt).unwrap();
-
-    let mut nonce = [0u8; 12];
-    file.read_exact(&mut nonce).unwrap();
+    // Salt and nonce
+    let mut buffer = vec![0u8; 28];
+    file.read_exact(&mut buffer).unwrap();
 
     let mut size = [0u8; 1];
-
-    // Domain size
     file.read_exact(&mut size).unwrap();
-    let domain_len = size[0] as usize;
 
-    let mut domain = vec![0u8; domain_len];
-    file.read_exact(&mut domain).unwrap();
+    // Empty domain starts the header of records with other ciphers than AES-256-GCM
+    if size[0] == 0 {
+        buffer.push(0);
+        read_field(&mut file, &mut buffer);
+        file.read_exact(&mut size).unwrap();
+    }
 
-    // Username size
-    file.read_exact(&mut size).unwrap();
-    let username_len = size[0] as usize;
+    // Domain, username and password
+    buffer.push(size[0]);
+    read_bytes(&mut file, &mut buffer, size[0] as usize);
+    read_field(&mut file, &mut buffer);
+    read_field(&mut file, &mut buffer);
 
-    let mut username = vec![0u8; username_len];
-    file.read_exact(&mut username).unwrap();
+    buffer
+}
 
-    // Password size
+// Reads the length byte and the bytes after it
+fn read_field(file: &mut File, buffer: &mut Vec<u8>) {
+    let mut size = [0u8; 1];
     file.read_exact(&mut size).unwrap();
-    let password_len = size[0] as usize;
-
-    let mut password = vec![0u8; password_len];
-    file.read_exact(&mut password).unwrap();
-
-    let mut buffer: Vec<u8> = Vec::new();
-    buffer.extend(salt);
-    buffer.extend(nonce);
-    buffer.push(domain.len().try_into().unwrap());
-    buffer.extend(domain);
-    buffer.push(username.len().try_into().unwrap());
-    buffer.extend(username);
-    buffer.push(password.len().try_into().unwrap());
-    buffer.extend(password);
+    buffer.push(size[0]);
+    read_bytes(file, buffer, size[0] as usize);
+}
 
-    buffer
+fn read_bytes(file: &mut File, buffer: &mut Vec<u8>, len: usize) {
+    let mut bytes = vec![0u8; len];
+    file.read_exact(&mut bytes).unwrap();
+    buffer.extend(bytes);
 }
 
 pub fn vault_add(data: &[u8]) {
@@ -60,26 +55,29 @@ pub fn vault_add(data: &[u8]) {
     file.write_all(data).unwrap();
 }
 
-pub fn vault_remove(start_byte: u64) -> u64 {
-    let b_entry = vault_read(start_byte);
-    let size = b_entry.len() as u64;
+// Overwrites the record with its tombstone. Returns size of the record.
+pub fn vault_tombstone(start_byte: u64) -> u64 {
+    let record = vault_read(start_byte);
+    let tombstone = super::tombstone(&record);
 
     let mut file = OpenOptions::new()
-    .read(true)
-    .write(true) 
-    .open(super::vault_file_path())
-    .unwrap();
-
-    let file_len = file.metadata().unwrap().len();
-    file.seek(SeekFrom::Start(start_byte + size)).unwrap();
-
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).unwrap();
+        .write(true)
+        .open(super::vault_file_path())
+        .unwrap();
     file.seek(SeekFrom::Start(start_byte)).unwrap();
-    file.write_all(&buffer).unwrap();
-    file.set_len(size + file_len).unwrap();
+    file.write_all(&tombstone).unwrap();
 
-    size
+    record.len() as u64
+}
+
+// Drops records appended after given length
+pub fn vault_truncate(len: u64) -> Result<(), String> {
+    let path = super::vault_file_path();
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_len(len))
+        .map_err(|err| format!("Can't write {}: {}", path.display(), err))
 }
 
 fn vault_metadata() -> Metadata {
@@ -0,0 +1,592 @@
+use crate::config;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Offline estimate of how many guesses an attacker needs, in the spirit of zxcvbn. The
+// password is split into known patterns and the cheapest way to build it from them decides
+// the score, so "P@ssw0rd1990" is weak even though it has every kind of character.
+
+// Ranked from the most common
+const PASSWORDS: &str = include_str!("passwords.txt");
+const WORDS: &str = include_str!("words.txt");
+
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+// Rows are shifted to the right by this many keys
+const KEYBOARD_OFFSETS: [f64; 4] = [0.0, 0.5, 0.75, 1.25];
+const KEYBOARD_KEYS: f64 = 47.0;
+const KEYBOARD_DEGREE: f64 = 4.6;
+
+// Characters like 1 can stand for several letters, every one doubles the variants
+const MAX_AMBIGUOUS_L33T: usize = 4;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_YEAR_SPACE: i32 = 20;
+// Longer passwords are only analysed up to this length, they are strong anyway
+const MAX_LENGTH: usize = 100;
+
+// Guesses needed for each score, score 4 needs more than the last one
+const SCORE_LOG10: [f64; 4] = [3.0, 6.0, 8.0, 10.0];
+pub const MAX_SCORE: u8 = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    CommonPassword,
+    Word { l33t: bool, reversed: bool },
+    UserInput,
+    Keyboard,
+    Repeat,
+    Sequence,
+    Date,
+    Bruteforce,
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    start: usize,
+    end: usize,
+    pattern: Pattern,
+    log10: f64,
+}
+
+#[derive(Debug)]
+pub struct Estimate {
+    pub score: u8,
+    // log10 of the number of guesses
+    pub guesses_log10: f64,
+    pub patterns: Vec<Pattern>,
+}
+
+impl Estimate {
+    // Advice that never repeats a part of the password
+    pub fn feedback(&self) -> Vec<&'static str> {
+        let mut feedback = Vec::new();
+        for pattern in &self.patterns {
+            let text = match pattern {
+                Pattern::CommonPassword => "It is a commonly used password.",
+                Pattern::Word { l33t: true, .. } => {
+                    "Substitutions like @ for a don't make words much harder to guess."
+                }
+                Pattern::Word { reversed: true, .. } => "Reversed words aren't harder to guess.",
+                Pattern::Word { .. } => "It contains a dictionary word.",
+                Pattern::UserInput => "It contains the domain or username.",
+                Pattern::Keyboard => "It contains a keyboard pattern like qwerty.",
+                Pattern::Repeat => "It contains repeated characters like aaa or abcabc.",
+                Pattern::Sequence => "It contains a sequence like abc or 123.",
+                Pattern::Date => "It contains a date or a year.",
+                Pattern::Bruteforce => continue,
+            };
+            if !feedback.contains(&text) {
+                feedback.push(text);
+            }
+        }
+
+        if feedback.is_empty() && self.score < MAX_SCORE {
+            feedback.push("Use a longer password.");
+        }
+
+        feedback
+    }
+}
+
+// Estimates the password. User inputs, like the domain and username, are treated as the
+// first words an attacker tries.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Estimate {
+    let chars: Vec<char> = password.chars().take(MAX_LENGTH).collect();
+    let matches = find_matches(&chars, user_inputs);
+    let (guesses_log10, chosen) = cheapest(chars.len(), &matches);
+
+    let score = SCORE_LOG10
+        .iter()
+        .take_while(|threshold| guesses_log10 >= **threshold)
+        .count() as u8;
+
+    Estimate {
+        score,
+        guesses_log10,
+        patterns: chosen.into_iter().map(|m| m.pattern).collect(),
+    }
+}
+
+fn find_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    matches.extend(dictionary_matches(chars, user_inputs));
+    matches.extend(keyboard_matches(chars));
+    matches.extend(repeat_matches(chars, user_inputs));
+    matches.extend(sequence_matches(chars));
+    matches.extend(date_matches(chars));
+    matches
+}
+
+// Finds the sequence of matches with the fewest guesses. Like zxcvbn, the product of the
+// guesses is multiplied by the factorial of the number of parts, because an attacker doesn't
+// know how many patterns were joined.
+fn cheapest(len: usize, matches: &[Match]) -> (f64, Vec<Match>) {
+    if len == 0 {
+        return (0.0, Vec::new());
+    }
+
+    // best[end][count] is the cheapest way to build the first end characters from count parts
+    let mut best: Vec<HashMap<usize, (f64, Option<Match>)>> = vec![HashMap::new(); len + 1];
+    best[0].insert(0, (0.0, None));
+
+    for end in 1..=len {
+        let mut candidates: Vec<Match> = matches.iter().filter(|m| m.end == end).cloned().collect();
+        candidates.extend((0..end).map(|start| bruteforce(start, end)));
+
+        for candidate in candidates {
+            let previous: Vec<(usize, f64)> = best[candidate.start]
+                .iter()
+                .map(|(count, (log10, _))| (*count, *log10))
+                .collect();
+
+            for (count, log10) in previous {
+                let total = log10 + candidate.log10;
+                let slot = best[end].entry(count + 1).or_insert((f64::MAX, None));
+                if total < slot.0 {
+                    *slot = (total, Some(candidate.clone()));
+                }
+            }
+        }
+    }
+
+    let (count, log10) = best[len]
+        .iter()
+        .map(|(count, (log10, _))| (*count, log10 + factorial_log10(*count)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+
+    // Walks back from the end to collect the chosen parts
+    let mut chosen = Vec::new();
+    let (mut end, mut count_left) = (len, count);
+    while end > 0 {
+        let part = best[end][&count_left].1.clone().unwrap();
+        end = part.start;
+        count_left -= 1;
+        chosen.push(part);
+    }
+    chosen.reverse();
+
+    (log10, chosen)
+}
+
+fn factorial_log10(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+fn bruteforce(start: usize, end: usize) -> Match {
+    let len = (end - start) as f64;
+    // Single characters are cheaper than longer random parts
+    let min = if end - start == 1 { 1.0 } else { 50f64.log10() };
+    Match {
+        start,
+        end,
+        pattern: Pattern::Bruteforce,
+        log10: (len * BRUTEFORCE_CARDINALITY.log10()).max(min),
+    }
+}
+
+fn ranked(list: &str) -> HashMap<&str, usize> {
+    list.lines()
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(index, word)| (word, index + 1))
+        .collect()
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let passwords = ranked(PASSWORDS);
+    let words = ranked(WORDS);
+    let inputs = user_words(user_inputs);
+    let longest = passwords
+        .keys()
+        .chain(words.keys())
+        .map(|word| word.len())
+        .chain(inputs.keys().map(|word| word.chars().count()))
+        .max()
+        .unwrap_or_default();
+    let mut matches = Vec::new();
+
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len().min(start + longest) {
+            let token = &chars[start..end];
+            let lower: String = token.iter().flat_map(|c| c.to_lowercase()).collect();
+            let reversed: String = lower.chars().rev().collect();
+            let case = uppercase_log10(token);
+
+            let mut candidates = vec![(lower.clone(), false, false), (reversed, false, true)];
+            candidates.extend(unl33t(&lower).into_iter().map(|word| (word, true, false)));
+
+            for (word, l33t, reversed) in candidates {
+                let variations = case
+                    + if l33t { l33t_log10(&lower) } else { 0.0 }
+                    + if reversed { 2f64.log10() } else { 0.0 };
+
+                let found = if let Some(rank) = inputs.get(word.as_str()) {
+                    Some((Pattern::UserInput, *rank))
+                } else if let Some(rank) = passwords.get(word.as_str()) {
+                    let pattern = match l33t || reversed {
+                        true => Pattern::Word { l33t, reversed },
+                        false => Pattern::CommonPassword,
+                    };
+                    Some((pattern, *rank))
+                } else {
+                    words
+                        .get(word.as_str())
+                        .map(|rank| (Pattern::Word { l33t, reversed }, *rank + passwords.len()))
+                };
+
+                if let Some((pattern, rank)) = found {
+                    matches.push(Match {
+                        start,
+                        end,
+                        pattern,
+                        log10: (rank as f64).log10() + variations,
+                    });
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+// Parts of the domain and username that are long enough to be worth trying
+fn user_words(user_inputs: &[&str]) -> HashMap<String, usize> {
+    let mut words = HashMap::new();
+    for input in user_inputs {
+        let input = input.to_lowercase();
+        let parts = std::iter::once(input.as_str())
+            .chain(input.split(|c: char| !c.is_alphanumeric()))
+            .filter(|part| part.chars().count() >= 3);
+
+        for part in parts {
+            let rank = words.len() + 1;
+            words.entry(part.to_string()).or_insert(rank);
+        }
+    }
+    words
+}
+
+// Capitalizing the first or every letter is the first thing tried, random case is costlier
+fn uppercase_log10(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        0.0
+    } else if lower == 0 || (upper == 1 && token[0].is_uppercase()) {
+        2f64.log10()
+    } else {
+        upper.min(lower) as f64 * 2f64.log10() + 1.0
+    }
+}
+
+fn l33t_table(c: char) -> &'static [char] {
+    match c {
+        '4' | '@' => &['a'],
+        '3' => &['e'],
+        '1' | '!' => &['i', 'l'],
+        '0' => &['o'],
+        '$' | '5' => &['s'],
+        '7' => &['t'],
+        _ => &[],
+    }
+}
+
+// Words the token could be with l33t substitutions undone
+fn unl33t(token: &str) -> Vec<String> {
+    let ambiguous = token.chars().filter(|c| l33t_table(*c).len() > 1).count();
+    if ambiguous > MAX_AMBIGUOUS_L33T || !token.chars().any(|c| !l33t_table(c).is_empty()) {
+        return Vec::new();
+    }
+
+    let mut variants = vec![String::new()];
+    for c in token.chars() {
+        let options = l33t_table(c);
+        variants = match options.is_empty() {
+            true => variants.into_iter().map(|v| v + &c.to_string()).collect(),
+            false => variants
+                .into_iter()
+                .flat_map(|v| options.iter().map(move |o| format!("{}{}", v, o)))
+                .collect(),
+        };
+    }
+    variants
+}
+
+fn l33t_log10(token: &str) -> f64 {
+    let substituted = token.chars().filter(|c| !l33t_table(*c).is_empty()).count();
+    substituted as f64 * 2f64.log10()
+}
+
+fn key_position(c: char) -> Option<(f64, f64)> {
+    let c = c.to_ascii_lowercase();
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        keys.chars()
+            .position(|key| key == c)
+            .map(|column| (row as f64, column as f64 + KEYBOARD_OFFSETS[row]))
+    })
+}
+
+// Direction from one key to its neighbour, None if they don't touch
+fn key_direction(from: char, to: char) -> Option<(i8, i8)> {
+    let (from_row, from_x) = key_position(from)?;
+    let (to_row, to_x) = key_position(to)?;
+    let (dy, dx) = (to_row - from_row, to_x - from_x);
+
+    let touching = (dy == 0.0 && dx.abs() == 1.0) || (dy.abs() == 1.0 && dx.abs() <= 1.0);
+    touching.then_some((dy as i8, dx.signum() as i8))
+}
+
+fn keyboard_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+
+    while start + 1 < chars.len() {
+        let mut end = start + 1;
+        let mut turns = 0;
+        let mut direction = None;
+
+        while end < chars.len() {
+            match key_direction(chars[end - 1], chars[end]) {
+                Some(next) => {
+                    if direction != Some(next) {
+                        turns += 1;
+                        direction = Some(next);
+                    }
+                    end += 1;
+                }
+                None => break,
+            }
+        }
+
+        if end - start >= 3 {
+            matches.push(Match {
+                start,
+                end,
+                pattern: Pattern::Keyboard,
+                log10: keyboard_log10(end - start, turns) + uppercase_log10(&chars[start..end]),
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+
+    matches
+}
+
+// Number of keyboard walks up to this length with at most this many turns
+fn keyboard_log10(len: usize, turns: usize) -> f64 {
+    let mut guesses = 0.0;
+    for i in 2..=len {
+        for j in 1..=turns.min(i - 1) {
+            guesses += binomial(i - 1, j - 1) * KEYBOARD_KEYS * KEYBOARD_DEGREE.powi(j as i32);
+        }
+    }
+    guesses.log10()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+// Runs of a repeated unit, like "aaa" or "abcabc". The shortest unit is estimated on its own.
+fn repeat_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let repeat = (1..=(chars.len() - start) / 2).find_map(|unit| {
+            let mut end = start + unit;
+            while end + unit <= chars.len() && chars[end..end + unit] == chars[start..start + unit]
+            {
+                end += unit;
+            }
+
+            let count = (end - start) / unit;
+            (count >= 3 || (unit > 1 && count == 2)).then_some((unit, end, count))
+        });
+
+        let Some((unit, end, count)) = repeat else {
+            start += 1;
+            continue;
+        };
+
+        let unit_text: String = chars[start..start + unit].iter().collect();
+        let unit_log10 = match unit {
+            1 => 1.0,
+            _ => estimate(&unit_text, user_inputs).guesses_log10,
+        };
+
+        matches.push(Match {
+            start,
+            end,
+            pattern: Pattern::Repeat,
+            log10: unit_log10 + (count as f64).log10(),
+        });
+        start = end;
+    }
+
+    matches
+}
+
+// Runs like "abc", "987" or "xyz" where every character is one step from the previous
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+
+    while start + 2 < chars.len() {
+        let step = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        while end < chars.len()
+            && step.abs() == 1
+            && chars[end] as i64 - chars[end - 1] as i64 == step
+            && same_class(chars[end], chars[start])
+        {
+            end += 1;
+        }
+
+        if end - start >= 3 {
+            let first = chars[start].to_ascii_lowercase();
+            let base = if ['a', 'z', '0', '1', '9'].contains(&first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let descending = if step < 0 { 2.0 } else { 1.0 };
+
+            matches.push(Match {
+                start,
+                end,
+                pattern: Pattern::Sequence,
+                log10: (base * descending * (end - start) as f64).log10(),
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+
+    matches
+}
+
+fn same_class(a: char, b: char) -> bool {
+    (a.is_ascii_digit() && b.is_ascii_digit())
+        || (a.is_ascii_lowercase() && b.is_ascii_lowercase())
+        || (a.is_ascii_uppercase() && b.is_ascii_uppercase())
+}
+
+fn current_year() -> i32 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    1970 + (seconds / 31_556_952) as i32
+}
+
+fn year_log10(year: i32) -> f64 {
+    ((year - current_year()).abs().max(MIN_YEAR_SPACE) as f64).log10()
+}
+
+// Years like 1990 and dates like 24.12.1990, 19901224 or 241290
+fn date_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    for start in 0..chars.len() {
+        for end in start + 4..=(start + 10).min(chars.len()) {
+            let token = &chars[start..end];
+            if !token[0].is_ascii_digit() || !token[token.len() - 1].is_ascii_digit() {
+                continue;
+            }
+
+            let separators: Vec<char> = token
+                .iter()
+                .filter(|c| !c.is_ascii_digit())
+                .copied()
+                .collect();
+            let digits: String = token.iter().filter(|c| c.is_ascii_digit()).collect();
+            let separated = match separators.as_slice() {
+                [] => false,
+                [a, b] if a == b && "/-._ ".contains(*a) => true,
+                _ => continue,
+            };
+
+            let log10 = if !separated && digits.len() == 4 {
+                digits
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|year| (1900..=2099).contains(year))
+                    .map(year_log10)
+            } else {
+                parse_date(&digits).map(|year| {
+                    365f64.log10() + year_log10(year) + if separated { 4f64.log10() } else { 0.0 }
+                })
+            };
+
+            if let Some(log10) = log10 {
+                matches.push(Match {
+                    start,
+                    end,
+                    pattern: Pattern::Date,
+                    log10,
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+// Year of the date written with day, month and year in any common order
+fn parse_date(digits: &str) -> Option<i32> {
+    let number = |range: std::ops::Range<usize>| digits.get(range)?.parse::<i32>().ok();
+    let valid = |day: i32, month: i32| (1..=31).contains(&day) && (1..=12).contains(&month);
+    let full_year = |year: i32| if year < 50 { 2000 + year } else { 1900 + year };
+
+    let candidates: Vec<(i32, i32, i32)> = match digits.len() {
+        8 => vec![
+            (number(6..8)?, number(4..6)?, number(0..4)?),
+            (number(0..2)?, number(2..4)?, number(4..8)?),
+            (number(2..4)?, number(0..2)?, number(4..8)?),
+        ],
+        6 => vec![
+            (number(0..2)?, number(2..4)?, full_year(number(4..6)?)),
+            (number(2..4)?, number(0..2)?, full_year(number(4..6)?)),
+            (number(4..6)?, number(2..4)?, full_year(number(0..2)?)),
+        ],
+        _ => return None,
+    };
+
+    candidates
+        .into_iter()
+        .find(|(day, month, year)| valid(*day, *month) && (1900..=2099).contains(year))
+        .map(|(_, _, year)| year)
+}
+
+// Score below the configured minimum blocks the password when strength.block is 1, and
+// only warns otherwise
+pub fn check(password: &str, user_inputs: &[&str]) -> Result<Option<String>, String> {
+    let minimum = config::get_number("strength.min_score");
+    let estimate = estimate(password, user_inputs);
+
+    if estimate.score as u64 >= minimum {
+        return Ok(None);
+    }
+
+    let message = format!(
+        "Password is weak, score {} of {} is below the minimum {}. {}",
+        estimate.score,
+        MAX_SCORE,
+        minimum,
+        estimate.feedback().join(" ")
+    );
+
+    match config::get_number("strength.block") {
+        0 => Ok(Some(message)),
+        _ => Err(message),
+    }
+}
+
+#[cfg(test)]
+mod test;
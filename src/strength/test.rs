@@ -0,0 +1,94 @@
+use super::*;
+use crate::storage;
+
+fn score(password: &str) -> u8 {
+    estimate(password, &[]).score
+}
+
+#[test]
+fn test_common_passwords() {
+    for password in [
+        "password", "123456", "qwerty", "letmein", "P@ssw0rd", "drowssap",
+    ] {
+        assert_eq!(score(password), 0, "{}", password);
+    }
+}
+
+#[test]
+fn test_patterns() {
+    let cases = [
+        ("zxcvbnm,./", Pattern::Keyboard),
+        ("aaaaaaaaaaaa", Pattern::Repeat),
+        ("mnopqrstu", Pattern::Sequence),
+        ("24.12.1990", Pattern::Date),
+        (
+            "tigerriver",
+            Pattern::Word {
+                l33t: false,
+                reversed: false,
+            },
+        ),
+    ];
+
+    for (password, pattern) in cases {
+        let estimate = estimate(password, &[]);
+        assert!(estimate.score <= 1, "{} {}", password, estimate.score);
+        assert!(
+            estimate.patterns.contains(&pattern),
+            "{} {:?}",
+            password,
+            estimate.patterns
+        );
+    }
+}
+
+#[test]
+fn test_strong_passwords() {
+    for password in [
+        "k8#Qz!v2Lp@9xW",
+        "correct horse battery staple",
+        "vG7^rT2-mB9qZ",
+    ] {
+        assert_eq!(score(password), MAX_SCORE, "{}", password);
+    }
+}
+
+#[test]
+fn test_user_inputs() {
+    let without = estimate("gmailjohn77", &[]);
+    let with = estimate("gmailjohn77", &["gmail", "john@example.com"]);
+
+    assert!(with.guesses_log10 < without.guesses_log10);
+    assert!(with.patterns.contains(&Pattern::UserInput));
+}
+
+#[test]
+fn test_feedback_hides_password() {
+    let estimate = estimate("Summer1990", &[]);
+    let feedback = estimate.feedback().join(" ").to_lowercase();
+
+    assert!(estimate.score <= 1);
+    assert!(feedback.contains("date"));
+    assert!(!feedback.contains("summer") && !feedback.contains("1990"));
+}
+
+#[test]
+fn test_long_password() {
+    let password = "1!".repeat(200);
+    assert!(estimate(&password, &[]).score <= 2);
+}
+
+#[test]
+fn test_check() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    assert!(check("k8#Qz!v2Lp@9xW", &[]).unwrap().is_none());
+    assert!(check("password", &[]).unwrap().is_some());
+
+    config::set("strength.block", "1").unwrap();
+    assert!(check("password", &[]).is_err());
+
+    config::set("strength.min_score", "0").unwrap();
+    assert!(check("password", &[]).unwrap().is_none());
+}
@@ -0,0 +1,525 @@
+use super::{entries, save, Entries, SyncEntry};
+use crate::backup;
+use crate::entry::{index_entry::IndexEntry, write_string, BinarySerialization, ByteReader};
+use crate::storage;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Every device writes only its own file into the shared directory and reads files of the
+// other devices, so a sync tool never has two versions of one file to choose from
+pub const SYNC_MAGIC: &[u8; 8] = b"DOMESYNC";
+pub const SYNC_VERSION: u8 = 1;
+
+const EXTENSION: &str = "dsync";
+
+// Device -> number of changes of the entry made on that device. Version that has every
+// counter at least as high as the other one is newer, otherwise the changes were
+// concurrent.
+pub type Versions = BTreeMap<String, u64>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Order {
+    Same,
+    Newer,
+    Older,
+    Concurrent,
+}
+
+#[derive(Clone, Debug)]
+pub struct Revision {
+    pub versions: Versions,
+    // Checksum of the entry, empty when the entry was removed
+    pub fingerprint: Vec<u8>,
+}
+
+// Revision of an entry together with the entry itself, None when it was removed
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub revision: Revision,
+    pub entry: Option<SyncEntry>,
+}
+
+pub struct Conflict {
+    pub domain: String,
+    pub device: String,
+    pub change: Change,
+}
+
+// What this device knows about the sync. It stays on the device, it is never shared.
+pub struct State {
+    pub device: String,
+    pub dir: Option<PathBuf>,
+    pub revisions: BTreeMap<String, Revision>,
+    // Checksums of files of other devices that were already merged
+    pub peers: BTreeMap<String, Vec<u8>>,
+}
+
+pub struct Report {
+    pub updated: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+pub fn compare(a: &Versions, b: &Versions) -> Order {
+    let covers = |a: &Versions, b: &Versions| {
+        b.iter()
+            .all(|(device, count)| a.get(device).unwrap_or(&0) >= count)
+    };
+
+    match (covers(a, b), covers(b, a)) {
+        (true, true) => Order::Same,
+        (true, false) => Order::Newer,
+        (false, true) => Order::Older,
+        (false, false) => Order::Concurrent,
+    }
+}
+
+// Highest counter of every device from both versions
+pub fn combine(a: &Versions, b: &Versions) -> Versions {
+    let mut versions = a.clone();
+    for (device, count) in b {
+        let current = versions.entry(device.clone()).or_default();
+        *current = (*current).max(*count);
+    }
+    versions
+}
+
+pub fn fingerprint(entry: &SyncEntry) -> Vec<u8> {
+    let index_entry = &entry.index_entry;
+
+    let mut data = entry.record.clone();
+    write_string(&mut data, &index_entry.folder);
+    write_string(&mut data, &index_entry.tags.join(";"));
+    write_string(&mut data, &index_entry.url);
+    // Left out when unset, so entries keep the fingerprint they had before policies existed
+    if index_entry.expiry_days > 0 {
+        data.extend(index_entry.expiry_days.to_le_bytes());
+    }
+    Sha256::digest(data).to_vec()
+}
+
+impl State {
+    pub fn new() -> State {
+        let device: String = rand::random::<[u8; 8]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        State {
+            device,
+            dir: None,
+            revisions: BTreeMap::new(),
+            peers: BTreeMap::new(),
+        }
+    }
+
+    pub fn read() -> Result<State, String> {
+        match fs::read(storage::sync_state_file_path()) {
+            Ok(data) => decode_state(&data).ok_or_else(|| String::from("Sync state is damaged.")),
+            Err(_) => Ok(State::new()),
+        }
+    }
+
+    pub fn write(&self) -> Result<(), String> {
+        storage::replace_file(&storage::sync_state_file_path(), &encode_state(self))
+    }
+
+    // Counts changes of the live vault made since the last sync as changes of this device
+    pub fn refresh(&mut self, local: &Entries) {
+        for (domain, entry) in local {
+            let fingerprint = fingerprint(entry);
+            if self.revisions.get(domain).map(|r| &r.fingerprint) != Some(&fingerprint) {
+                self.bump(domain, fingerprint);
+            }
+        }
+
+        let removed: Vec<String> = self
+            .revisions
+            .iter()
+            .filter(|(domain, revision)| {
+                !revision.fingerprint.is_empty() && !local.contains_key(*domain)
+            })
+            .map(|(domain, _)| domain.clone())
+            .collect();
+
+        for domain in removed {
+            self.bump(&domain, Vec::new());
+        }
+    }
+
+    fn bump(&mut self, domain: &str, fingerprint: Vec<u8>) {
+        let revision = self
+            .revisions
+            .entry(domain.to_string())
+            .or_insert_with(|| Revision {
+                versions: Versions::new(),
+                fingerprint: Vec::new(),
+            });
+
+        *revision.versions.entry(self.device.clone()).or_default() += 1;
+        revision.fingerprint = fingerprint;
+    }
+
+    fn file_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.{}", self.device, EXTENSION))
+    }
+}
+
+// Sets the shared directory, the directory of a sync tool or a shared drive
+pub fn set_dir(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path).map_err(|err| format!("Can't create {}: {}", path.display(), err))?;
+    let dir = fs::canonicalize(path).map_err(|err| err.to_string())?;
+
+    let mut state = State::read()?;
+    state.dir = Some(dir);
+    state.write()
+}
+
+pub fn is_configured() -> bool {
+    State::read().is_ok_and(|state| state.dir.is_some())
+}
+
+// Merges files of other devices that changed since the last sync into the live vault and
+// publishes the vault of this device. Concurrent changes of one entry are not merged, the
+// version of the other device is saved as a conflict.
+pub fn sync(master_password: &str) -> Result<Report, String> {
+    let mut state = State::read()?;
+    let dir = match &state.dir {
+        Some(dir) => dir.clone(),
+        None => return Err(String::from("Shared directory is not set.")),
+    };
+
+    let (records, index) = storage::open().snapshot()?;
+    let mut local = entries(&records, &index)?;
+    state.refresh(&local);
+
+    let mut conflicts = read_conflicts()?;
+    let mut updated = Vec::new();
+
+    for (peer, path) in peer_files(&dir, &state.device)? {
+        let data =
+            fs::read(&path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+        let checksum = Sha256::digest(&data).to_vec();
+        if state.peers.get(&peer) == Some(&checksum) {
+            continue;
+        }
+
+        let (device, changes) =
+            decode_changes(&data).ok_or_else(|| format!("{} is damaged.", path.display()))?;
+
+        for (domain, theirs) in changes {
+            let mine = state.revisions.get(&domain);
+            let order = match mine {
+                Some(mine) => compare(&mine.versions, &theirs.revision.versions),
+                None => Order::Older,
+            };
+
+            match order {
+                Order::Older => {
+                    conflicts.retain(|c| {
+                        c.domain != domain
+                            || compare(&theirs.revision.versions, &c.change.revision.versions)
+                                == Order::Older
+                    });
+
+                    match &theirs.entry {
+                        Some(entry) => local.insert(domain.clone(), entry.clone()),
+                        None => local.remove(&domain),
+                    };
+                    state.revisions.insert(domain.clone(), theirs.revision);
+                    updated.push(domain);
+                }
+                Order::Concurrent if mine.unwrap().fingerprint == theirs.revision.fingerprint => {
+                    // Both devices made the same change
+                    let revision = state.revisions.get_mut(&domain).unwrap();
+                    revision.versions = combine(&revision.versions, &theirs.revision.versions);
+                }
+                Order::Concurrent => {
+                    conflicts.retain(|c| c.domain != domain || c.device != device);
+                    conflicts.push(Conflict {
+                        domain,
+                        device: device.clone(),
+                        change: theirs,
+                    });
+                }
+                Order::Same | Order::Newer => {}
+            }
+        }
+
+        state.peers.insert(peer, checksum);
+    }
+
+    if !updated.is_empty() {
+        backup::create(master_password, "sync")?;
+        save(&local, master_password)?;
+    }
+
+    publish(&state, &dir, &local)?;
+    write_conflicts(&conflicts)?;
+    state.write()?;
+
+    let mut conflicts: Vec<String> = conflicts.into_iter().map(|c| c.domain).collect();
+    conflicts.dedup();
+
+    Ok(Report { updated, conflicts })
+}
+
+pub fn conflicts() -> Result<Vec<Conflict>, String> {
+    read_conflicts()
+}
+
+// Resolves the conflict of the entry. The kept version gets a revision newer than both
+// versions, so other devices take it without another conflict.
+pub fn resolve(domain: &str, keep_other: bool, master_password: &str) -> Result<(), String> {
+    let mut conflicts = read_conflicts()?;
+    let position = conflicts
+        .iter()
+        .position(|c| c.domain == domain)
+        .ok_or_else(|| format!("{} has no conflict.", domain))?;
+    let conflict = conflicts.remove(position);
+
+    let mut state = State::read()?;
+    let (records, index) = storage::open().snapshot()?;
+    let mut local = entries(&records, &index)?;
+    state.refresh(&local);
+
+    let revision = state.revisions.get_mut(domain).unwrap();
+    revision.versions = combine(&revision.versions, &conflict.change.revision.versions);
+
+    if keep_other {
+        backup::create(master_password, "conflict")?;
+
+        match &conflict.change.entry {
+            Some(entry) => local.insert(domain.to_string(), entry.clone()),
+            None => local.remove(domain),
+        };
+        revision.fingerprint = conflict.change.revision.fingerprint.clone();
+
+        save(&local, master_password)?;
+    }
+
+    state.bump(domain, state.revisions[domain].fingerprint.clone());
+
+    if let Some(dir) = &state.dir {
+        publish(&state, dir, &local)?;
+    }
+
+    write_conflicts(&conflicts)?;
+    state.write()
+}
+
+fn publish(state: &State, dir: &Path, local: &Entries) -> Result<(), String> {
+    let changes: BTreeMap<String, Change> = state
+        .revisions
+        .iter()
+        .map(|(domain, revision)| {
+            let change = Change {
+                revision: revision.clone(),
+                entry: local.get(domain).cloned(),
+            };
+            (domain.clone(), change)
+        })
+        .collect();
+
+    storage::replace_file(
+        &state.file_path(dir),
+        &encode_changes(&state.device, &changes),
+    )
+}
+
+// Files of other devices in the shared directory
+fn peer_files(dir: &Path, device: &str) -> Result<Vec<(String, PathBuf)>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("Can't read {}: {}", dir.display(), err))?;
+
+    let mut files: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path.clone())))
+        .filter(|(name, _)| name != device)
+        .collect();
+
+    files.sort();
+    Ok(files)
+}
+
+fn read_conflicts() -> Result<Vec<Conflict>, String> {
+    let data = match fs::read(storage::conflicts_file_path()) {
+        Ok(data) => data,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let damaged = || String::from("List of conflicts is damaged.");
+    let mut reader = ByteReader::new(&data);
+    let count = reader.read_u32().ok_or_else(damaged)?;
+
+    (0..count)
+        .map(|_| {
+            Some(Conflict {
+                domain: reader.read_string()?,
+                device: reader.read_string()?,
+                change: read_change(&mut reader)?,
+            })
+        })
+        .collect::<Option<Vec<Conflict>>>()
+        .ok_or_else(damaged)
+}
+
+fn write_conflicts(conflicts: &[Conflict]) -> Result<(), String> {
+    let mut data = (conflicts.len() as u32).to_le_bytes().to_vec();
+    for conflict in conflicts {
+        write_string(&mut data, &conflict.domain);
+        write_string(&mut data, &conflict.device);
+        write_change(&mut data, &conflict.change);
+    }
+
+    storage::replace_file(&storage::conflicts_file_path(), &data)
+}
+
+pub fn encode_changes(device: &str, changes: &BTreeMap<String, Change>) -> Vec<u8> {
+    let mut data = SYNC_MAGIC.to_vec();
+    data.push(SYNC_VERSION);
+    write_string(&mut data, device);
+    data.extend((changes.len() as u32).to_le_bytes());
+
+    for (domain, change) in changes {
+        write_string(&mut data, domain);
+        write_change(&mut data, change);
+    }
+
+    data
+}
+
+pub fn decode_changes(data: &[u8]) -> Option<(String, BTreeMap<String, Change>)> {
+    if !data.starts_with(SYNC_MAGIC) {
+        return None;
+    }
+
+    let mut reader = ByteReader::new(&data[SYNC_MAGIC.len()..]);
+    if reader.read_u8()? > SYNC_VERSION {
+        return None;
+    }
+
+    let device = reader.read_string()?;
+    let count = reader.read_u32()?;
+    let changes = (0..count)
+        .map(|_| Some((reader.read_string()?, read_change(&mut reader)?)))
+        .collect::<Option<_>>()?;
+
+    Some((device, changes))
+}
+
+fn write_revision(data: &mut Vec<u8>, revision: &Revision) {
+    data.push(revision.fingerprint.len() as u8);
+    data.extend(&revision.fingerprint);
+    data.extend((revision.versions.len() as u16).to_le_bytes());
+
+    for (device, count) in &revision.versions {
+        write_string(data, device);
+        data.extend(count.to_le_bytes());
+    }
+}
+
+fn read_revision(reader: &mut ByteReader) -> Option<Revision> {
+    let len = reader.read_u8()? as usize;
+    let fingerprint = reader.read_bytes(len)?.to_vec();
+
+    let count = reader.read_u16()?;
+    let versions = (0..count)
+        .map(|_| Some((reader.read_string()?, reader.read_u64()?)))
+        .collect::<Option<_>>()?;
+
+    Some(Revision {
+        versions,
+        fingerprint,
+    })
+}
+
+fn write_change(data: &mut Vec<u8>, change: &Change) {
+    write_revision(data, &change.revision);
+
+    match &change.entry {
+        Some(entry) => {
+            let index_entry = entry.index_entry.serialize();
+            data.push(1);
+            data.extend((index_entry.len() as u32).to_le_bytes());
+            data.extend(index_entry);
+            data.extend((entry.record.len() as u32).to_le_bytes());
+            data.extend(&entry.record);
+        }
+        None => data.push(0),
+    }
+}
+
+fn read_change(reader: &mut ByteReader) -> Option<Change> {
+    let revision = read_revision(reader)?;
+
+    let entry = match reader.read_u8()? {
+        0 => None,
+        _ => {
+            let len = reader.read_u32()? as usize;
+            let index_entry = IndexEntry::read(reader.read_bytes(len)?)?;
+            let len = reader.read_u32()? as usize;
+            let record = reader.read_bytes(len)?.to_vec();
+            Some(SyncEntry {
+                index_entry,
+                record,
+            })
+        }
+    };
+
+    Some(Change { revision, entry })
+}
+
+fn encode_state(state: &State) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_string(&mut data, &state.device);
+    write_string(
+        &mut data,
+        &state
+            .dir
+            .as_ref()
+            .map_or(String::new(), |dir| dir.display().to_string()),
+    );
+
+    data.extend((state.revisions.len() as u32).to_le_bytes());
+    for (domain, revision) in &state.revisions {
+        write_string(&mut data, domain);
+        write_revision(&mut data, revision);
+    }
+
+    data.extend((state.peers.len() as u32).to_le_bytes());
+    for (peer, checksum) in &state.peers {
+        write_string(&mut data, peer);
+        data.extend(checksum);
+    }
+
+    data
+}
+
+fn decode_state(data: &[u8]) -> Option<State> {
+    let mut reader = ByteReader::new(data);
+    let device = reader.read_string()?;
+    let dir = reader.read_string()?;
+
+    let count = reader.read_u32()?;
+    let revisions = (0..count)
+        .map(|_| Some((reader.read_string()?, read_revision(&mut reader)?)))
+        .collect::<Option<_>>()?;
+
+    let count = reader.read_u32()?;
+    let peers = (0..count)
+        .map(|_| Some((reader.read_string()?, reader.read_bytes(32)?.to_vec())))
+        .collect::<Option<_>>()?;
+
+    Some(State {
+        device,
+        dir: (!dir.is_empty()).then(|| PathBuf::from(dir)),
+        revisions,
+        peers,
+    })
+}
+
+#[cfg(test)]
+mod test;
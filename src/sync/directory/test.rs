@@ -0,0 +1,225 @@
+use super::*;
+use crate::command::{self, EntryChanges, NewEntry};
+use tempfile::TempDir;
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+
+struct Devices {
+    shared: TempDir,
+    first: TempDir,
+    second: TempDir,
+}
+
+impl Devices {
+    fn new() -> Devices {
+        let devices = Devices {
+            shared: TempDir::new().unwrap(),
+            first: TempDir::new().unwrap(),
+            second: TempDir::new().unwrap(),
+        };
+
+        for dir in [&devices.first, &devices.second] {
+            storage::use_dir(dir.path());
+            set_dir(devices.shared.path()).unwrap();
+        }
+
+        devices
+    }
+
+    fn first(&self) {
+        storage::use_dir(self.first.path());
+    }
+
+    fn second(&self) {
+        storage::use_dir(self.second.path());
+    }
+}
+
+fn versions(pairs: &[(&str, u64)]) -> Versions {
+    pairs
+        .iter()
+        .map(|(device, count)| (device.to_string(), *count))
+        .collect()
+}
+
+fn add(domain: &str, password: &str) {
+    let entry = NewEntry {
+        domain: String::from(domain),
+        username: String::from("john"),
+        password: String::from(password),
+        url: String::new(),
+        tags: Vec::new(),
+        folder: String::new(),
+    };
+    command::put_entry(&entry, MASTER_PASSWORD).unwrap();
+}
+
+fn password(domain: &str) -> String {
+    command::get_entry(domain, MASTER_PASSWORD).unwrap().1
+}
+
+fn edit(domain: &str, password: &str) {
+    let changes = EntryChanges {
+        password: Some(String::from(password)),
+        ..Default::default()
+    };
+    command::edit_entry(domain, &changes, MASTER_PASSWORD).unwrap();
+}
+
+#[test]
+fn test_compare() {
+    let a = versions(&[("a", 2), ("b", 1)]);
+
+    assert_eq!(compare(&a, &a), Order::Same);
+    assert_eq!(compare(&a, &versions(&[("a", 1)])), Order::Newer);
+    assert_eq!(compare(&a, &versions(&[("a", 2), ("b", 2)])), Order::Older);
+    assert_eq!(
+        compare(&a, &versions(&[("a", 1), ("c", 1)])),
+        Order::Concurrent
+    );
+    assert_eq!(
+        combine(&a, &versions(&[("a", 1), ("c", 1)])),
+        versions(&[("a", 2), ("b", 1), ("c", 1)])
+    );
+}
+
+#[test]
+fn test_changes_of_both_devices_are_merged() {
+    let devices = Devices::new();
+
+    devices.first();
+    add("gmail", "gm41l");
+    add("github", "g1thub");
+    sync(MASTER_PASSWORD).unwrap();
+
+    devices.second();
+    add("aws", "4ws");
+    assert_eq!(
+        sync(MASTER_PASSWORD).unwrap().updated,
+        vec!["github", "gmail"]
+    );
+
+    // Different entries changed on both devices
+    edit("gmail", "gm41l-2");
+    devices.first();
+    command::remove_entry("github", MASTER_PASSWORD).unwrap();
+
+    devices.second();
+    sync(MASTER_PASSWORD).unwrap();
+    devices.first();
+    let report = sync(MASTER_PASSWORD).unwrap();
+    assert_eq!(report.updated, vec!["aws", "gmail"]);
+    assert!(report.conflicts.is_empty());
+
+    devices.second();
+    sync(MASTER_PASSWORD).unwrap();
+    for device in [Devices::first, Devices::second] {
+        device(&devices);
+        assert_eq!(command::get_index_set().unwrap().data.len(), 2);
+        assert_eq!(password("gmail"), "gm41l-2");
+        assert_eq!(password("aws"), "4ws");
+    }
+}
+
+#[test]
+fn test_concurrent_edit_is_a_conflict() {
+    let devices = Devices::new();
+
+    devices.first();
+    add("gmail", "gm41l");
+    sync(MASTER_PASSWORD).unwrap();
+    devices.second();
+    sync(MASTER_PASSWORD).unwrap();
+
+    edit("gmail", "second");
+    sync(MASTER_PASSWORD).unwrap();
+    devices.first();
+    edit("gmail", "first");
+    let report = sync(MASTER_PASSWORD).unwrap();
+
+    // Nothing is overwritten until the conflict is resolved
+    assert_eq!(report.conflicts, vec!["gmail"]);
+    assert_eq!(password("gmail"), "first");
+    assert_eq!(conflicts().unwrap()[0].device.len(), 16);
+
+    resolve("gmail", true, MASTER_PASSWORD).unwrap();
+    assert_eq!(password("gmail"), "second");
+    assert!(conflicts().unwrap().is_empty());
+
+    // Second device sees the conflict too, the resolution wins over it
+    devices.second();
+    let report = sync(MASTER_PASSWORD).unwrap();
+    assert!(report.conflicts.is_empty());
+    assert_eq!(password("gmail"), "second");
+    assert!(resolve("gmail", false, MASTER_PASSWORD).is_err());
+}
+
+#[test]
+fn test_same_change_on_both_devices() {
+    let devices = Devices::new();
+
+    devices.first();
+    add("gmail", "gm41l");
+    sync(MASTER_PASSWORD).unwrap();
+    devices.second();
+    sync(MASTER_PASSWORD).unwrap();
+
+    command::remove_entry("gmail", MASTER_PASSWORD).unwrap();
+    sync(MASTER_PASSWORD).unwrap();
+    devices.first();
+    command::remove_entry("gmail", MASTER_PASSWORD).unwrap();
+
+    assert!(sync(MASTER_PASSWORD).unwrap().conflicts.is_empty());
+}
+
+#[test]
+fn test_changes_format() {
+    let mut changes = BTreeMap::new();
+    let revision = Revision {
+        versions: versions(&[("a", 3)]),
+        fingerprint: Vec::new(),
+    };
+    changes.insert(
+        String::from("gmail"),
+        Change {
+            revision,
+            entry: None,
+        },
+    );
+
+    let (device, decoded) = decode_changes(&encode_changes("a", &changes)).unwrap();
+
+    assert_eq!(device, "a");
+    assert_eq!(decoded["gmail"].revision.versions, versions(&[("a", 3)]));
+    assert!(decoded["gmail"].entry.is_none());
+    assert!(decode_changes(b"DOMESYNC").is_none());
+}
+
+#[test]
+fn test_damaged_entry_in_changes() {
+    let mut changes = BTreeMap::new();
+    let index_entry = IndexEntry::new("gmail", 0);
+    changes.insert(
+        String::from("gmail"),
+        Change {
+            revision: Revision {
+                versions: versions(&[("a", 1)]),
+                fingerprint: Vec::new(),
+            },
+            entry: Some(SyncEntry {
+                index_entry: index_entry.clone(),
+                record: vec![1, 2, 3],
+            }),
+        },
+    );
+
+    let mut data = encode_changes("a", &changes);
+    let serialized = index_entry.serialize();
+    let start = data
+        .windows(serialized.len())
+        .position(|window| window == serialized.as_slice())
+        .unwrap();
+    data[start..start + serialized.len()].fill(0xff);
+
+    assert!(decode_changes(&data).is_none());
+}
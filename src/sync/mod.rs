@@ -0,0 +1,339 @@
+use crate::backup;
+use crate::crypto::{self, Cipher};
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::{index_entry::IndexEntry, vault_entry::Entry, BinarySerialization};
+use crate::storage::{self, memory::MemoryBackend, single_file, StorageBackend};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+pub mod directory;
+
+// Vault is stored in the repository as one single-file vault without garbage
+const VAULT_NAME: &str = "vault.db";
+const REMOTE: &str = "origin";
+const BRANCH: &str = "main";
+
+// Entry of the vault with its encrypted record. Position of the record differs between
+// machines, so entries are matched by domain.
+#[derive(Clone, Debug)]
+pub struct SyncEntry {
+    pub index_entry: IndexEntry,
+    pub record: Vec<u8>,
+}
+
+pub type Entries = BTreeMap<String, SyncEntry>;
+
+pub struct Merged {
+    pub entries: Entries,
+    // Domains changed on both sides in different ways, local version was kept
+    pub conflicts: Vec<String>,
+}
+
+pub struct Report {
+    pub remote: Option<String>,
+    pub updated: bool,
+    pub conflicts: Vec<String>,
+}
+
+impl SyncEntry {
+    fn same(&self, other: &SyncEntry) -> bool {
+        let (a, b) = (&self.index_entry, &other.index_entry);
+        self.record == other.record
+            && a.folder == b.folder
+            && a.tags == b.tags
+            && a.url == b.url
+            && a.expiry_days == b.expiry_days
+    }
+}
+
+fn same(a: Option<&SyncEntry>, b: Option<&SyncEntry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same(b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Live entries of records and index
+pub fn entries(records: &[u8], index: &[u8]) -> Result<Entries, String> {
+    let backend = MemoryBackend::from_parts(records.to_vec(), index.to_vec());
+
+    Ok(backend
+        .list()?
+        .data
+        .into_iter()
+        .map(|index_entry| {
+            let record = backend.read_entry(index_entry.value);
+            (
+                index_entry.key.clone(),
+                SyncEntry {
+                    index_entry,
+                    record,
+                },
+            )
+        })
+        .collect())
+}
+
+// Records and index with the entries written one after another. The index is not sealed.
+pub fn build(entries: &Entries) -> (Vec<u8>, IndexSet) {
+    let mut records = Vec::new();
+    let mut index_set = IndexSet::new();
+
+    for entry in entries.values() {
+        let mut index_entry = entry.index_entry.clone();
+        index_entry.value = records.len() as u64;
+        records.extend(&entry.record);
+        index_set.add(&index_entry);
+    }
+
+    (records, index_set)
+}
+
+// Replaces the live vault with the merged entries. The index keeps the cipher and the number
+// of logged events of the vault and is sealed with the master password, so every entry that came from the other side has
+// to decrypt with it first. Otherwise a tampered index would be sealed as if it was valid.
+pub fn save(entries: &Entries, master_password: &str) -> Result<(), String> {
+    let (records, index) = storage::open().snapshot()?;
+    let live = self::entries(&records, &index)?;
+
+    for (domain, entry) in entries {
+        if !same(Some(entry), live.get(domain)) {
+            check(entry, master_password).map_err(|_| {
+                format!(
+                    "{} from the other side doesn't open with the master password, the merge was rejected.",
+                    domain
+                )
+            })?;
+        }
+    }
+
+    let (records, mut index_set) = build(entries);
+    let live_set = storage::open().list()?;
+    index_set.cipher = live_set.cipher;
+    index_set.logged_events = live_set.logged_events;
+    index_set.seal(master_password);
+    storage::open().replace(&records, &index_set.serialize())
+}
+
+// Decrypts the record with its domain, username and ID as associated data
+fn check(entry: &SyncEntry, master_password: &str) -> Result<(), String> {
+    let record = &entry.record;
+    let readable = match storage::record_size(record) == Some(record.len()) {
+        false => false,
+        true if record[28] == 0 => record[29] > 0 && Cipher::from_id(record[30]).is_some(),
+        true => true,
+    };
+    if !readable {
+        return Err(String::from("Record is damaged."));
+    }
+
+    let index_entry = &entry.index_entry;
+    let record = Entry::deserialize(&entry.record);
+    let key = crypto::derive_key(master_password, &record.salt);
+    let aad = crypto::associated_data(&index_entry.key, &record.username, &index_entry.id);
+    crypto::decrypt(record.cipher, &key, &record.nonce, &record.password, &aad).map(|_| ())
+}
+
+// Three-way merge by domain. Side that changed an entry since the base wins, when both
+// changed it differently the local version is kept. Entry removed on one side and changed
+// on the other is kept, so no password is lost.
+pub fn merge(base: &Entries, local: &Entries, remote: &Entries) -> Merged {
+    let domains: BTreeSet<&String> = base
+        .keys()
+        .chain(local.keys())
+        .chain(remote.keys())
+        .collect();
+    let mut entries = Entries::new();
+    let mut conflicts = Vec::new();
+
+    for domain in domains {
+        let (base, local, remote) = (base.get(domain), local.get(domain), remote.get(domain));
+
+        let entry = if same(local, remote) || same(remote, base) {
+            local
+        } else if same(local, base) {
+            remote
+        } else {
+            conflicts.push(domain.clone());
+            local.or(remote)
+        };
+
+        if let Some(entry) = entry {
+            entries.insert(domain.clone(), entry.clone());
+        }
+    }
+
+    Merged { entries, conflicts }
+}
+
+// Sets remote repository the vault is pushed to and pulled from
+pub fn set_remote(url: &str) -> Result<(), String> {
+    let dir = storage::sync_dir_path();
+    init(&dir)?;
+
+    match remote_url(&dir) {
+        Some(_) => git(&dir, &["remote", "set-url", REMOTE, url]).map(|_| ()),
+        None => git(&dir, &["remote", "add", REMOTE, url]).map(|_| ()),
+    }
+}
+
+// Commits the live vault, merges changes of the remote and pushes the result. Live vault
+// is replaced only when the merge brought something new, and a backup is saved first.
+pub fn sync(master_password: &str) -> Result<Report, String> {
+    let dir = storage::sync_dir_path();
+    init(&dir)?;
+
+    let (records, index) = storage::open().snapshot()?;
+    let local = entries(&records, &index)?;
+    commit(&dir, &local, "Update vault")?;
+
+    let remote = remote_url(&dir);
+    let mut conflicts = Vec::new();
+
+    if remote.is_some() {
+        git(&dir, &["fetch", "-q", REMOTE])?;
+        let tracking = format!("{}/{}", REMOTE, BRANCH);
+
+        if git(&dir, &["rev-parse", "-q", "--verify", &tracking]).is_ok()
+            && !is_ancestor(&dir, &tracking, "HEAD")
+        {
+            if is_ancestor(&dir, "HEAD", &tracking) {
+                git(&dir, &["merge", "-q", "--ff-only", &tracking])?;
+            } else {
+                conflicts = merge_remote(&dir, &tracking)?;
+            }
+        }
+
+        git(&dir, &["push", "-q", REMOTE, &format!("HEAD:{}", BRANCH)])?;
+    }
+
+    let merged = read_vault(&dir, None)?;
+    let updated = merged.len() != local.len()
+        || merged
+            .iter()
+            .any(|(domain, entry)| !same(Some(entry), local.get(domain)));
+
+    if updated {
+        backup::create(master_password, "sync")?;
+        save(&merged, master_password)?;
+    }
+
+    Ok(Report {
+        remote,
+        updated,
+        conflicts,
+    })
+}
+
+// Merge commit with both histories as parents and the entries merged by Dome
+fn merge_remote(dir: &Path, tracking: &str) -> Result<Vec<String>, String> {
+    let base = match git(dir, &["merge-base", "HEAD", tracking]) {
+        Ok(base) => read_vault(dir, Some(&base))?,
+        Err(_) => Entries::new(),
+    };
+    let local = read_vault(dir, Some("HEAD"))?;
+    let remote = read_vault(dir, Some(tracking))?;
+    let merged = merge(&base, &local, &remote);
+
+    git(
+        dir,
+        &[
+            "merge",
+            "-q",
+            "--no-commit",
+            "--allow-unrelated-histories",
+            "-s",
+            "ours",
+            tracking,
+        ],
+    )?;
+    commit(dir, &merged.entries, "Merge vault")?;
+
+    Ok(merged.conflicts)
+}
+
+fn init(dir: &Path) -> Result<(), String> {
+    if dir.join(".git").exists() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|err| format!("Can't create {}: {}", dir.display(), err))?;
+    git(dir, &["init", "-q"])?;
+    git(
+        dir,
+        &["symbolic-ref", "HEAD", &format!("refs/heads/{}", BRANCH)],
+    )?;
+
+    // Commits need an author even on machines where git was never configured
+    if git(dir, &["config", "user.name"]).is_err() {
+        git(dir, &["config", "user.name", "Dome"])?;
+        git(dir, &["config", "user.email", "dome@localhost"])?;
+    }
+
+    Ok(())
+}
+
+fn commit(dir: &Path, entries: &Entries, message: &str) -> Result<(), String> {
+    let (records, index_set) = build(entries);
+    storage::replace_file(
+        &dir.join(VAULT_NAME),
+        &single_file::encode(&records, &index_set.serialize()),
+    )?;
+    git(dir, &["add", VAULT_NAME])?;
+
+    let merging = dir.join(".git/MERGE_HEAD").exists();
+    let unchanged = git(dir, &["diff", "--cached", "--quiet"]).is_ok();
+    if unchanged && !merging {
+        return Ok(());
+    }
+
+    git(dir, &["commit", "-q", "-m", message]).map(|_| ())
+}
+
+// Entries of the vault in the working tree, or in the given revision
+fn read_vault(dir: &Path, revision: Option<&str>) -> Result<Entries, String> {
+    let data = match revision {
+        Some(revision) => git_output(dir, &["show", &format!("{}:{}", revision, VAULT_NAME)])?,
+        None => fs::read(dir.join(VAULT_NAME)).map_err(|err| err.to_string())?,
+    };
+
+    let (records, index) = single_file::decode(&data)?;
+    entries(&records, &index)
+}
+
+fn remote_url(dir: &Path) -> Option<String> {
+    git(dir, &["remote", "get-url", REMOTE]).ok()
+}
+
+fn is_ancestor(dir: &Path, ancestor: &str, revision: &str) -> bool {
+    git(dir, &["merge-base", "--is-ancestor", ancestor, revision]).is_ok()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    git_output(dir, args).map(|output| String::from_utf8_lossy(&output).trim().to_string())
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,200 @@
+use super::*;
+use crate::command::{self, EntryChanges, NewEntry};
+use tempfile::TempDir;
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+
+fn entry(domain: &str, record: &[u8]) -> (String, SyncEntry) {
+    let entry = SyncEntry {
+        index_entry: IndexEntry::new(domain, 0),
+        record: record.to_vec(),
+    };
+    (String::from(domain), entry)
+}
+
+fn add(domain: &str) {
+    let entry = NewEntry {
+        domain: String::from(domain),
+        username: String::from("john"),
+        password: format!("{}-s3cret", domain),
+        url: String::new(),
+        tags: Vec::new(),
+        folder: String::new(),
+    };
+    command::add_entry(&entry, MASTER_PASSWORD).unwrap();
+}
+
+fn machine(dir: &TempDir) {
+    storage::use_dir(dir.path());
+}
+
+fn domains() -> Vec<String> {
+    command::get_index_set()
+        .unwrap()
+        .data
+        .iter()
+        .map(|e| e.key.clone())
+        .collect()
+}
+
+#[test]
+fn test_merge() {
+    let base = Entries::from([
+        entry("edited", b"a"),
+        entry("removed", b"b"),
+        entry("both", b"c"),
+    ]);
+    let local = Entries::from([
+        entry("edited", b"a"),
+        entry("both", b"local"),
+        entry("added", b"d"),
+    ]);
+    let remote = Entries::from([
+        entry("edited", b"new"),
+        entry("removed", b"b"),
+        entry("both", b"remote"),
+        entry("other", b"e"),
+    ]);
+
+    let merged = merge(&base, &local, &remote);
+    let records: Vec<(&str, &[u8])> = merged
+        .entries
+        .iter()
+        .map(|(domain, entry)| (domain.as_str(), entry.record.as_slice()))
+        .collect();
+
+    assert_eq!(
+        records,
+        vec![
+            ("added", b"d".as_slice()),
+            ("both", b"local".as_slice()),
+            ("edited", b"new".as_slice()),
+            ("other", b"e".as_slice()),
+        ]
+    );
+    assert_eq!(merged.conflicts, vec!["both"]);
+}
+
+#[test]
+fn test_merge_removed_and_changed() {
+    let base = Entries::from([entry("gmail", b"a")]);
+    let local = Entries::new();
+    let remote = Entries::from([entry("gmail", b"b")]);
+
+    let merged = merge(&base, &local, &remote);
+
+    assert_eq!(merged.entries["gmail"].record, b"b");
+    assert_eq!(merged.conflicts, vec!["gmail"]);
+}
+
+#[test]
+fn test_sync_two_machines() {
+    let (remote, first, second) = (
+        TempDir::new().unwrap(),
+        TempDir::new().unwrap(),
+        TempDir::new().unwrap(),
+    );
+    git(remote.path(), &["init", "-q", "--bare"]).unwrap();
+    let url = remote.path().to_str().unwrap();
+
+    machine(&first);
+    add("gmail");
+    set_remote(url).unwrap();
+    sync(MASTER_PASSWORD).unwrap();
+
+    machine(&second);
+    add("github");
+    set_remote(url).unwrap();
+    assert!(sync(MASTER_PASSWORD).unwrap().updated);
+    assert_eq!(domains(), vec!["github", "gmail"]);
+
+    // Concurrent changes on both machines
+    add("gitlab");
+    command::remove_entry("github", MASTER_PASSWORD).unwrap();
+    machine(&first);
+    add("aws");
+    command::edit_entry(
+        "gmail",
+        &EntryChanges {
+            password: Some(String::from("changed")),
+            ..Default::default()
+        },
+        MASTER_PASSWORD,
+    )
+    .unwrap();
+    sync(MASTER_PASSWORD).unwrap();
+    assert_eq!(domains(), vec!["aws", "github", "gmail"]);
+
+    machine(&second);
+    let report = sync(MASTER_PASSWORD).unwrap();
+    assert!(report.updated && report.conflicts.is_empty());
+    assert_eq!(domains(), vec!["aws", "gitlab", "gmail"]);
+    assert_eq!(
+        command::get_entry("gmail", MASTER_PASSWORD).unwrap().1,
+        "changed"
+    );
+
+    machine(&first);
+    sync(MASTER_PASSWORD).unwrap();
+    assert_eq!(domains(), vec!["aws", "gitlab", "gmail"]);
+    assert_eq!(
+        command::get_entry("gitlab", MASTER_PASSWORD).unwrap().1,
+        "gitlab-s3cret"
+    );
+}
+
+#[test]
+fn test_sync_without_remote() {
+    let dir = TempDir::new().unwrap();
+    machine(&dir);
+    add("gmail");
+
+    let report = sync(MASTER_PASSWORD).unwrap();
+
+    assert!(report.remote.is_none() && !report.updated);
+    assert_eq!(
+        read_vault(&storage::sync_dir_path(), Some("HEAD"))
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_save_rejects_entries_that_dont_decrypt() {
+    let dir = TempDir::new().unwrap();
+    machine(&dir);
+    add("gmail");
+    add("github");
+    let (records, index) = storage::open().snapshot().unwrap();
+    let local = entries(&records, &index).unwrap();
+
+    // Other side swapped the records of two entries
+    let mut swapped = local.clone();
+    let gmail = swapped["gmail"].record.clone();
+    swapped.get_mut("gmail").unwrap().record = swapped["github"].record.clone();
+    swapped.get_mut("github").unwrap().record = gmail;
+    assert!(save(&swapped, MASTER_PASSWORD).is_err());
+
+    // Or brought a record damaged beyond reading
+    let mut damaged = local.clone();
+    damaged.get_mut("gmail").unwrap().record.truncate(20);
+    assert!(save(&damaged, MASTER_PASSWORD).is_err());
+
+    assert_eq!(storage::open().snapshot().unwrap(), (records, index));
+    assert!(command::get_index_set()
+        .unwrap()
+        .verify_seal(MASTER_PASSWORD, 0)
+        .is_ok());
+
+    // Entries that decrypt are saved
+    let mut added = local.clone();
+    let other = TempDir::new().unwrap();
+    machine(&other);
+    add("gitlab");
+    let (records, index) = storage::open().snapshot().unwrap();
+    added.extend(entries(&records, &index).unwrap());
+    machine(&dir);
+    save(&added, MASTER_PASSWORD).unwrap();
+    assert_eq!(domains(), vec!["github", "gitlab", "gmail"]);
+}
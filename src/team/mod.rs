@@ -0,0 +1,557 @@
+use crate::crypto::{self, Cipher, KEY_SIZE, NONCE_SIZE, SALT_SIZE};
+use crate::entry::{write_string, ByteReader};
+use crate::storage;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+// Team vault is a single file that members share through a synced directory. Every entry
+// is encrypted with its own random key, and that key is wrapped to the public key of every
+// member the entry is shared with. Anyone who can write the file can change its member list,
+// so keys are wrapped only for members whose key this user pinned on this device.
+pub const TEAM_MAGIC: &[u8; 8] = b"DOMETEAM";
+pub const TEAM_VERSION: u8 = 1;
+
+// Key pair of this user. Private key is encrypted with the master password.
+pub struct Identity {
+    secret: StaticSecret,
+    pub public: [u8; 32],
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: String,
+    pub public: [u8; 32],
+}
+
+// Entry key encrypted for one member with a key agreed from an ephemeral key pair
+#[derive(Clone, Debug)]
+pub struct Wrap {
+    pub member: String,
+    pub ephemeral: [u8; 32],
+    pub nonce: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SharedEntry {
+    pub domain: String,
+    pub nonce: Vec<u8>,
+    pub cipher: Vec<u8>,
+    pub wraps: Vec<Wrap>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Secret {
+    pub username: String,
+    pub password: String,
+    pub url: String,
+}
+
+// Names and public keys of members this user added or checked, kept in the Dome directory
+#[derive(Default)]
+pub struct Pins {
+    keys: BTreeMap<String, [u8; 32]>,
+}
+
+#[derive(Default)]
+pub struct Team {
+    pub members: Vec<Member>,
+    pub entries: Vec<SharedEntry>,
+}
+
+impl Identity {
+    pub fn generate() -> Identity {
+        let secret = StaticSecret::from(rand::random::<[u8; 32]>());
+        let public = PublicKey::from(&secret).to_bytes();
+        Identity { secret, public }
+    }
+
+    // Identity of this user, created on first use
+    pub fn load(master_password: &str) -> Result<Identity, String> {
+        let path = storage::identity_file_path();
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => {
+                let identity = Identity::generate();
+                storage::replace_file(&path, &identity.encode(master_password))?;
+                return Ok(identity);
+            }
+        };
+
+        let mut reader = ByteReader::new(&data);
+        let damaged = || String::from("Identity file is damaged.");
+        let salt = reader.read_bytes(SALT_SIZE).ok_or_else(damaged)?;
+        let nonce = reader.read_bytes(NONCE_SIZE).ok_or_else(damaged)?;
+
+        let key = crypto::derive_key(master_password, salt);
+        let secret = crypto::decrypt_bytes(&key, nonce, reader.read_rest())?;
+        let secret: [u8; 32] = secret.try_into().map_err(|_| damaged())?;
+
+        let secret = StaticSecret::from(secret);
+        let public = PublicKey::from(&secret).to_bytes();
+        Ok(Identity { secret, public })
+    }
+
+    fn encode(&self, master_password: &str) -> Vec<u8> {
+        let salt = crypto::generate_salt();
+        let key = crypto::derive_key(master_password, &salt);
+        let (cipher, nonce) = crypto::encrypt_bytes(&key, self.secret.as_bytes());
+
+        let mut data = salt;
+        data.extend(nonce);
+        data.extend(cipher);
+        data
+    }
+
+    pub fn public_text(&self) -> String {
+        encode_public_key(&self.public)
+    }
+}
+
+// Private key follows the master password when it changes
+pub fn reencrypt_identity(current: &str, new: &str) -> Result<(), String> {
+    let path = storage::identity_file_path();
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let identity = Identity::load(current)?;
+    storage::replace_file(&path, &identity.encode(new))
+}
+
+pub fn encode_public_key(public: &[u8; 32]) -> String {
+    STANDARD.encode(public)
+}
+
+pub fn parse_public_key(text: &str) -> Result<[u8; 32], String> {
+    STANDARD
+        .decode(text.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| String::from("Public key must be 32 bytes encoded in base64."))
+}
+
+impl Pins {
+    pub fn load() -> Result<Pins, String> {
+        let text = match fs::read_to_string(storage::team_pins_file_path()) {
+            Ok(text) => text,
+            Err(_) => return Ok(Pins::default()),
+        };
+
+        let mut pins = Pins::default();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let (name, key) = line
+                .rsplit_once(' ')
+                .ok_or_else(|| String::from("Pinned team keys are damaged."))?;
+            pins.pin(name, parse_public_key(key)?);
+        }
+        Ok(pins)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text: String = self
+            .keys
+            .iter()
+            .map(|(name, key)| format!("{} {}\n", name, encode_public_key(key)))
+            .collect();
+        storage::replace_file(&storage::team_pins_file_path(), text.as_bytes())
+    }
+
+    pub fn pin(&mut self, name: &str, public: [u8; 32]) {
+        self.keys.insert(name.to_string(), public);
+    }
+
+    pub fn unpin(&mut self, name: &str) {
+        self.keys.remove(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[u8; 32]> {
+        self.keys.get(name)
+    }
+
+    // Fails unless the member has the key that was pinned for it
+    pub fn check(&self, member: &Member) -> Result<(), String> {
+        match self.keys.get(&member.name) {
+            Some(key) if *key == member.public => Ok(()),
+            Some(_) => Err(format!(
+                "Key of {} in the team vault doesn't match the pinned one, the file may have been changed.",
+                member.name
+            )),
+            None => Err(format!(
+                "Key of {} isn't pinned on this device. Check it with {} and add it with 'team trust <key> --name {}'.",
+                member.name, member.name, member.name
+            )),
+        }
+    }
+}
+
+impl Team {
+    pub fn read(path: &Path) -> Result<Team, String> {
+        let data =
+            fs::read(path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+        decode(&data).ok_or_else(|| format!("{} is not a Dome team vault.", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        storage::replace_file(path, &encode(self))
+    }
+
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    pub fn entry(&self, domain: &str) -> Option<&SharedEntry> {
+        self.entries.iter().find(|e| e.domain == domain)
+    }
+
+    // Name under which this user is a member
+    pub fn name_of(&self, identity: &Identity) -> Option<&str> {
+        self.members
+            .iter()
+            .find(|m| m.public == identity.public)
+            .map(|m| m.name.as_str())
+    }
+
+    pub fn add_member(&mut self, name: &str, public: [u8; 32]) -> Result<(), String> {
+        if self.member(name).is_some() {
+            return Err(format!("{} is already a member of the team.", name));
+        }
+
+        if self.members.iter().any(|m| m.public == public) {
+            return Err(String::from("Member with this key is already in the team."));
+        }
+
+        self.members.push(Member {
+            name: name.to_string(),
+            public,
+        });
+        Ok(())
+    }
+
+    // Shares the secret with given members, the user sharing it keeps access too. Entry
+    // that is already shared stays shared with its current members.
+    pub fn share(
+        &mut self,
+        identity: &Identity,
+        pins: &Pins,
+        domain: &str,
+        secret: &Secret,
+        with: &[String],
+    ) -> Result<(), String> {
+        let own = self
+            .name_of(identity)
+            .ok_or_else(|| String::from("You are not a member of the team."))?
+            .to_string();
+
+        let mut recipients = vec![own];
+        if let Some(entry) = self.entry(domain) {
+            unwrap_key(entry, identity)?;
+            recipients.extend(entry.wraps.iter().map(|w| w.member.clone()));
+        }
+
+        for name in with {
+            if self.member(name).is_none() {
+                return Err(format!("{} is not a member of the team.", name));
+            }
+            recipients.push(name.clone());
+        }
+
+        recipients.sort();
+        recipients.dedup();
+        self.check_recipients(identity, pins, &recipients)?;
+
+        let entry = self.seal(domain, &encode_secret(secret), &recipients);
+        self.entries.retain(|e| e.domain != domain);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn get(&self, domain: &str, identity: &Identity) -> Result<Secret, String> {
+        let entry = self
+            .entry(domain)
+            .ok_or_else(|| format!("{} is not shared with the team.", domain))?;
+        let key = unwrap_key(entry, identity)?;
+        let payload = open(entry, &key)?;
+
+        decode_secret(&payload).ok_or_else(|| format!("Shared entry {} is damaged.", domain))
+    }
+
+    // Removes the member and encrypts every entry the member could read with a new key, so
+    // a copy of the old key doesn't open new versions of the file. Fails without changing
+    // anything when some of those entries aren't shared with this user.
+    pub fn remove_member(
+        &mut self,
+        name: &str,
+        identity: &Identity,
+        pins: &Pins,
+    ) -> Result<usize, String> {
+        if self.member(name).is_none() {
+            return Err(format!("{} is not a member of the team.", name));
+        }
+
+        let mut rekeyed = Vec::new();
+        for entry in &self.entries {
+            if entry.wraps.iter().any(|w| w.member == name) {
+                let key = unwrap_key(entry, identity).map_err(|_| {
+                    format!(
+                        "{} can't be re-keyed, it isn't shared with you. Ask its owner to remove {}.",
+                        entry.domain, name
+                    )
+                })?;
+                let payload = open(entry, &key)?;
+                let recipients: Vec<String> = entry
+                    .wraps
+                    .iter()
+                    .map(|w| w.member.clone())
+                    .filter(|member| member != name)
+                    .collect();
+                self.check_recipients(identity, pins, &recipients)?;
+
+                rekeyed.push(self.seal(&entry.domain, &payload, &recipients));
+            }
+        }
+
+        let count = rekeyed.len();
+        for entry in rekeyed {
+            let position = self.entries.iter().position(|e| e.domain == entry.domain);
+            self.entries[position.unwrap()] = entry;
+        }
+
+        self.members.retain(|m| m.name != name);
+        Ok(count)
+    }
+
+    // Own key comes from the identity, keys of the others have to match their pins
+    fn check_recipients(
+        &self,
+        identity: &Identity,
+        pins: &Pins,
+        recipients: &[String],
+    ) -> Result<(), String> {
+        for name in recipients {
+            let member = self
+                .member(name)
+                .ok_or_else(|| format!("{} is not a member of the team.", name))?;
+
+            if member.public != identity.public {
+                pins.check(member)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Encrypts the payload with a new key wrapped for every recipient
+    fn seal(&self, domain: &str, payload: &[u8], recipients: &[String]) -> SharedEntry {
+        let key = rand::random::<[u8; KEY_SIZE]>();
+        let (cipher, nonce) =
+            crypto::encrypt_with_aad(Cipher::Aes256Gcm, &key, payload, &associated_data(domain));
+
+        let wraps = recipients
+            .iter()
+            .filter_map(|name| self.member(name))
+            .map(|member| wrap_key(&key, member))
+            .collect();
+
+        SharedEntry {
+            domain: domain.to_string(),
+            nonce,
+            cipher,
+            wraps,
+        }
+    }
+}
+
+// Path of the team vault this user works with
+pub fn team_path() -> Result<PathBuf, String> {
+    fs::read_to_string(storage::team_file_path())
+        .map(|path| PathBuf::from(path.trim()))
+        .map_err(|_| String::from("No team vault is set. Create or join one with 'team init'."))
+}
+
+// Creates the team vault with this user as its first member, or joins an existing one.
+// Keys pinned for another team are forgotten.
+pub fn init(path: &Path, name: &str, identity: &Identity) -> Result<bool, String> {
+    let created = !path.exists();
+    if created {
+        let mut team = Team::default();
+        team.add_member(name, identity.public)?;
+        team.write(path)?;
+    }
+
+    let path = fs::canonicalize(path).map_err(|err| err.to_string())?;
+    if team_path().ok() != Some(path.clone()) {
+        Pins::default().save()?;
+    }
+    storage::replace_file(
+        &storage::team_file_path(),
+        path.display().to_string().as_bytes(),
+    )?;
+    Ok(created)
+}
+
+// Binds the cipher text to its domain, so entries swapped in the file don't decrypt. The
+// username is part of the encrypted secret, so it is authenticated with it.
+fn associated_data(domain: &str) -> Vec<u8> {
+    crypto::associated_data(domain, "", TEAM_MAGIC)
+}
+
+fn open(entry: &SharedEntry, key: &[u8]) -> Result<Vec<u8>, String> {
+    let aad = associated_data(&entry.domain);
+    crypto::decrypt_with_aad(Cipher::Aes256Gcm, key, &entry.nonce, &entry.cipher, &aad)
+}
+
+fn wrap_key(key: &[u8; KEY_SIZE], member: &Member) -> Wrap {
+    let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+    let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+    let shared = ephemeral.diffie_hellman(&PublicKey::from(member.public));
+
+    let wrapping_key = wrapping_key(shared.as_bytes(), &ephemeral_public, &member.public);
+    let (key, nonce) = crypto::encrypt_bytes(&wrapping_key, key);
+
+    Wrap {
+        member: member.name.clone(),
+        ephemeral: ephemeral_public,
+        nonce,
+        key,
+    }
+}
+
+fn unwrap_key(entry: &SharedEntry, identity: &Identity) -> Result<Vec<u8>, String> {
+    let not_shared = || format!("{} is not shared with you.", entry.domain);
+
+    for wrap in &entry.wraps {
+        let shared = identity
+            .secret
+            .diffie_hellman(&PublicKey::from(wrap.ephemeral));
+        let wrapping_key = wrapping_key(shared.as_bytes(), &wrap.ephemeral, &identity.public);
+
+        // Wraps of other members don't open with this key
+        if let Ok(key) = crypto::decrypt_bytes(&wrapping_key, &wrap.nonce, &wrap.key) {
+            return Ok(key);
+        }
+    }
+
+    Err(not_shared())
+}
+
+// Agreed secret is hashed together with both public keys, so the key is bound to them
+fn wrapping_key(shared: &[u8], ephemeral: &[u8; 32], recipient: &[u8; 32]) -> [u8; KEY_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dome-team-key");
+    hasher.update(shared);
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+    hasher.finalize().into()
+}
+
+fn encode_secret(secret: &Secret) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_string(&mut data, &secret.username);
+    write_string(&mut data, &secret.password);
+    write_string(&mut data, &secret.url);
+    data
+}
+
+fn decode_secret(data: &[u8]) -> Option<Secret> {
+    let mut reader = ByteReader::new(data);
+    Some(Secret {
+        username: reader.read_string()?,
+        password: reader.read_string()?,
+        url: reader.read_string()?,
+    })
+}
+
+pub fn encode(team: &Team) -> Vec<u8> {
+    let mut data = TEAM_MAGIC.to_vec();
+    data.push(TEAM_VERSION);
+
+    data.extend((team.members.len() as u16).to_le_bytes());
+    for member in &team.members {
+        write_string(&mut data, &member.name);
+        data.extend(member.public);
+    }
+
+    data.extend((team.entries.len() as u32).to_le_bytes());
+    for entry in &team.entries {
+        write_string(&mut data, &entry.domain);
+        data.extend(&entry.nonce);
+        data.extend((entry.cipher.len() as u32).to_le_bytes());
+        data.extend(&entry.cipher);
+
+        data.extend((entry.wraps.len() as u16).to_le_bytes());
+        for wrap in &entry.wraps {
+            write_string(&mut data, &wrap.member);
+            data.extend(wrap.ephemeral);
+            data.extend(&wrap.nonce);
+            data.push(wrap.key.len() as u8);
+            data.extend(&wrap.key);
+        }
+    }
+
+    data
+}
+
+pub fn decode(data: &[u8]) -> Option<Team> {
+    if !data.starts_with(TEAM_MAGIC) {
+        return None;
+    }
+
+    let mut reader = ByteReader::new(&data[TEAM_MAGIC.len()..]);
+    if reader.read_u8()? > TEAM_VERSION {
+        return None;
+    }
+
+    let count = reader.read_u16()?;
+    let members = (0..count)
+        .map(|_| {
+            Some(Member {
+                name: reader.read_string()?,
+                public: reader.read_bytes(32)?.try_into().ok()?,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    let count = reader.read_u32()?;
+    let entries = (0..count)
+        .map(|_| {
+            let domain = reader.read_string()?;
+            let nonce = reader.read_bytes(NONCE_SIZE)?.to_vec();
+            let len = reader.read_u32()? as usize;
+            let cipher = reader.read_bytes(len)?.to_vec();
+
+            let count = reader.read_u16()?;
+            let wraps = (0..count)
+                .map(|_| {
+                    let member = reader.read_string()?;
+                    let ephemeral = reader.read_bytes(32)?.try_into().ok()?;
+                    let nonce = reader.read_bytes(NONCE_SIZE)?.to_vec();
+                    let len = reader.read_u8()? as usize;
+                    let key = reader.read_bytes(len)?.to_vec();
+                    Some(Wrap {
+                        member,
+                        ephemeral,
+                        nonce,
+                        key,
+                    })
+                })
+                .collect::<Option<_>>()?;
+
+            Some(SharedEntry {
+                domain,
+                nonce,
+                cipher,
+                wraps,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    Some(Team { members, entries })
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,225 @@
+use super::*;
+
+fn secret(password: &str) -> Secret {
+    Secret {
+        username: String::from("john"),
+        password: String::from(password),
+        url: String::from("https://mail.google.com"),
+    }
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+// Every current member pinned
+fn pins(team: &Team) -> Pins {
+    let mut pins = Pins::default();
+    for member in &team.members {
+        pins.pin(&member.name, member.public);
+    }
+    pins
+}
+
+// Team of alice, bob and carol where alice shared gmail with bob
+fn team() -> (Team, Identity, Identity, Identity) {
+    let (alice, bob, carol) = (
+        Identity::generate(),
+        Identity::generate(),
+        Identity::generate(),
+    );
+
+    let mut team = Team::default();
+    team.add_member("alice", alice.public).unwrap();
+    team.add_member("bob", bob.public).unwrap();
+    team.add_member("carol", carol.public).unwrap();
+    team.share(
+        &alice,
+        &pins(&team),
+        "gmail",
+        &secret("gm41l"),
+        &names(&["bob"]),
+    )
+    .unwrap();
+
+    (team, alice, bob, carol)
+}
+
+#[test]
+fn test_share() {
+    let (team, alice, bob, carol) = team();
+
+    assert_eq!(team.get("gmail", &alice).unwrap(), secret("gm41l"));
+    assert_eq!(team.get("gmail", &bob).unwrap(), secret("gm41l"));
+    assert_eq!(
+        team.get("gmail", &carol).err().unwrap(),
+        "gmail is not shared with you."
+    );
+    assert!(team.get("github", &alice).is_err());
+}
+
+#[test]
+fn test_swapped_entries_dont_decrypt() {
+    let (mut team, alice, bob, _) = team();
+    let pins = pins(&team);
+    team.share(&alice, &pins, "github", &secret("g1thub"), &names(&["bob"]))
+        .unwrap();
+
+    // Someone who can write the file gives gmail the contents of github
+    let github = team.entry("github").unwrap().clone();
+    let gmail = team
+        .entries
+        .iter_mut()
+        .find(|e| e.domain == "gmail")
+        .unwrap();
+    gmail.nonce = github.nonce;
+    gmail.cipher = github.cipher;
+    gmail.wraps = github.wraps;
+
+    assert!(team.get("gmail", &alice).is_err());
+    assert!(team.get("gmail", &bob).is_err());
+    assert_eq!(team.get("github", &bob).unwrap(), secret("g1thub"));
+}
+
+#[test]
+fn test_share_with_more_members() {
+    let (mut team, _, bob, carol) = team();
+
+    // Bob can share further, everyone who had access keeps it
+    team.share(
+        &bob,
+        &pins(&team),
+        "gmail",
+        &secret("gm41l-2"),
+        &names(&["carol"]),
+    )
+    .unwrap();
+
+    assert_eq!(team.entry("gmail").unwrap().wraps.len(), 3);
+    assert_eq!(team.get("gmail", &carol).unwrap(), secret("gm41l-2"));
+    assert!(team
+        .share(
+            &carol,
+            &pins(&team),
+            "gmail",
+            &secret("x"),
+            &names(&["dave"])
+        )
+        .is_err());
+}
+
+#[test]
+fn test_remove_member_rekeys() {
+    let (mut team, alice, bob, _) = team();
+    let old_key = unwrap_key(team.entry("gmail").unwrap(), &bob).unwrap();
+    let old_nonce = team.entry("gmail").unwrap().nonce.clone();
+
+    assert_eq!(team.remove_member("bob", &alice, &pins(&team)).unwrap(), 1);
+
+    let entry = team.entry("gmail").unwrap();
+    assert!(team.member("bob").is_none());
+    assert_eq!(entry.wraps.len(), 1);
+    assert!(team.get("gmail", &bob).is_err());
+    assert_eq!(team.get("gmail", &alice).unwrap(), secret("gm41l"));
+
+    // Key bob kept doesn't open the entry any more
+    assert!(crypto::decrypt_bytes(&old_key, &old_nonce, &entry.cipher).is_err());
+    assert!(crypto::decrypt_bytes(&old_key, &entry.nonce, &entry.cipher).is_err());
+}
+
+#[test]
+fn test_remove_member_without_access() {
+    let (mut team, alice, _, carol) = team();
+
+    // Carol can't re-key gmail, so bob stays
+    assert!(team.remove_member("bob", &carol, &pins(&team)).is_err());
+    assert!(team.member("bob").is_some());
+    assert!(team.remove_member("dave", &alice, &pins(&team)).is_err());
+}
+
+#[test]
+fn test_unpinned_keys_are_refused() {
+    let (mut team, alice, ..) = team();
+    let pins = pins(&team);
+    let mallory = Identity::generate();
+
+    // Member added by editing the shared file
+    team.add_member("mallory", mallory.public).unwrap();
+    assert!(team
+        .share(&alice, &pins, "github", &secret("x"), &names(&["mallory"]))
+        .unwrap_err()
+        .contains("isn't pinned"));
+
+    // Key of bob swapped in the shared file
+    team.members[1].public = mallory.public;
+    assert!(team
+        .share(&alice, &pins, "gmail", &secret("x"), &names(&[]))
+        .unwrap_err()
+        .contains("doesn't match"));
+    assert!(team.entry("github").is_none());
+}
+
+#[test]
+fn test_pins_file() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    let (team, ..) = team();
+
+    pins(&team).save().unwrap();
+    let loaded = Pins::load().unwrap();
+    for member in &team.members {
+        assert!(loaded.check(member).is_ok());
+    }
+}
+
+#[test]
+fn test_members() {
+    let (mut team, alice, ..) = team();
+
+    assert!(team.add_member("alice", [1; 32]).is_err());
+    assert!(team.add_member("dave", alice.public).is_err());
+    assert_eq!(team.name_of(&alice), Some("alice"));
+    assert_eq!(
+        parse_public_key(&alice.public_text()).unwrap(),
+        alice.public
+    );
+    assert!(parse_public_key("c2hvcnQ=").is_err());
+}
+
+#[test]
+fn test_file_round_trip() {
+    let (team, _, bob, _) = team();
+    let decoded = decode(&encode(&team)).unwrap();
+
+    assert_eq!(decoded.members.len(), 3);
+    assert_eq!(decoded.get("gmail", &bob).unwrap(), secret("gm41l"));
+    assert!(decode(b"DOMETEAM").is_none());
+}
+
+#[test]
+fn test_identity() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    let identity = Identity::load("master").unwrap();
+    assert_eq!(Identity::load("master").unwrap().public, identity.public);
+    assert!(Identity::load("wrong").is_err());
+
+    reencrypt_identity("master", "new").unwrap();
+    assert_eq!(Identity::load("new").unwrap().public, identity.public);
+}
+
+#[test]
+fn test_init_and_join() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+    let path = dir.path().join("team.vault");
+    let (alice, bob) = (Identity::generate(), Identity::generate());
+
+    assert!(init(&path, "alice", &alice).unwrap());
+    assert!(!init(&path, "bob", &bob).unwrap());
+
+    let team = Team::read(&team_path().unwrap()).unwrap();
+    assert_eq!(team.name_of(&alice), Some("alice"));
+    assert_eq!(team.name_of(&bob), None);
+}
@@ -0,0 +1,213 @@
+use crate::config;
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::BinarySerialization;
+use crate::storage;
+use crate::time;
+use std::fs;
+
+// Wrong master passwords make the following attempts wait longer and longer, and can lock or
+// destroy the vault once attempts.limit is reached. The counter is kept in the index header
+// and in its own file. The higher one is used, so deleting or rolling back either of them
+// doesn't reset it, and the difference is reported.
+
+// Attempts in a row that don't have to wait
+pub const FREE_ATTEMPTS: u32 = 3;
+pub const MAX_DELAY: u64 = 60 * 60;
+const HOUR: u64 = 60 * 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Attempts {
+    pub failures: u32,
+    pub last_failure: u64,
+}
+
+impl Attempts {
+    // Time the next attempt can be made at
+    pub fn next_attempt(&self) -> u64 {
+        let limit = config::get_number("attempts.limit");
+
+        if limit > 0 && self.failures as u64 >= limit {
+            return self.last_failure + config::get_number("attempts.lockout_hours") * HOUR;
+        }
+
+        self.last_failure + delay(self.failures)
+    }
+}
+
+// Seconds to wait after given number of wrong passwords, doubled by every one of them
+pub fn delay(failures: u32) -> u64 {
+    match failures.checked_sub(FREE_ATTEMPTS) {
+        None => 0,
+        Some(extra) if extra >= 12 => MAX_DELAY,
+        Some(extra) => MAX_DELAY.min(1 << extra),
+    }
+}
+
+// Higher of both counters
+pub fn read() -> Attempts {
+    let (in_index, in_file) = stored();
+
+    Attempts {
+        failures: in_index.failures.max(in_file.failures),
+        last_failure: in_index.last_failure.max(in_file.last_failure),
+    }
+}
+
+// True when one copy of the counter was changed or deleted outside of Dome
+pub fn tampered() -> bool {
+    let (in_index, in_file) = stored();
+    in_index != in_file
+}
+
+// Fails with the time to wait when the vault doesn't accept a password right now
+pub fn check(now: u64) -> Result<(), String> {
+    let attempts = read();
+    let next_attempt = attempts.next_attempt();
+
+    if now >= next_attempt {
+        return Ok(());
+    }
+
+    let limit = config::get_number("attempts.limit");
+    if limit > 0 && attempts.failures as u64 >= limit {
+        return Err(format!(
+            "Vault is locked after {} wrong master passwords until {}.",
+            attempts.failures,
+            time::format_time(next_attempt)
+        ));
+    }
+
+    Err(format!(
+        "Too many wrong master passwords. Try again in {} seconds.",
+        next_attempt - now
+    ))
+}
+
+// Counts a wrong password. Returns what it means for the next attempts, the vault is
+// destroyed here when attempts.destroy is set and the limit was reached.
+pub fn failed(now: u64) -> Result<Option<String>, String> {
+    let attempts = Attempts {
+        failures: read().failures.saturating_add(1),
+        last_failure: now,
+    };
+    save(attempts, None)?;
+
+    let limit = config::get_number("attempts.limit");
+    let left = limit.saturating_sub(attempts.failures as u64);
+
+    if limit > 0 && left == 0 {
+        if config::get_number("attempts.destroy") == 1 {
+            destroy()?;
+            return Ok(Some(format!(
+                "Vault and its backups were deleted after {} wrong master passwords.",
+                attempts.failures
+            )));
+        }
+
+        return Ok(Some(format!(
+            "Vault is locked until {}.",
+            time::format_time(attempts.next_attempt())
+        )));
+    }
+
+    let wait = match delay(attempts.failures) {
+        0 => None,
+        seconds => Some(format!("Next attempt is possible in {} seconds.", seconds)),
+    };
+
+    let warning = match config::get_number("attempts.destroy") {
+        1 => "destroyed",
+        _ => "locked",
+    };
+    let left = Some(format!(
+        "{} attempts left before the vault is {}.",
+        left, warning
+    ))
+    .filter(|_| limit > 0);
+
+    let notes: Vec<String> = [wait, left].into_iter().flatten().collect();
+    Ok(Some(notes.join(" ")).filter(|notes| !notes.is_empty()))
+}
+
+// Resets the counter after the right password. The index is sealed again, because the
+// seal doesn't let the counter go back.
+pub fn succeeded(master_password: &str) -> Result<(), String> {
+    let (in_index, in_file) = stored();
+    if in_index == Attempts::default() && in_file == Attempts::default() {
+        return Ok(());
+    }
+
+    save(Attempts::default(), Some(master_password))
+}
+
+// Counter from the index and from the attempts file. A damaged index leaves only the file,
+// the command fails on the index later anyway.
+fn stored() -> (Attempts, Attempts) {
+    let in_index = storage::open()
+        .list()
+        .map(|index_set| Attempts {
+            failures: index_set.failures,
+            last_failure: index_set.last_failure,
+        })
+        .unwrap_or_default();
+
+    let in_file = fs::read_to_string(storage::attempts_file_path())
+        .ok()
+        .and_then(|text| parse(&text))
+        .unwrap_or_default();
+
+    (in_index, in_file)
+}
+
+// Attempts file has the number of failures and the time of the last one, like "4 1700000000"
+fn parse(text: &str) -> Option<Attempts> {
+    let mut parts = text.split_whitespace();
+
+    Some(Attempts {
+        failures: parts.next()?.parse().ok()?,
+        last_failure: parts.next()?.parse().ok()?,
+    })
+}
+
+fn save(attempts: Attempts, master_password: Option<&str>) -> Result<(), String> {
+    storage::open().transaction(&mut |backend| {
+        let mut index_set = backend.list()?;
+        index_set.failures = attempts.failures;
+        index_set.last_failure = attempts.last_failure;
+        if let Some(master_password) = master_password {
+            index_set.seal(master_password);
+        }
+        backend.write_index(&index_set)
+    })?;
+
+    let path = storage::attempts_file_path();
+    if attempts == Attempts::default() {
+        return match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Can't reset {}: {}", path.display(), err))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let text = format!("{} {}\n", attempts.failures, attempts.last_failure);
+    storage::replace_file(&path, text.as_bytes())
+}
+
+// Empties the vault and deletes the backups, which are encrypted with the same password
+fn destroy() -> Result<(), String> {
+    storage::open().replace(&[], &IndexSet::new().serialize())?;
+
+    let backups = storage::backup_dir_path();
+    if backups.exists() {
+        fs::remove_dir_all(&backups)
+            .map_err(|err| format!("Can't delete {}: {}", backups.display(), err))?;
+    }
+
+    let _ = fs::remove_file(storage::attempts_file_path());
+    let _ = fs::remove_file(storage::seal_file_path());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,119 @@
+use super::*;
+use crate::entry::index_entry::IndexEntry;
+
+const NOW: u64 = 1_700_000_000;
+
+fn prepare_vault() {
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list().unwrap();
+            index_set.add(&IndexEntry::new("gmail", backend.write_entry(b"record")?));
+            backend.write_index(&index_set)
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_delay() {
+    assert_eq!(delay(0), 0);
+    assert_eq!(delay(FREE_ATTEMPTS - 1), 0);
+    assert_eq!(delay(FREE_ATTEMPTS), 1);
+    assert_eq!(delay(FREE_ATTEMPTS + 3), 8);
+    assert_eq!(delay(FREE_ATTEMPTS + 20), MAX_DELAY);
+    assert_eq!(delay(u32::MAX), MAX_DELAY);
+}
+
+#[test]
+fn test_back_off() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    prepare_vault();
+
+    for _ in 0..FREE_ATTEMPTS - 1 {
+        assert_eq!(failed(NOW).unwrap(), None);
+        assert!(check(NOW).is_ok());
+    }
+
+    assert!(failed(NOW).unwrap().unwrap().contains("1 seconds"));
+    assert!(check(NOW).is_err());
+    assert!(check(NOW + 1).is_ok());
+
+    failed(NOW + 1).unwrap();
+    assert_eq!(
+        check(NOW + 1).unwrap_err(),
+        "Too many wrong master passwords. Try again in 2 seconds."
+    );
+
+    succeeded("master").unwrap();
+    assert_eq!(read(), Attempts::default());
+    assert!(!storage::attempts_file_path().exists());
+    assert!(check(NOW + 1).is_ok());
+}
+
+#[test]
+fn test_counter_survives_deleting_a_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    prepare_vault();
+
+    for _ in 0..5 {
+        failed(NOW).unwrap();
+    }
+    assert!(!tampered());
+
+    fs::remove_file(storage::attempts_file_path()).unwrap();
+    assert!(tampered());
+    assert_eq!(read().failures, 5);
+
+    // Index rolled back to the version without the counter, the file still has it
+    failed(NOW).unwrap();
+    storage::open()
+        .transaction(&mut |backend| {
+            let mut index_set = backend.list().unwrap();
+            index_set.failures = 0;
+            backend.write_index(&index_set)
+        })
+        .unwrap();
+    assert!(tampered());
+    assert_eq!(read().failures, 6);
+    assert!(check(NOW).is_err());
+}
+
+#[test]
+fn test_lockout() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    prepare_vault();
+    config::set("attempts.limit", "2").unwrap();
+    config::set("attempts.lockout_hours", "1").unwrap();
+
+    assert_eq!(
+        failed(NOW).unwrap().unwrap(),
+        "1 attempts left before the vault is locked."
+    );
+    assert!(failed(NOW)
+        .unwrap()
+        .unwrap()
+        .starts_with("Vault is locked until"));
+
+    assert!(check(NOW + HOUR - 1)
+        .unwrap_err()
+        .starts_with("Vault is locked after 2 wrong master passwords"));
+    assert!(check(NOW + HOUR).is_ok());
+    assert!(!storage::open().list().unwrap().is_empty());
+}
+
+#[test]
+fn test_destroy() {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_memory(dir.path());
+    prepare_vault();
+    fs::create_dir_all(storage::backup_dir_path()).unwrap();
+    config::set("attempts.limit", "1").unwrap();
+    config::set("attempts.destroy", "1").unwrap();
+
+    assert!(failed(NOW).unwrap().unwrap().contains("were deleted"));
+    assert!(storage::open().list().unwrap().is_empty());
+    assert!(!storage::backup_dir_path().exists());
+    assert_eq!(read(), Attempts::default());
+}
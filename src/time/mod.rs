@@ -0,0 +1,47 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// "2026-10-19 03:15:00 UTC"
+pub fn format_time(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = utc(secs);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, hour, minute, second
+    )
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// Calendar date from unix time, algorithm from Howard Hinnant's date library
+pub fn utc(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86400;
+    (
+        year as u64,
+        month as u64,
+        day as u64,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+    )
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,8 @@
+use super::*;
+
+#[test]
+fn test_format_time() {
+    assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+    assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00 UTC");
+    assert_eq!(format_time(1_792_379_725), "2026-10-19 03:15:25 UTC");
+}
@@ -0,0 +1,627 @@
+use crate::backup;
+use crate::command::{self, EntryChanges, NewEntry};
+use crate::datastructures::sorted_indexentry_set::IndexSet;
+use crate::entry::index_entry::{self, IndexEntry};
+use crate::keyfile;
+use crate::log::{self, Event};
+use crate::search::{self, SearchItem};
+use crate::strength;
+
+pub mod terminal;
+
+const GENERATED_LENGTH: u8 = 16;
+const HIDDEN_PASSWORD: &str = "********";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Esc,
+    Backspace,
+    Up,
+    Down,
+    Tab,
+    BackTab,
+    Resize,
+}
+
+// Source of key presses and target of rendered frames. Terminal backend talks to the
+// real terminal, tests use a scripted one.
+pub trait Backend {
+    // Returns None when there is no more input
+    fn next_key(&mut self) -> Option<Key>;
+    fn draw(&mut self, lines: &[String]);
+    fn size(&self) -> (usize, usize);
+    fn copy(&mut self, text: &str) -> Result<(), String>;
+}
+
+pub fn run(backend: &mut dyn Backend) {
+    let mut app = App::new();
+
+    loop {
+        let (width, height) = backend.size();
+        backend.draw(&app.render(width, height));
+
+        if app.quit {
+            break;
+        }
+
+        let key = match backend.next_key() {
+            Some(key) => key,
+            None => break,
+        };
+
+        if let Some(text) = app.handle_key(key) {
+            app.status = match backend.copy(&text) {
+                Ok(()) => format!("{} copied to clipboard.", app.status),
+                Err(err) => format!("Copying to clipboard failed: {}", err),
+            };
+        }
+    }
+}
+
+enum Mode {
+    List,
+    Filter,
+    Dialog(Dialog),
+}
+
+enum Action {
+    Reveal(String),
+    CopyPassword(String),
+    Add,
+    Edit(String),
+    Remove(String),
+    Generate,
+}
+
+struct Field {
+    label: &'static str,
+    value: String,
+    secret: bool,
+}
+
+impl Field {
+    fn new(label: &'static str, value: &str) -> Field {
+        Field {
+            label,
+            value: String::from(value),
+            secret: false,
+        }
+    }
+
+    fn secret(label: &'static str, value: &str) -> Field {
+        Field {
+            secret: true,
+            ..Field::new(label, value)
+        }
+    }
+}
+
+struct Dialog {
+    title: String,
+    action: Action,
+    fields: Vec<Field>,
+    focus: usize,
+}
+
+struct App {
+    index_set: IndexSet,
+    items: Vec<SearchItem>,
+    query: String,
+    visible: Vec<String>,
+    selected: usize,
+    mode: Mode,
+    status: String,
+    // Master password is kept for the session after it was used successfully, together with
+    // the key file mixed in. It is verified once, like the session of the shell.
+    master_password: Option<String>,
+    unlocked: Option<String>,
+    revealed: Option<(String, String)>,
+    quit: bool,
+}
+
+impl App {
+    fn new() -> App {
+        let mut app = App {
+            index_set: IndexSet::new(),
+            items: Vec::new(),
+            query: String::new(),
+            visible: Vec::new(),
+            selected: 0,
+            mode: Mode::List,
+            status: String::new(),
+            master_password: None,
+            unlocked: None,
+            revealed: None,
+            quit: false,
+        };
+
+        app.reload();
+        app
+    }
+
+    // Damaged index is reported on the status line, the list stays as it was
+    fn reload(&mut self) {
+        match command::get_index_set() {
+            Ok(index_set) => self.index_set = index_set,
+            Err(err) => self.status = err,
+        }
+        self.items = command::get_search_items(&self.index_set);
+        self.apply_filter();
+    }
+
+    fn apply_filter(&mut self) {
+        self.visible = if self.query.is_empty() {
+            self.index_set.data.iter().map(|e| e.key.clone()).collect()
+        } else {
+            search::search(&self.query, &self.items)
+                .into_iter()
+                .map(|result| result.key)
+                .collect()
+        };
+
+        self.selected = self.selected.min(self.visible.len().saturating_sub(1));
+    }
+
+    fn select(&mut self, key: &str) {
+        if let Some(position) = self.visible.iter().position(|k| k == key) {
+            self.selected = position;
+        }
+    }
+
+    fn selected_entry(&self) -> Option<&IndexEntry> {
+        let key = self.visible.get(self.selected)?;
+        self.index_set.find(key).map(|(_, entry)| entry)
+    }
+
+    // Returns text that should be copied to the clipboard
+    fn handle_key(&mut self, key: Key) -> Option<String> {
+        match std::mem::replace(&mut self.mode, Mode::List) {
+            Mode::List => self.handle_list_key(key),
+            Mode::Filter => {
+                self.handle_filter_key(key);
+                None
+            }
+            Mode::Dialog(dialog) => self.handle_dialog_key(dialog, key),
+        }
+    }
+
+    fn handle_list_key(&mut self, key: Key) -> Option<String> {
+        let selected = self.selected_entry().map(|entry| entry.key.clone());
+
+        match (key, selected) {
+            (Key::Char('q'), _) | (Key::Esc, _) => self.quit = true,
+            (Key::Up, _) | (Key::Char('k'), _) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            (Key::Down, _) | (Key::Char('j'), _) if self.selected + 1 < self.visible.len() => {
+                self.selected += 1;
+            }
+            (Key::Char('/'), _) => self.mode = Mode::Filter,
+            (Key::Char('a'), _) => self.open_add_dialog(),
+            (Key::Char('g'), _) => {
+                self.mode = Mode::Dialog(Dialog {
+                    title: String::from("Generate password"),
+                    action: Action::Generate,
+                    fields: vec![Field::new("Length", &GENERATED_LENGTH.to_string())],
+                    focus: 0,
+                })
+            }
+            (Key::Char('l'), _) => {
+                self.master_password = None;
+                self.unlocked = None;
+                self.revealed = None;
+                self.status = String::from("Vault was locked.");
+            }
+            (Key::Enter, Some(domain)) => return self.unlock(Action::Reveal(domain)),
+            (Key::Char('c'), Some(domain)) => return self.unlock(Action::CopyPassword(domain)),
+            (Key::Char('u'), Some(domain)) => {
+                let item = self.items.iter().find(|item| item.key == domain)?;
+                self.status = format!("Username for {}", domain);
+                return Some(item.username.clone());
+            }
+            (Key::Char('e'), Some(domain)) => self.open_edit_dialog(&domain),
+            (Key::Char('d'), Some(domain)) => {
+                self.mode = Mode::Dialog(Dialog {
+                    title: format!("Remove {}? Press Enter to confirm.", domain),
+                    action: Action::Remove(domain),
+                    fields: vec![Field::secret(
+                        "Master password",
+                        &self.master_password.clone().unwrap_or_default(),
+                    )],
+                    focus: 0,
+                })
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn handle_filter_key(&mut self, key: Key) {
+        match key {
+            Key::Enter => return,
+            Key::Esc => self.query.clear(),
+            Key::Backspace => {
+                self.query.pop();
+            }
+            Key::Char(c) => self.query.push(c),
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down if self.selected + 1 < self.visible.len() => self.selected += 1,
+            _ => {}
+        }
+
+        self.apply_filter();
+
+        if key != Key::Esc {
+            self.mode = Mode::Filter;
+        }
+    }
+
+    fn handle_dialog_key(&mut self, mut dialog: Dialog, key: Key) -> Option<String> {
+        let field_count = dialog.fields.len().max(1);
+
+        match key {
+            Key::Esc => return None,
+            Key::Enter => return self.submit(dialog),
+            Key::Tab | Key::Down => dialog.focus = (dialog.focus + 1) % field_count,
+            Key::BackTab | Key::Up => dialog.focus = (dialog.focus + field_count - 1) % field_count,
+            Key::Backspace => {
+                if let Some(field) = dialog.fields.get_mut(dialog.focus) {
+                    field.value.pop();
+                }
+            }
+            Key::Ctrl('g') => {
+                if let Some(field) = dialog.fields.get_mut(dialog.focus) {
+                    if field.secret && field.label != "Master password" {
+                        field.value = command::generate_password(GENERATED_LENGTH);
+                        self.status = String::from("Generated password was filled in.");
+                    }
+                }
+            }
+            Key::Char(c) => {
+                if let Some(field) = dialog.fields.get_mut(dialog.focus) {
+                    field.value.push(c);
+                }
+            }
+            _ => {}
+        }
+
+        self.mode = Mode::Dialog(dialog);
+        None
+    }
+
+    // Runs the action right away if the session is unlocked, otherwise asks for master password
+    fn unlock(&mut self, action: Action) -> Option<String> {
+        if let Some(master_password) = self.master_password.clone() {
+            let dialog = Dialog {
+                title: String::new(),
+                action,
+                fields: vec![Field::secret("Master password", &master_password)],
+                focus: 0,
+            };
+
+            return self.submit(dialog);
+        }
+
+        self.mode = Mode::Dialog(Dialog {
+            title: String::from("Unlock"),
+            action,
+            fields: vec![Field::secret("Master password", "")],
+            focus: 0,
+        });
+
+        None
+    }
+
+    fn open_add_dialog(&mut self) {
+        let master_password = self.master_password.clone().unwrap_or_default();
+
+        self.mode = Mode::Dialog(Dialog {
+            title: String::from("Add entry"),
+            action: Action::Add,
+            fields: vec![
+                Field::new("Domain", ""),
+                Field::new("Username", ""),
+                Field::new("URL", ""),
+                Field::new("Tags", ""),
+                Field::new("Folder", ""),
+                Field::secret("Password", ""),
+                Field::secret("Master password", &master_password),
+            ],
+            focus: 0,
+        });
+    }
+
+    fn open_edit_dialog(&mut self, domain: &str) {
+        let (index_entry, item) = match (
+            self.index_set.find(domain),
+            self.items.iter().find(|item| item.key == domain),
+        ) {
+            (Some((_, index_entry)), Some(item)) => (index_entry, item),
+            _ => return,
+        };
+
+        let master_password = self.master_password.clone().unwrap_or_default();
+
+        self.mode = Mode::Dialog(Dialog {
+            title: format!("Edit {}", domain),
+            action: Action::Edit(String::from(domain)),
+            fields: vec![
+                Field::new("Username", &item.username),
+                Field::new("URL", &index_entry.url),
+                Field::new("Tags", &index_entry.tags.join(",")),
+                Field::new("Folder", &index_entry.folder),
+                Field::secret("New password", ""),
+                Field::secret("Master password", &master_password),
+            ],
+            focus: 0,
+        });
+    }
+
+    fn submit(&mut self, mut dialog: Dialog) -> Option<String> {
+        let values: Vec<String> = dialog.fields.iter().map(|f| f.value.clone()).collect();
+        let master_password = values.last().cloned().unwrap_or_default();
+
+        // Password of the session is used as it is. A typed one gets the key file mixed in
+        // and is verified, wrong passwords are counted.
+        let unlocked = match (&dialog.action, &self.unlocked) {
+            (Action::Generate, _) => Ok(String::new()),
+            (_, Some(unlocked)) if self.master_password.as_ref() == Some(&master_password) => {
+                Ok(unlocked.clone())
+            }
+            _ => keyfile::unlock(&master_password).and_then(command::try_master_password),
+        };
+        let session = unlocked.clone().ok();
+
+        let result = match (unlocked, &dialog.action) {
+            (Err(err), _) => Err(err),
+            (Ok(unlocked), Action::Reveal(domain) | Action::CopyPassword(domain)) => {
+                command::get_entry(domain, &unlocked).map(|(_, password)| {
+                    // Time of access and the log are best effort, the password is shown
+                    // either way
+                    let _ = command::mark_accessed(domain);
+                    let _ = log::record(Event::Get, domain, &unlocked);
+
+                    if let Action::CopyPassword(_) = dialog.action {
+                        self.status = format!("Password for {}", domain);
+                        return Some(password);
+                    }
+
+                    self.revealed = Some((domain.clone(), password));
+                    None
+                })
+            }
+            (Ok(unlocked), Action::Add) => {
+                let new_entry = NewEntry {
+                    domain: values[0].trim().to_string(),
+                    username: values[1].clone(),
+                    url: values[2].clone(),
+                    tags: index_entry::parse_tags(&values[3]),
+                    folder: values[4].clone(),
+                    password: values[5].clone(),
+                };
+
+                let inputs = [new_entry.domain.as_str(), new_entry.username.as_str()];
+
+                if new_entry.password.is_empty() {
+                    Err(String::from("Password can't be empty."))
+                } else {
+                    strength::check(&new_entry.password, &inputs).and_then(|warning| {
+                        command::add_entry(&new_entry, &unlocked).map(|()| {
+                            let _ = log::record(Event::Add, &new_entry.domain, &unlocked);
+                            self.reload();
+                            self.select(&new_entry.domain);
+                            self.status = with_warning(
+                                format!("{} was added to the vault.", new_entry.domain),
+                                warning,
+                            );
+                            None
+                        })
+                    })
+                }
+            }
+            (Ok(unlocked), Action::Edit(domain)) => {
+                // Fields left as they were are not sent, so they don't count as changes
+                let index_entry = self.index_set.find(domain).map(|(_, entry)| entry);
+                let item = self.items.iter().find(|item| item.key == *domain);
+                let mut tags = index_entry::parse_tags(&values[2]);
+                tags.sort();
+                tags.dedup();
+
+                let changes = EntryChanges {
+                    username: Some(values[0].clone())
+                        .filter(|u| Some(u) != item.map(|item| &item.username)),
+                    url: Some(values[1].clone()).filter(|u| Some(u) != index_entry.map(|e| &e.url)),
+                    tags: Some(tags).filter(|t| Some(t) != index_entry.map(|e| &e.tags)),
+                    folder: Some(index_entry::normalize_folder(&values[3]))
+                        .filter(|f| Some(f) != index_entry.map(|e| &e.folder)),
+                    password: Some(values[4].clone()).filter(|p| !p.is_empty()),
+                };
+
+                let warning = match &changes.password {
+                    Some(password) => strength::check(password, &[domain, &values[0]]),
+                    None => Ok(None),
+                };
+
+                warning.and_then(|warning| {
+                    command::edit_entry(domain, &changes, &unlocked).map(|()| {
+                        let _ = log::record(Event::Edit, domain, &unlocked);
+                        self.reload();
+                        self.revealed = None;
+                        self.status = with_warning(format!("{} was updated.", domain), warning);
+                        None
+                    })
+                })
+            }
+            (Ok(unlocked), Action::Remove(domain)) => backup::create(&unlocked, "remove")
+                .and_then(|_| command::remove_entry(domain, &unlocked))
+                .map(|()| {
+                    let _ = log::record(Event::Remove, domain, &unlocked);
+                    self.reload();
+                    self.revealed = None;
+                    self.status = format!("{} was deleted from the vault.", domain);
+                    None
+                }),
+            (Ok(_), Action::Generate) => match values[0].trim().parse::<u8>() {
+                Ok(len) => {
+                    // Password goes only to the clipboard, the screen and scrollback keep a mask
+                    let password = command::generate_password(len);
+                    self.status = format!("Generated password {}", HIDDEN_PASSWORD);
+                    Ok(Some(password))
+                }
+                Err(_) => Err(String::from("Length must be a number between 0 and 255")),
+            },
+        };
+
+        match result {
+            Ok(copy) => {
+                let unlocked = matches!(
+                    dialog.action,
+                    Action::Reveal(_)
+                        | Action::CopyPassword(_)
+                        | Action::Add
+                        | Action::Edit(_)
+                        | Action::Remove(_)
+                );
+                if unlocked {
+                    self.master_password = Some(master_password);
+                    self.unlocked = session;
+                }
+
+                copy
+            }
+            Err(err) => {
+                self.status = err;
+
+                if let Some(field) = dialog.fields.last_mut() {
+                    if field.label == "Master password" {
+                        field.value.clear();
+                        dialog.focus = dialog.fields.len() - 1;
+                    }
+                }
+
+                self.master_password = None;
+                self.unlocked = None;
+                self.mode = Mode::Dialog(dialog);
+                None
+            }
+        }
+    }
+
+    fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let list_width = (width / 3).max(16).min(width);
+        let detail_width = width.saturating_sub(list_width + 3);
+        let body_height = height.saturating_sub(4);
+
+        let mut header = format!(" Dome - {} entries", self.index_set.size);
+        if !self.query.is_empty() || matches!(self.mode, Mode::Filter) {
+            header.push_str(&format!("   Search: {}", self.query));
+        }
+
+        let detail = match &self.mode {
+            Mode::Dialog(dialog) => self.render_dialog(dialog),
+            _ => self.render_detail(),
+        };
+
+        let mut lines = vec![fit(&header, width), "-".repeat(width)];
+
+        let offset = self.selected.saturating_sub(body_height.saturating_sub(1));
+        for row in 0..body_height {
+            let item = match self.visible.get(offset + row) {
+                Some(key) if offset + row == self.selected => format!("> {}", key),
+                Some(key) => format!("  {}", key),
+                None => String::new(),
+            };
+            let detail_line = detail.get(row).map(|s| s.as_str()).unwrap_or("");
+
+            lines.push(format!(
+                "{} | {}",
+                fit(&item, list_width),
+                fit(detail_line, detail_width)
+            ));
+        }
+
+        let help = match self.mode {
+            Mode::List => "/ search  enter show  c copy  u copy user  a add  e edit  d remove  g generate  l lock  q quit",
+            Mode::Filter => "type to search  enter done  esc clear",
+            Mode::Dialog(_) => "enter confirm  esc cancel  tab next field  ctrl+g generate password",
+        };
+
+        lines.push(fit(&format!(" {}", self.status), width));
+        lines.push(fit(&format!(" {}", help), width));
+        lines.truncate(height);
+        lines
+    }
+
+    fn render_detail(&self) -> Vec<String> {
+        let entry = match self.selected_entry() {
+            Some(entry) => entry,
+            None => return vec![String::from("Vault is empty. Press 'a' to add new entry.")],
+        };
+
+        let username = self
+            .items
+            .iter()
+            .find(|item| item.key == entry.key)
+            .map(|item| item.username.as_str())
+            .unwrap_or("");
+
+        let password = match &self.revealed {
+            Some((domain, password)) if domain == &entry.key => password.as_str(),
+            _ => HIDDEN_PASSWORD,
+        };
+
+        vec![
+            format!("Domain:   {}", entry.key),
+            format!("Username: {}", username),
+            format!("URL:      {}", entry.url),
+            format!("Folder:   {}", entry.folder),
+            format!("Tags:     {}", entry.tags.join(", ")),
+            format!("Password: {}", password),
+        ]
+    }
+
+    fn render_dialog(&self, dialog: &Dialog) -> Vec<String> {
+        let mut lines = vec![format!("[ {} ]", dialog.title), String::new()];
+
+        for (index, field) in dialog.fields.iter().enumerate() {
+            let marker = if index == dialog.focus { ">" } else { " " };
+            let value = if field.secret {
+                "*".repeat(field.value.chars().count())
+            } else {
+                field.value.clone()
+            };
+
+            lines.push(format!(
+                "{} {:<16} {}",
+                marker,
+                format!("{}:", field.label),
+                value
+            ));
+        }
+
+        lines
+    }
+}
+
+// Pads or cuts the text to exactly given number of characters
+fn fit(text: &str, width: usize) -> String {
+    let mut result: String = text.chars().take(width).collect();
+    let len = result.chars().count();
+    result.push_str(&" ".repeat(width - len));
+    result
+}
+
+// Status line followed by the strength warning, if there is one
+fn with_warning(status: String, warning: Option<String>) -> String {
+    match warning {
+        Some(warning) => format!("{} {}", status, warning),
+        None => status,
+    }
+}
+
+#[cfg(test)]
+mod test;
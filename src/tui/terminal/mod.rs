@@ -0,0 +1,85 @@
+use super::{Backend, Key};
+use crate::cli;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue, style::Print};
+use std::io::{self, Write};
+
+// Full-screen backend over the real terminal. Terminal is restored when dropped.
+pub struct TerminalBackend {
+    stdout: io::Stdout,
+}
+
+impl TerminalBackend {
+    pub fn new() -> Result<TerminalBackend, String> {
+        let mut stdout = io::stdout();
+
+        terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+        execute!(stdout, EnterAlternateScreen, Hide).map_err(|e| e.to_string())?;
+
+        Ok(TerminalBackend { stdout })
+    }
+}
+
+impl Drop for TerminalBackend {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn next_key(&mut self) -> Option<Key> {
+        loop {
+            let key_event = match event::read().ok()? {
+                Event::Key(key_event) if key_event.kind != KeyEventKind::Release => key_event,
+                Event::Resize(_, _) => return Some(Key::Resize),
+                _ => continue,
+            };
+
+            let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+
+            let key = match key_event.code {
+                KeyCode::Char('c') if ctrl => Key::Esc,
+                KeyCode::Char(c) if ctrl => Key::Ctrl(c),
+                KeyCode::Char(c) => Key::Char(c),
+                KeyCode::Enter => Key::Enter,
+                KeyCode::Esc => Key::Esc,
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::Tab => Key::Tab,
+                KeyCode::BackTab => Key::BackTab,
+                _ => continue,
+            };
+
+            return Some(key);
+        }
+    }
+
+    fn draw(&mut self, lines: &[String]) {
+        for (row, line) in lines.iter().enumerate() {
+            let _ = queue!(
+                self.stdout,
+                MoveTo(0, row as u16),
+                Print(line),
+                Clear(ClearType::UntilNewLine)
+            );
+        }
+
+        let _ = queue!(self.stdout, Clear(ClearType::FromCursorDown));
+        let _ = self.stdout.flush();
+    }
+
+    fn size(&self) -> (usize, usize) {
+        match terminal::size() {
+            Ok((width, height)) => (width as usize, height as usize),
+            Err(_) => (80, 24),
+        }
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        cli::copy_to_clipboard(text)
+    }
+}
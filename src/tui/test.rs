@@ -0,0 +1,250 @@
+use super::*;
+use crate::storage;
+use crate::time;
+use std::collections::VecDeque;
+
+const MASTER_PASSWORD: &str = "mAste&rPass123word";
+
+struct ScriptedBackend {
+    keys: VecDeque<Key>,
+    frames: Vec<Vec<String>>,
+    clipboard: Vec<String>,
+}
+
+impl ScriptedBackend {
+    fn new(keys: Vec<Key>) -> ScriptedBackend {
+        ScriptedBackend {
+            keys: VecDeque::from(keys),
+            frames: Vec::new(),
+            clipboard: Vec::new(),
+        }
+    }
+
+    fn last_frame(&self) -> String {
+        self.frames.last().unwrap().join("\n")
+    }
+}
+
+impl Backend for ScriptedBackend {
+    fn next_key(&mut self) -> Option<Key> {
+        self.keys.pop_front()
+    }
+
+    fn draw(&mut self, lines: &[String]) {
+        self.frames.push(lines.to_vec());
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (100, 20)
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        self.clipboard.push(String::from(text));
+        Ok(())
+    }
+}
+
+fn typed(text: &str) -> Vec<Key> {
+    text.chars().map(Key::Char).collect()
+}
+
+fn prepare_vault() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    storage::use_dir(dir.path());
+
+    for (domain, username, tags) in [("github", "johndoe", "work"), ("gmail", "john", "mail")] {
+        let new_entry = NewEntry {
+            domain: String::from(domain),
+            username: String::from(username),
+            password: format!("{}-password", domain),
+            url: String::new(),
+            tags: index_entry::parse_tags(tags),
+            folder: String::new(),
+        };
+        command::add_entry(&new_entry, MASTER_PASSWORD).unwrap();
+    }
+
+    dir
+}
+
+#[test]
+fn test_reveal_and_copy() {
+    let _dir = prepare_vault();
+
+    let mut keys = vec![Key::Down, Key::Enter];
+    keys.extend(typed(MASTER_PASSWORD));
+    keys.extend([Key::Enter, Key::Char('c')]);
+
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert!(backend.last_frame().contains("Password: gmail-password"));
+    assert_eq!(backend.clipboard, vec!["gmail-password"]);
+}
+
+#[test]
+fn test_wrong_master_password() {
+    let _dir = prepare_vault();
+
+    let mut keys = vec![Key::Char('c')];
+    keys.extend(typed("wrong"));
+    keys.push(Key::Enter);
+
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert!(backend
+        .last_frame()
+        .contains("Master password is not correct"));
+    assert!(backend.clipboard.is_empty());
+}
+
+#[test]
+fn test_search() {
+    let _dir = prepare_vault();
+
+    let mut keys = vec![Key::Char('/')];
+    keys.extend(typed("mail"));
+    keys.extend([Key::Enter, Key::Char('u')]);
+
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    let frame = backend.last_frame();
+    assert!(frame.contains("> gmail"));
+    assert!(!frame.contains("github"));
+    assert_eq!(backend.clipboard, vec!["john"]);
+}
+
+#[test]
+fn test_add_edit_remove() {
+    let _dir = prepare_vault();
+
+    let mut keys = vec![Key::Char('a')];
+    keys.extend(typed("gitlab"));
+    keys.push(Key::Tab);
+    keys.extend(typed("jdoe"));
+    keys.extend([Key::Tab, Key::Tab]);
+    keys.extend(typed("work,dev"));
+    keys.extend([Key::Tab, Key::Tab, Key::Ctrl('g'), Key::Tab]);
+    keys.extend(typed(MASTER_PASSWORD));
+    keys.push(Key::Enter);
+
+    // Edit username of the new entry, master password is remembered from adding it
+    keys.push(Key::Char('e'));
+    keys.extend([Key::Backspace; 4]);
+    keys.extend(typed("jane"));
+    keys.push(Key::Enter);
+
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert!(backend.last_frame().contains("gitlab was updated"));
+    let (entry, password) = command::get_entry("gitlab", MASTER_PASSWORD).unwrap();
+    assert_eq!(entry.username, "jane");
+    assert_eq!(password.len(), GENERATED_LENGTH as usize);
+    assert_eq!(command::get_index_set().unwrap().with_tag("dev").len(), 1);
+
+    // New session doesn't remember the master password, removing asks for it
+    let mut keys = vec![Key::Char('d')];
+    keys.extend(typed(MASTER_PASSWORD));
+    keys.push(Key::Enter);
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert!(!command::get_index_set().unwrap().has("github"));
+    assert!(backend.last_frame().contains("github was deleted"));
+}
+
+#[test]
+fn test_generate() {
+    let _dir = prepare_vault();
+
+    let keys = vec![
+        Key::Char('g'),
+        Key::Backspace,
+        Key::Backspace,
+        Key::Char('2'),
+        Key::Char('4'),
+        Key::Enter,
+        Key::Char('q'),
+    ];
+
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert_eq!(backend.clipboard.len(), 1);
+    assert_eq!(backend.clipboard[0].len(), 24);
+
+    // Generated password is only in the clipboard
+    let shown = backend
+        .frames
+        .iter()
+        .flatten()
+        .any(|line| line.contains(&backend.clipboard[0]));
+    assert!(!shown);
+    assert!(backend
+        .last_frame()
+        .contains("Generated password ******** copied"));
+}
+
+#[test]
+fn test_edit_tags_keeps_modified() {
+    let _dir = prepare_vault();
+    let before = command::get_index_set()
+        .unwrap()
+        .find("github")
+        .unwrap()
+        .1
+        .clone();
+
+    // Only the tags are changed, github is the first entry
+    let mut keys = vec![Key::Char('e'), Key::Tab, Key::Tab];
+    keys.extend(typed(",dev"));
+    keys.extend([Key::Tab, Key::Tab, Key::Tab]);
+    keys.extend(typed(MASTER_PASSWORD));
+    keys.push(Key::Enter);
+    let mut backend = ScriptedBackend::new(keys);
+    run(&mut backend);
+
+    assert!(backend.last_frame().contains("github was updated"));
+    let after = command::get_index_set()
+        .unwrap()
+        .find("github")
+        .unwrap()
+        .1
+        .clone();
+    assert_eq!(after.tags, vec!["dev", "work"]);
+    assert_eq!(after.modified, before.modified);
+    assert_eq!(after.id, before.id);
+}
+
+#[test]
+fn test_session_is_verified_once() {
+    let _dir = prepare_vault();
+    let mut app = App::new();
+
+    app.handle_key(Key::Enter);
+    for key in typed(MASTER_PASSWORD) {
+        app.handle_key(key);
+    }
+    app.handle_key(Key::Enter);
+    assert!(app.unlocked.is_some());
+
+    // Typed password would have to wait now, the session doesn't ask for it again
+    let attempts = format!("10 {}\n", time::now());
+    std::fs::write(storage::attempts_file_path(), attempts).unwrap();
+    assert_eq!(
+        app.handle_key(Key::Char('c')),
+        Some(String::from("github-password"))
+    );
+
+    // Locked session verifies the password again
+    app.handle_key(Key::Char('l'));
+    app.handle_key(Key::Char('c'));
+    for key in typed(MASTER_PASSWORD) {
+        app.handle_key(key);
+    }
+    assert_eq!(app.handle_key(Key::Enter), None);
+    assert!(app.status.contains("Try again"));
+}